@@ -1,12 +1,19 @@
+use crate::config::assert_not_paused;
 use crate::contract::{query_total_issued, slashing};
 use crate::math::decimal_division;
-use crate::state::{CONFIG, CURRENT_BATCH, PARAMETERS, STATE};
-use basset::hub::State;
+use crate::state::{CONFIG, CURRENT_BATCH, NATIVE_TOKEN, PARAMETERS, STATE};
+use basset::contract_error::ContractError;
+use basset::hub::{Config, PausableOperation, State};
+use basset::reward::{
+    ConfigResponse as RewardConfigResponse, ExecuteMsg as RewardExecuteMsg,
+    QueryMsg as RewardQueryMsg,
+};
+use basset::token_factory::mint_msg;
 use cosmwasm_std::Coin;
 use cosmwasm_std::QueryRequest;
 use cosmwasm_std::{
-    attr, to_binary, CosmosMsg, DepsMut, Env, MessageInfo, Response, StakingMsg, StdError,
-    StdResult, Uint128, WasmMsg, WasmQuery,
+    attr, to_binary, BankMsg, CosmosMsg, Decimal, Deps, DepsMut, Env, MessageInfo, Response,
+    StakingMsg, StdResult, Uint128, WasmMsg, WasmQuery,
 };
 use cw20::Cw20ExecuteMsg;
 
@@ -14,7 +21,86 @@ use lido_terra_validators_registry::common::calculate_delegations;
 use lido_terra_validators_registry::msg::QueryMsg as QueryValidators;
 use lido_terra_validators_registry::registry::ValidatorResponse;
 
-pub fn execute_bond(mut deps: DepsMut, env: Env, info: MessageInfo) -> StdResult<Response> {
+pub fn execute_bond(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let sender = info.sender.clone();
+    let (payment, mint_amount, mut messages) = bond(deps.branch(), env, info)?;
+
+    // issue the basset token for sender
+    let mint_msg = Cw20ExecuteMsg::Mint {
+        recipient: sender.to_string(),
+        amount: mint_amount,
+    };
+
+    let config = CONFIG.load(deps.storage)?;
+    let token_address = config
+        .token_contract
+        .ok_or(ContractError::MissingRegisteredContract {
+            contract: "token".to_string(),
+        })?
+        .to_string();
+
+    messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: token_address,
+        msg: to_binary(&mint_msg)?,
+        funds: vec![],
+    }));
+
+    Ok(Response::new().add_messages(messages).add_attributes(vec![
+        attr("action", "mint"),
+        attr("from", sender),
+        attr("bonded", payment.amount),
+        attr("minted", mint_amount),
+    ]))
+}
+
+/// Bond like `execute_bond`, issuing the native token of the hub instead of the CW20 token.
+/// The minted bAsset is converted to native tokens at the native token rate
+pub fn execute_bond_native(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let mut native_token = NATIVE_TOKEN
+        .may_load(deps.storage)?
+        .ok_or(ContractError::NoNativeToken {})?;
+
+    let sender = info.sender.clone();
+    let hub = env.contract.address.to_string();
+    let (payment, mint_amount, mut messages) = bond(deps.branch(), env, info)?;
+
+    let native_amount = decimal_division(mint_amount, native_token.basset_rate);
+    native_token.supply += native_amount;
+    NATIVE_TOKEN.save(deps.storage, &native_token)?;
+
+    // the hub mints to itself, then sends the minted tokens
+    let minted = Coin::new(native_amount.u128(), native_token.denom);
+    messages.push(mint_msg(&hub, &minted));
+    messages.push(CosmosMsg::Bank(BankMsg::Send {
+        to_address: sender.to_string(),
+        amount: vec![minted],
+    }));
+
+    Ok(Response::new().add_messages(messages).add_attributes(vec![
+        attr("action", "mint_native"),
+        attr("from", sender),
+        attr("bonded", payment.amount),
+        attr("minted", native_amount),
+    ]))
+}
+
+/// Delegate the underlying coins sent along and update the exchange rate. Returns the payment,
+/// the amount of basset to issue for it and the delegation messages
+fn bond(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<(Coin, Uint128, Vec<CosmosMsg>), ContractError> {
+    assert_not_paused(deps.storage, PausableOperation::Bond)?;
+
     let params = PARAMETERS.load(deps.storage)?;
     let coin_denom = params.underlying_coin_denom;
     let threshold = params.er_threshold;
@@ -27,24 +113,21 @@ pub fn execute_bond(mut deps: DepsMut, env: Env, info: MessageInfo) -> StdResult
 
     // coin must have be sent along with transaction and it should be in underlying coin denom
     if info.funds.len() > 1usize {
-        return Err(StdError::generic_err(
-            "More than one coin is sent; only one asset is supported",
-        ));
+        return Err(ContractError::MultipleCoins {});
     }
 
     let payment = info
         .funds
         .iter()
         .find(|x| x.denom == coin_denom && x.amount > Uint128::zero())
-        .ok_or_else(|| {
-            StdError::generic_err(format!("No {} assets are provided to bond", coin_denom))
+        .ok_or_else(|| ContractError::WrongDenom {
+            denom: coin_denom.clone(),
         })?;
 
     // check slashing
     slashing(&mut deps, env)?;
 
     let state = STATE.load(deps.storage)?;
-    let sender = info.sender;
 
     // get the total supply
     let mut total_supply = query_total_issued(deps.as_ref()).unwrap_or_default();
@@ -70,12 +153,167 @@ pub fn execute_bond(mut deps: DepsMut, env: Env, info: MessageInfo) -> StdResult
         Ok(prev_state)
     })?;
 
-    let validators_registry_contract = if let Some(v) = config.validators_registry_contract {
+    let (remaining_buffered_balance, delegate_msgs) =
+        registry_delegations(deps.as_ref(), &config, payment)?;
+    // every bonded coin must be delegated, otherwise it would be accounted as slashed
+    if !remaining_buffered_balance.is_zero() {
+        return Err(ContractError::NoDelegationCapacity {
+            amount: remaining_buffered_balance,
+        });
+    }
+
+    Ok((payment.clone(), mint_amount_with_fee, delegate_msgs))
+}
+
+/// Restake the share of the native token and the compounded share of the underlying rewards
+/// withdrawn to the hub, and send the rest of the withdrawn rewards to the reward contract.
+/// Only the hub itself is allowed to execute
+pub fn execute_compound_rewards(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    prev_balances: Vec<Coin>,
+) -> Result<Response, ContractError> {
+    if info.sender != env.contract.address {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let params = PARAMETERS.load(deps.storage)?;
+    let config = CONFIG.load(deps.storage)?;
+    let reward_address =
+        config
+            .reward_contract
+            .clone()
+            .ok_or(ContractError::MissingRegisteredContract {
+                contract: "reward".to_string(),
+            })?;
+
+    // the rewards are whatever the hub received since the withdrawal
+    let mut rewards: Vec<Coin> = vec![];
+    for balance in deps.querier.query_all_balances(env.contract.address)? {
+        let prev_amount = prev_balances
+            .iter()
+            .find(|prev_balance| prev_balance.denom == balance.denom)
+            .map(|prev_balance| prev_balance.amount)
+            .unwrap_or_default();
+        let amount = balance.amount.saturating_sub(prev_amount);
+        if !amount.is_zero() {
+            rewards.push(Coin::new(amount.u128(), balance.denom));
+        }
+    }
+
+    // the compounded share of the underlying rewards goes to every bAsset through the exchange
+    // rate. The native token holders don't go through the reward contract, so their share of
+    // the rest is restaked for them as well
+    let mut native_token = NATIVE_TOKEN.may_load(deps.storage)?;
+    let total_issued = query_total_issued(deps.as_ref())?;
+    let native_share = |amount: Uint128| match &native_token {
+        Some(native_token) if !total_issued.is_zero() => (amount - amount * params.compound_ratio)
+            .multiply_ratio(native_token.basset_supply(), total_issued),
+        _ => Uint128::zero(),
+    };
+
+    let mut compounded = Uint128::zero();
+    let mut native_compounded = Uint128::zero();
+    let mut protocol_fee = Uint128::zero();
+    let mut messages: Vec<CosmosMsg> = vec![];
+    if let Some(underlying_rewards) = rewards.iter_mut().find(|reward| {
+        reward.denom == params.underlying_coin_denom
+            && !(reward.amount * params.compound_ratio + native_share(reward.amount)).is_zero()
+    }) {
+        let native_rewards = native_share(underlying_rewards.amount);
+        let to_compound = underlying_rewards.amount * params.compound_ratio + native_rewards;
+
+        // the compounded rewards pay the protocol fee of the reward contract like the others
+        let reward_config: RewardConfigResponse = deps
+            .querier
+            .query_wasm_smart(&reward_address, &RewardQueryMsg::Config {})?;
+        if reward_config.fee_collector.is_some() {
+            protocol_fee = to_compound.multiply_ratio(reward_config.protocol_fee_bps, 10_000u128);
+        }
+
+        let to_delegate = to_compound - protocol_fee;
+        if !to_delegate.is_zero() {
+            let (remaining, mut delegate_msgs) = registry_delegations(
+                deps.as_ref(),
+                &config,
+                &Coin::new(to_delegate.u128(), underlying_rewards.denom.clone()),
+            )?;
+            // what the validators have no capacity for goes to the reward contract, which
+            // takes the fee of that part
+            protocol_fee -= protocol_fee.multiply_ratio(remaining, to_delegate);
+            compounded = to_delegate - remaining;
+            native_compounded = compounded.multiply_ratio(native_rewards, to_compound);
+            messages.append(&mut delegate_msgs);
+        }
+        underlying_rewards.amount -= compounded + protocol_fee;
+
+        // the reward contract sends the fee to its collector and accounts for it
+        if !protocol_fee.is_zero() {
+            messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: reward_address.to_string(),
+                msg: to_binary(&RewardExecuteMsg::CollectCompoundProtocolFee {})?,
+                funds: vec![Coin::new(
+                    protocol_fee.u128(),
+                    underlying_rewards.denom.clone(),
+                )],
+            }));
+        }
+    }
+
+    if !compounded.is_zero() {
+        let requested_with_fee = CURRENT_BATCH.load(deps.storage)?.requested_with_fee;
+        let mut state = STATE.load(deps.storage)?;
+        state.total_bond_amount += compounded - native_compounded;
+        state.update_exchange_rate(total_issued, requested_with_fee);
+
+        // the native token then stands for the bAsset its share buys at the new exchange rate
+        if let Some(native_token) = native_token.as_mut() {
+            if !native_compounded.is_zero() {
+                native_token.basset_rate += Decimal::from_ratio(
+                    decimal_division(native_compounded, state.exchange_rate),
+                    native_token.supply,
+                );
+                NATIVE_TOKEN.save(deps.storage, native_token)?;
+
+                state.total_bond_amount += native_compounded;
+                state.update_exchange_rate(query_total_issued(deps.as_ref())?, requested_with_fee);
+            }
+        }
+        STATE.save(deps.storage, &state)?;
+    }
+
+    rewards.retain(|reward| !reward.amount.is_zero());
+    if !rewards.is_empty() {
+        messages.push(CosmosMsg::Bank(BankMsg::Send {
+            to_address: reward_address.to_string(),
+            amount: rewards,
+        }));
+    }
+
+    let state = STATE.load(deps.storage)?;
+    Ok(Response::new().add_messages(messages).add_attributes(vec![
+        attr("action", "compound_rewards"),
+        attr("compounded", compounded),
+        attr("native_compounded", native_compounded),
+        attr("protocol_fee", protocol_fee),
+        attr("new_exchange_rate", state.exchange_rate.to_string()),
+    ]))
+}
+
+/// Splits `amount` between the validators of the registry. Returns the amount the validators
+/// have no capacity left for, along with the delegation messages
+fn registry_delegations(
+    deps: Deps,
+    config: &Config,
+    amount: &Coin,
+) -> Result<(Uint128, Vec<CosmosMsg>), ContractError> {
+    let validators_registry_contract = if let Some(v) = &config.validators_registry_contract {
         v
     } else {
-        return Err(StdError::generic_err(
-            "Validators registry contract address is empty",
-        ));
+        return Err(ContractError::MissingRegisteredContract {
+            contract: "validators registry".to_string(),
+        });
     };
     let validators: Vec<ValidatorResponse> =
         deps.querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
@@ -84,47 +322,22 @@ pub fn execute_bond(mut deps: DepsMut, env: Env, info: MessageInfo) -> StdResult
         }))?;
 
     if validators.is_empty() {
-        return Err(StdError::generic_err("Validators registry is empty"));
+        return Err(ContractError::EmptyRegistry {});
     }
 
-    let (_remaining_buffered_balance, delegations) =
-        calculate_delegations(payment.amount, validators.as_slice())?;
+    let (remaining_buffered_balance, delegations) =
+        calculate_delegations(amount.amount, validators.as_slice())?;
 
-    let mut external_call_msgs: Vec<cosmwasm_std::CosmosMsg> = vec![];
+    let mut messages: Vec<CosmosMsg> = vec![];
     for i in 0..delegations.len() {
         if delegations[i].is_zero() {
             continue;
         }
-        external_call_msgs.push(cosmwasm_std::CosmosMsg::Staking(StakingMsg::Delegate {
+        messages.push(CosmosMsg::Staking(StakingMsg::Delegate {
             validator: validators[i].address.clone(),
-            amount: Coin::new(delegations[i].u128(), payment.denom.as_str()),
+            amount: Coin::new(delegations[i].u128(), amount.denom.as_str()),
         }));
     }
 
-    // issue the basset token for sender
-    let mint_msg = Cw20ExecuteMsg::Mint {
-        recipient: sender.to_string(),
-        amount: mint_amount_with_fee,
-    };
-
-    let config = CONFIG.load(deps.storage)?;
-    let token_address = config
-        .token_contract
-        .ok_or_else(|| StdError::generic_err("the token contract must have been registered"))?
-        .to_string();
-
-    external_call_msgs.push(CosmosMsg::Wasm(WasmMsg::Execute {
-        contract_addr: token_address,
-        msg: to_binary(&mint_msg)?,
-        funds: vec![],
-    }));
-
-    Ok(Response::new()
-        .add_messages(external_call_msgs)
-        .add_attributes(vec![
-            attr("action", "mint"),
-            attr("from", sender),
-            attr("bonded", payment.amount),
-            attr("minted", mint_amount_with_fee),
-        ]))
+    Ok((remaining_buffered_balance, messages))
 }
@@ -1,8 +1,14 @@
-use crate::state::{Parameters, CONFIG, PARAMETERS};
-use basset::hub::Config;
+use crate::state::{
+    read_pause_status, Parameters, CONFIG, GUARDIAN, IBC_CHANNELS, NATIVE_TOKEN, PARAMETERS,
+    PAUSE_STATUS, PENDING_OWNER,
+};
+use basset::contract_error::ContractError;
+use basset::hub::{Config, NativeToken, PausableOperation};
+use basset::ownership::OwnershipProposal;
+use basset::token_factory::{create_denom_msg, native_denom};
 use cosmwasm_std::{
-    attr, CosmosMsg, Decimal, DepsMut, DistributionMsg, Env, MessageInfo, Response, StdError,
-    StdResult,
+    attr, CosmosMsg, Decimal, DepsMut, DistributionMsg, Empty, Env, MessageInfo, Response,
+    StdResult, Storage, Uint128,
 };
 
 /// Update general parameters
@@ -15,11 +21,12 @@ pub fn execute_update_params(
     epoch_period: Option<u64>,
     peg_recovery_fee: Option<Decimal>,
     er_threshold: Option<Decimal>,
-) -> StdResult<Response> {
+    compound_ratio: Option<Decimal>,
+) -> Result<Response, ContractError> {
     // only owner can send this message.
     let config = CONFIG.load(deps.storage)?;
     if info.sender != config.creator {
-        return Err(StdError::generic_err("unauthorized"));
+        return Err(ContractError::Unauthorized {});
     }
 
     let params: Parameters = PARAMETERS.load(deps.storage)?;
@@ -31,12 +38,14 @@ pub fn execute_update_params(
         peg_recovery_fee: peg_recovery_fee.unwrap_or(params.peg_recovery_fee),
         er_threshold: er_threshold.unwrap_or(params.er_threshold),
         reward_denom: params.reward_denom,
+        compound_ratio: compound_ratio.unwrap_or(params.compound_ratio),
     };
 
     if new_params.peg_recovery_fee > Decimal::one() {
-        return Err(StdError::generic_err(
-            "peg_recovery_fee can not be greater than 1",
-        ));
+        return Err(ContractError::InvalidPegRecoveryFee {});
+    }
+    if new_params.compound_ratio > Decimal::one() {
+        return Err(ContractError::InvalidCompoundRatio {});
     }
 
     PARAMETERS.save(deps.storage, &new_params)?;
@@ -44,34 +53,88 @@ pub fn execute_update_params(
     Ok(Response::new().add_attributes(vec![attr("action", "update_params")]))
 }
 
-/// Update the config. Update the owner, reward and token contracts.
+/// Create the token factory denom of the hub. It can only be created once
+/// Only creator/owner is allowed to execute
+pub fn execute_create_native_token(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    subdenom: String,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    if info.sender != config.creator {
+        return Err(ContractError::Unauthorized {});
+    }
+    if NATIVE_TOKEN.may_load(deps.storage)?.is_some() {
+        return Err(ContractError::NativeTokenAlreadyCreated {});
+    }
+
+    let hub = env.contract.address.to_string();
+    let denom = native_denom(&hub, &subdenom);
+    NATIVE_TOKEN.save(
+        deps.storage,
+        &NativeToken {
+            denom: denom.clone(),
+            supply: Uint128::zero(),
+            basset_rate: Decimal::one(),
+        },
+    )?;
+
+    Ok(Response::new()
+        .add_message(create_denom_msg(&hub, &subdenom))
+        .add_attributes(vec![
+            attr("action", "create_native_token"),
+            attr("denom", denom),
+        ]))
+}
+
+/// Allow or disallow withdrawals through the transfer channel `channel_id`.
+/// Only creator/owner is allowed to execute
+pub fn execute_update_ibc_channel(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    channel_id: String,
+    allowed: bool,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    if info.sender != config.creator {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let action = if allowed {
+        IBC_CHANNELS.save(deps.storage, &channel_id, &Empty {})?;
+        "allow_ibc_channel"
+    } else {
+        IBC_CHANNELS.remove(deps.storage, &channel_id);
+        "disallow_ibc_channel"
+    };
+
+    Ok(
+        Response::new()
+            .add_attributes(vec![attr("action", action), attr("channel_id", channel_id)]),
+    )
+}
+
+/// Update the config. Update the reward, token and validators registry contracts.
 /// Only creator/owner is allowed to execute
 pub fn execute_update_config(
     deps: DepsMut,
     _env: Env,
     info: MessageInfo,
-    owner: Option<String>,
     reward_contract: Option<String>,
     token_contract: Option<String>,
     validators_registry_contract: Option<String>,
     //airdrop_registry_contract: Option<String>,
-) -> StdResult<Response> {
+) -> Result<Response, ContractError> {
     // only owner must be able to send this message.
     let conf = CONFIG.load(deps.storage)?;
     if info.sender != conf.creator {
-        return Err(StdError::generic_err("unauthorized"));
+        return Err(ContractError::Unauthorized {});
     }
 
     let mut messages: Vec<CosmosMsg> = vec![];
 
-    if let Some(o) = owner {
-        let owner_raw = deps.api.addr_validate(o.as_str())?;
-
-        CONFIG.update(deps.storage, |mut last_config| -> StdResult<Config> {
-            last_config.creator = owner_raw;
-            Ok(last_config)
-        })?;
-    }
     if let Some(reward) = reward_contract {
         let reward_raw = deps.api.addr_validate(reward.as_str())?;
 
@@ -115,3 +178,173 @@ pub fn execute_update_config(
         .add_messages(messages)
         .add_attributes(vec![attr("action", "update_config")]))
 }
+
+/// Propose a new owner. The ownership transfer only happens once the new owner accepts it.
+/// Only creator/owner is allowed to execute
+pub fn execute_propose_new_owner(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    new_owner: String,
+    expires_in: Option<u64>,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    if info.sender != config.creator {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let proposal = OwnershipProposal {
+        owner: deps.api.addr_validate(&new_owner)?,
+        expiry: OwnershipProposal::expiry(env.block.time.seconds(), expires_in)?,
+    };
+    PENDING_OWNER.save(deps.storage, &proposal)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "propose_new_owner"),
+        attr("new_owner", new_owner),
+    ]))
+}
+
+/// Cancel the pending ownership transfer.
+/// Only creator/owner is allowed to execute
+pub fn execute_cancel_ownership_transfer(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    if info.sender != config.creator {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    PENDING_OWNER.remove(deps.storage);
+
+    Ok(Response::new().add_attributes(vec![attr("action", "cancel_ownership_transfer")]))
+}
+
+/// Accept the pending ownership transfer.
+/// Only the proposed owner is allowed to execute
+pub fn execute_accept_ownership(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let proposal = PENDING_OWNER
+        .may_load(deps.storage)?
+        .ok_or(ContractError::NoPendingOwner {})?;
+
+    if info.sender != proposal.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    if proposal.is_expired(env.block.time.seconds()) {
+        return Err(ContractError::OwnershipProposalExpired {});
+    }
+
+    CONFIG.update(deps.storage, |mut last_config| -> StdResult<Config> {
+        last_config.creator = proposal.owner.clone();
+        Ok(last_config)
+    })?;
+    PENDING_OWNER.remove(deps.storage);
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "accept_ownership"),
+        attr("new_owner", proposal.owner),
+    ]))
+}
+
+/// Set or remove the guardian.
+/// Only creator/owner is allowed to execute
+pub fn execute_update_guardian(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    guardian: Option<String>,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    if info.sender != config.creator {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    match guardian {
+        Some(g) => GUARDIAN.save(deps.storage, &deps.api.addr_validate(&g)?)?,
+        None => GUARDIAN.remove(deps.storage),
+    }
+
+    Ok(Response::new().add_attributes(vec![attr("action", "update_guardian")]))
+}
+
+/// Pause the given operations.
+/// Only creator/owner or the guardian is allowed to execute
+pub fn execute_pause(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    operations: Vec<PausableOperation>,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let guardian = GUARDIAN.may_load(deps.storage)?;
+    if info.sender != config.creator && Some(&info.sender) != guardian.as_ref() {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    set_paused(deps.storage, &operations, true)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "pause"),
+        attr("operations", format_operations(&operations)),
+    ]))
+}
+
+/// Resume the given operations.
+/// Only creator/owner is allowed to execute
+pub fn execute_unpause(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    operations: Vec<PausableOperation>,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    if info.sender != config.creator {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    set_paused(deps.storage, &operations, false)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "unpause"),
+        attr("operations", format_operations(&operations)),
+    ]))
+}
+
+/// Return an error if the operation has been paused by the owner or the guardian
+pub(crate) fn assert_not_paused(
+    storage: &dyn Storage,
+    operation: PausableOperation,
+) -> Result<(), ContractError> {
+    if read_pause_status(storage)?.is_paused(operation) {
+        return Err(ContractError::OperationPaused {
+            operation: operation.to_string(),
+        });
+    }
+    Ok(())
+}
+
+fn set_paused(
+    storage: &mut dyn Storage,
+    operations: &[PausableOperation],
+    paused: bool,
+) -> StdResult<()> {
+    let mut status = read_pause_status(storage)?;
+    for operation in operations {
+        status.set(*operation, paused);
+    }
+    PAUSE_STATUS.save(storage, &status)
+}
+
+fn format_operations(operations: &[PausableOperation]) -> String {
+    operations
+        .iter()
+        .map(|o| o.to_string())
+        .collect::<Vec<String>>()
+        .join(",")
+}
@@ -1,30 +1,51 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::Coin;
 use cosmwasm_std::{
-    attr, from_binary, to_binary, Addr, Binary, CosmosMsg, Decimal, Deps, DepsMut, DistributionMsg,
-    Env, MessageInfo, QueryRequest, Response, StakingMsg, StdError, StdResult, SubMsg, Uint128,
-    WasmMsg, WasmQuery,
+    attr, from_binary, to_binary, Addr, Binary, CosmosMsg, Decimal, Decimal256, Deps, DepsMut,
+    DistributionMsg, Env, MessageInfo, Order, QueryRequest, Reply, Response, StakingMsg, StdError,
+    StdResult, SubMsg, Uint128, WasmMsg, WasmQuery,
 };
 use cosmwasm_std::{entry_point, Empty};
 use lido_terra_validators_registry::msg::QueryMsg as ValidatorRegistryQueryMsg;
 use lido_terra_validators_registry::registry::ValidatorResponse;
 
-use crate::config::{execute_update_config, execute_update_params};
+use crate::config::{
+    assert_not_paused, execute_accept_ownership, execute_cancel_ownership_transfer,
+    execute_create_native_token, execute_pause, execute_propose_new_owner, execute_unpause,
+    execute_update_config, execute_update_guardian, execute_update_ibc_channel,
+    execute_update_params,
+};
 
 use crate::state::{
-    all_unbond_history, get_unbond_requests, query_get_finished_amount, CurrentBatch, Parameters,
-    CONFIG, CURRENT_BATCH, PARAMETERS, STATE,
+    all_unbond_history, get_unbond_requests, query_get_finished_amount,
+    read_exchange_rate_snapshots, read_pause_status, CurrentBatch, Parameters, CONFIG,
+    CURRENT_BATCH, EXCHANGE_RATE_SNAPSHOTS, GUARDIAN, IBC_CHANNELS, NATIVE_TOKEN, PARAMETERS,
+    PENDING_OWNER, STATE,
+};
+use crate::unbond::{
+    execute_instant_unbond, execute_unbond, execute_unbond_native, execute_withdraw_unbonded,
+    reply_ibc_transfer, sudo_ibc_lifecycle_complete,
 };
-use crate::unbond::{execute_unbond, execute_withdraw_unbonded};
 
-use crate::bond::execute_bond;
+use crate::bond::{execute_bond, execute_bond_native, execute_compound_rewards};
+use basset::contract_error::ContractError;
 use basset::hub::{
-    AllHistoryResponse, Config, ConfigResponse, CurrentBatchResponse, Cw20HookMsg, ExecuteMsg,
-    InstantiateMsg, QueryMsg, State, StateResponse, UnbondRequestsResponse,
-    WithdrawableUnbondedResponse,
+    AllHistoryResponse, AprResponse, Config, ConfigResponse, CurrentBatchResponse, Cw20HookMsg,
+    ExchangeRateHistoryResponse, ExchangeRateSnapshot, ExecuteMsg, InstantiateMsg,
+    NativeTokenResponse, PausableOperation, PauseStatusResponse, QueryMsg, State, StateResponse,
+    SudoMsg, UnbondRequestsResponse, WithdrawableUnbondedResponse,
 };
+use basset::ibc::IbcChannelsResponse;
+use basset::ownership::PendingOwnerResponse;
 use basset::reward::ExecuteMsg::{SwapToRewardDenom, UpdateGlobalIndex};
+use basset::reward::{QueryMsg as RewardQueryMsg, StateResponse as RewardStateResponse};
 use cw20::{Cw20QueryMsg, Cw20ReceiveMsg, TokenInfoResponse};
+use cw_storage_plus::Bound;
+
+const SECONDS_PER_YEAR: u64 = 31_536_000;
+
+/// Id of the IBC transfer submessages of the withdrawals
+pub const TRANSFER_REPLY_ID: u64 = 1;
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
@@ -32,7 +53,7 @@ pub fn instantiate(
     env: Env,
     info: MessageInfo,
     msg: InstantiateMsg,
-) -> StdResult<Response> {
+) -> Result<Response, ContractError> {
     // store config
     let data = Config {
         creator: info.sender,
@@ -54,9 +75,7 @@ pub fn instantiate(
     STATE.save(deps.storage, &state)?;
 
     if msg.peg_recovery_fee.gt(&Decimal::one()) {
-        return Err(StdError::generic_err(
-            "peg_recovery_fee can not be greater than 1",
-        ));
+        return Err(ContractError::InvalidPegRecoveryFee {});
     }
     // instantiate parameters
     let params = Parameters {
@@ -66,6 +85,7 @@ pub fn instantiate(
         peg_recovery_fee: msg.peg_recovery_fee,
         er_threshold: msg.er_threshold,
         reward_denom: msg.reward_denom,
+        compound_ratio: Decimal::zero(),
     };
 
     PARAMETERS.save(deps.storage, &params)?;
@@ -80,19 +100,31 @@ pub fn instantiate(
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn execute(deps: DepsMut, env: Env, info: MessageInfo, msg: ExecuteMsg) -> StdResult<Response> {
+pub fn execute(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: ExecuteMsg,
+) -> Result<Response, ContractError> {
     match msg {
         ExecuteMsg::Receive(msg) => receive_cw20(deps, env, info, msg),
         ExecuteMsg::Bond {} => execute_bond(deps, env, info),
+        ExecuteMsg::BondNative {} => execute_bond_native(deps, env, info),
+        ExecuteMsg::UnbondNative {} => execute_unbond_native(deps, env, info),
         ExecuteMsg::UpdateGlobalIndex {} => {
             execute_update_global(deps, env) //airdrop_hooks)
         }
-        ExecuteMsg::WithdrawUnbonded {} => execute_withdraw_unbonded(deps, env, info),
+        ExecuteMsg::WithdrawUnbonded {
+            recipient,
+            batch_ids,
+            ibc_transfer,
+        } => execute_withdraw_unbonded(deps, env, info, recipient, batch_ids, ibc_transfer),
         ExecuteMsg::CheckSlashing {} => execute_slashing(deps, env),
         ExecuteMsg::UpdateParams {
             epoch_period,
             peg_recovery_fee,
             er_threshold,
+            compound_ratio,
         } => execute_update_params(
             deps,
             env,
@@ -100,9 +132,9 @@ pub fn execute(deps: DepsMut, env: Env, info: MessageInfo, msg: ExecuteMsg) -> S
             epoch_period,
             peg_recovery_fee,
             er_threshold,
+            compound_ratio,
         ),
         ExecuteMsg::UpdateConfig {
-            owner,
             reward_contract,
             token_contract,
             validators_registry_contract,
@@ -111,16 +143,43 @@ pub fn execute(deps: DepsMut, env: Env, info: MessageInfo, msg: ExecuteMsg) -> S
             deps,
             env,
             info,
-            owner,
             reward_contract,
             token_contract,
             validators_registry_contract,
             //airdrop_registry_contract,
         ),
+        ExecuteMsg::ProposeNewOwner {
+            new_owner,
+            expires_in,
+        } => execute_propose_new_owner(deps, env, info, new_owner, expires_in),
+        ExecuteMsg::CancelOwnershipTransfer {} => {
+            execute_cancel_ownership_transfer(deps, env, info)
+        }
+        ExecuteMsg::AcceptOwnership {} => execute_accept_ownership(deps, env, info),
+        ExecuteMsg::UpdateGuardian { guardian } => {
+            execute_update_guardian(deps, env, info, guardian)
+        }
+        ExecuteMsg::Pause { operations } => execute_pause(deps, env, info, operations),
+        ExecuteMsg::Unpause { operations } => execute_unpause(deps, env, info, operations),
         ExecuteMsg::RedelegateProxy {
             src_validator,
             redelegations,
         } => execute_redelegate_proxy(deps, env, info, src_validator, redelegations),
+        ExecuteMsg::CompoundRewards { prev_balances } => {
+            execute_compound_rewards(deps, env, info, prev_balances)
+        }
+        ExecuteMsg::StoreExchangeRateSnapshot {} => {
+            execute_store_exchange_rate_snapshot(deps, env, info)
+        }
+        ExecuteMsg::AllowIbcChannel { channel_id } => {
+            execute_update_ibc_channel(deps, env, info, channel_id, true)
+        }
+        ExecuteMsg::DisallowIbcChannel { channel_id } => {
+            execute_update_ibc_channel(deps, env, info, channel_id, false)
+        }
+        ExecuteMsg::CreateNativeToken { subdenom } => {
+            execute_create_native_token(deps, env, info, subdenom)
+        }
     }
 }
 
@@ -130,15 +189,16 @@ pub fn execute_redelegate_proxy(
     info: MessageInfo,
     src_validator: String,
     redelegations: Vec<(String, Coin)>,
-) -> StdResult<Response> {
+) -> Result<Response, ContractError> {
     let conf = CONFIG.load(deps.storage)?;
-    let validators_registry_contract =
-        conf.validators_registry_contract.clone().ok_or_else(|| {
-            StdError::generic_err("the validator registry contract must have been registered")
-        })?;
+    let validators_registry_contract = conf.validators_registry_contract.clone().ok_or(
+        ContractError::MissingRegisteredContract {
+            contract: "validators registry".to_string(),
+        },
+    )?;
 
     if info.sender != validators_registry_contract && info.sender != conf.creator {
-        return Err(StdError::generic_err("unauthorized"));
+        return Err(ContractError::Unauthorized {});
     }
 
     let all_validators: Vec<ValidatorResponse> = deps.querier.query_wasm_smart(
@@ -149,13 +209,13 @@ pub fn execute_redelegate_proxy(
     let all_validators_addr: Vec<String> =
         all_validators.iter().map(|v| v.address.clone()).collect();
 
-    let messages: StdResult<Vec<CosmosMsg>> = redelegations
+    let messages: Result<Vec<CosmosMsg>, ContractError> = redelegations
         .into_iter()
         .map(|(dst_validator, amount)| {
             if !all_validators_addr.contains(&dst_validator) {
-                return Err(StdError::generic_err(
-                    "Validator must be registered in the registry before redelegating",
-                ));
+                return Err(ContractError::ValidatorNotRegistered {
+                    validator: dst_validator,
+                });
             }
             Ok(cosmwasm_std::CosmosMsg::Staking(StakingMsg::Redelegate {
                 src_validator: src_validator.clone(),
@@ -175,7 +235,7 @@ pub fn receive_cw20(
     env: Env,
     info: MessageInfo,
     cw20_msg: Cw20ReceiveMsg,
-) -> StdResult<Response> {
+) -> Result<Response, ContractError> {
     let contract_addr = info.sender.clone();
 
     match from_binary(&cw20_msg.msg) {
@@ -183,15 +243,31 @@ pub fn receive_cw20(
             // only token contract can execute this message
             let conf = CONFIG.load(deps.storage)?;
             if deps.api.addr_validate(contract_addr.as_str())?
-                != conf.token_contract.ok_or_else(|| {
-                    StdError::generic_err("the token contract must have been registered")
-                })?
+                != conf
+                    .token_contract
+                    .ok_or(ContractError::MissingRegisteredContract {
+                        contract: "token".to_string(),
+                    })?
             {
-                return Err(StdError::generic_err("unauthorized"));
+                return Err(ContractError::Unauthorized {});
             }
             execute_unbond(deps, env, info, cw20_msg.amount, cw20_msg.sender)
         }
-        Err(err) => Err(err),
+        Ok(Cw20HookMsg::InstantUnbond { min_receive }) => {
+            // only token contract can execute this message
+            let conf = CONFIG.load(deps.storage)?;
+            if deps.api.addr_validate(contract_addr.as_str())?
+                != conf
+                    .token_contract
+                    .ok_or(ContractError::MissingRegisteredContract {
+                        contract: "token".to_string(),
+                    })?
+            {
+                return Err(ContractError::Unauthorized {});
+            }
+            execute_instant_unbond(deps, env, cw20_msg.amount, cw20_msg.sender, min_receive)
+        }
+        Err(err) => Err(err.into()),
     }
 }
 
@@ -201,13 +277,17 @@ pub fn execute_update_global(
     deps: DepsMut,
     env: Env,
     //airdrop_hooks: Option<Vec<Binary>>,
-) -> StdResult<Response> {
+) -> Result<Response, ContractError> {
+    assert_not_paused(deps.storage, PausableOperation::UpdateGlobalIndex)?;
+
     let mut messages: Vec<SubMsg> = vec![];
 
     let config = CONFIG.load(deps.storage)?;
     let reward_addr = config
         .reward_contract
-        .ok_or_else(|| StdError::generic_err("the reward contract must have been registered"))?
+        .ok_or(ContractError::MissingRegisteredContract {
+            contract: "reward".to_string(),
+        })?
         .to_string();
 
     /*
@@ -225,7 +305,32 @@ pub fn execute_update_global(
 
     // Send withdraw message
     let mut withdraw_msgs = withdraw_all_rewards(&deps, env.contract.address.clone())?;
-    messages.append(&mut withdraw_msgs);
+    let params = PARAMETERS.load(deps.storage)?;
+    let native_supply = NATIVE_TOKEN
+        .may_load(deps.storage)?
+        .map(|native_token| native_token.supply)
+        .unwrap_or_default();
+    if params.compound_ratio.is_zero() && native_supply.is_zero() {
+        messages.append(&mut withdraw_msgs);
+    } else {
+        // the rewards are withdrawn to the hub, which restakes the share of the native token
+        // and the compounded share, and sends the rest to the reward contract
+        let prev_balances = deps
+            .querier
+            .query_all_balances(env.contract.address.clone())?;
+        messages.push(SubMsg::new(DistributionMsg::SetWithdrawAddress {
+            address: env.contract.address.to_string(),
+        }));
+        messages.append(&mut withdraw_msgs);
+        messages.push(SubMsg::new(DistributionMsg::SetWithdrawAddress {
+            address: reward_addr.clone(),
+        }));
+        messages.push(SubMsg::new(WasmMsg::Execute {
+            contract_addr: env.contract.address.to_string(),
+            msg: to_binary(&ExecuteMsg::CompoundRewards { prev_balances })?,
+            funds: vec![],
+        }));
+    }
 
     // Send Swap message to reward contract
     let swap_msg = SwapToRewardDenom {};
@@ -241,6 +346,13 @@ pub fn execute_update_global(
         funds: vec![],
     })));
 
+    // the snapshot is taken once the messages above are executed
+    messages.push(SubMsg::new(WasmMsg::Execute {
+        contract_addr: env.contract.address.to_string(),
+        msg: to_binary(&ExecuteMsg::StoreExchangeRateSnapshot {})?,
+        funds: vec![],
+    }));
+
     //update state last modified
     STATE.update(deps.storage, |mut last_state| -> StdResult<State> {
         last_state.last_index_modification = env.block.time.seconds();
@@ -252,6 +364,56 @@ pub fn execute_update_global(
         .add_attributes(vec![attr("action", "update_global_index")]))
 }
 
+/// Only the hub itself is allowed to execute
+pub fn execute_store_exchange_rate_snapshot(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    if info.sender != env.contract.address {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let state = STATE.load(deps.storage)?;
+    store_exchange_rate_snapshot(deps, env.block.time.seconds(), &state)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "store_exchange_rate_snapshot"),
+        attr("exchange_rate", state.exchange_rate.to_string()),
+    ]))
+}
+
+/// Record the exchange rate of `state` along with the global index of the reward contract
+pub(crate) fn store_exchange_rate_snapshot(
+    deps: DepsMut,
+    time: u64,
+    state: &State,
+) -> StdResult<()> {
+    let reward_index = query_reward_index(deps.as_ref())?;
+    EXCHANGE_RATE_SNAPSHOTS.save(
+        deps.storage,
+        time,
+        &ExchangeRateSnapshot {
+            time,
+            exchange_rate: state.exchange_rate,
+            total_bond_amount: state.total_bond_amount,
+            reward_index,
+        },
+    )
+}
+
+fn query_reward_index(deps: Deps) -> StdResult<Decimal256> {
+    match CONFIG.load(deps.storage)?.reward_contract {
+        Some(reward_contract) => {
+            let reward_state: RewardStateResponse = deps
+                .querier
+                .query_wasm_smart(reward_contract, &RewardQueryMsg::State {})?;
+            Ok(reward_state.global_index)
+        }
+        None => Ok(Decimal256::zero()),
+    }
+}
+
 /// Create withdraw requests for all validators
 fn withdraw_all_rewards(deps: &DepsMut, delegator: Addr) -> StdResult<Vec<SubMsg>> {
     let mut messages: Vec<SubMsg> = vec![];
@@ -272,7 +434,7 @@ fn withdraw_all_rewards(deps: &DepsMut, delegator: Addr) -> StdResult<Vec<SubMsg
 
 /// Check whether slashing has happened
 /// This is used for checking slashing while bonding or unbonding
-pub fn slashing(deps: &mut DepsMut, env: Env) -> StdResult<()> {
+pub fn slashing(deps: &mut DepsMut, env: Env) -> Result<(), ContractError> {
     //read params
     let params = PARAMETERS.load(deps.storage)?;
     let coin_denom = params.underlying_coin_denom;
@@ -310,7 +472,7 @@ pub fn slashing(deps: &mut DepsMut, env: Env) -> StdResult<()> {
 }
 
 /// Handler for tracking slashing
-pub fn execute_slashing(mut deps: DepsMut, env: Env) -> StdResult<Response> {
+pub fn execute_slashing(mut deps: DepsMut, env: Env) -> Result<Response, ContractError> {
     // call slashing
     slashing(&mut deps, env)?;
     // read state for log
@@ -335,24 +497,45 @@ pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
         QueryMsg::AllHistory { start_from, limit } => {
             to_binary(&query_unbond_requests_limitation(deps, start_from, limit)?)
         }
+        QueryMsg::PendingOwner {} => to_binary(&query_pending_owner(deps)?),
+        QueryMsg::PauseStatus {} => to_binary(&query_pause_status(deps)?),
+        QueryMsg::ExchangeRateHistory { start_after, limit } => {
+            to_binary(&query_exchange_rate_history(deps, start_after, limit)?)
+        }
+        QueryMsg::Apr { window_seconds } => to_binary(&query_apr(deps, env, window_seconds)?),
+        QueryMsg::NativeToken {} => to_binary(&query_native_token(deps)?),
+        QueryMsg::IbcChannels {} => to_binary(&query_ibc_channels(deps)?),
     }
 }
 
+fn query_native_token(deps: Deps) -> StdResult<NativeTokenResponse> {
+    Ok(match NATIVE_TOKEN.may_load(deps.storage)? {
+        Some(native_token) => NativeTokenResponse {
+            denom: Some(native_token.denom),
+            supply: native_token.supply,
+            basset_rate: native_token.basset_rate,
+        },
+        None => NativeTokenResponse {
+            denom: None,
+            supply: Uint128::zero(),
+            basset_rate: Decimal::one(),
+        },
+    })
+}
+
+fn query_ibc_channels(deps: Deps) -> StdResult<IbcChannelsResponse> {
+    let channels = IBC_CHANNELS
+        .keys(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<String>>>()?;
+    Ok(IbcChannelsResponse { channels })
+}
+
 fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
     let config = CONFIG.load(deps.storage)?;
-    let mut reward: Option<String> = None;
-    let mut token: Option<String> = None;
-    if config.reward_contract.is_some() {
-        reward = Some(config.reward_contract.unwrap().to_string());
-    }
-    if config.token_contract.is_some() {
-        token = Some(config.token_contract.unwrap().to_string());
-    }
-
     Ok(ConfigResponse {
         owner: config.creator.to_string(),
-        reward_contract: reward,
-        token_contract: token,
+        reward_contract: config.reward_contract.map(|x| x.to_string()),
+        token_contract: config.token_contract.map(|x| x.to_string()),
         validator_registry_contract: config.validators_registry_contract.map(|x| x.to_string()),
         //airdrop_registry_contract: airdrop,
     })
@@ -395,23 +578,50 @@ fn query_withdrawable_unbonded(
     Ok(withdrawable)
 }
 
+fn query_pending_owner(deps: Deps) -> StdResult<PendingOwnerResponse> {
+    Ok(PENDING_OWNER.may_load(deps.storage)?.into())
+}
+
+fn query_pause_status(deps: Deps) -> StdResult<PauseStatusResponse> {
+    let status = read_pause_status(deps.storage)?;
+    Ok(PauseStatusResponse {
+        guardian: GUARDIAN.may_load(deps.storage)?.map(|g| g.to_string()),
+        bond: status.bond,
+        unbond: status.unbond,
+        withdraw: status.withdraw,
+        update_global_index: status.update_global_index,
+    })
+}
+
 fn query_params(deps: Deps) -> StdResult<Parameters> {
     PARAMETERS.load(deps.storage)
 }
 
-pub(crate) fn query_total_issued(deps: Deps) -> StdResult<Uint128> {
-    let token_address = CONFIG
-        .load(deps.storage)?
-        .token_contract
-        .ok_or_else(|| StdError::generic_err("token contract must have been registered"))?
-        .to_string();
-    let token_info: TokenInfoResponse =
-        deps.querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
-            contract_addr: token_address,
-            msg: to_binary(&Cw20QueryMsg::TokenInfo {})?,
-        }))?;
+/// Supply of the CW20 token and bAsset the native token stands for
+pub(crate) fn query_total_issued(deps: Deps) -> Result<Uint128, ContractError> {
+    let native_token = NATIVE_TOKEN.may_load(deps.storage)?;
+    let token_supply = match CONFIG.load(deps.storage)?.token_contract {
+        Some(token_address) => {
+            let token_info: TokenInfoResponse =
+                deps.querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+                    contract_addr: token_address.to_string(),
+                    msg: to_binary(&Cw20QueryMsg::TokenInfo {})?,
+                }))?;
+            token_info.total_supply
+        }
+        // the hub can only issue the native token
+        None if native_token.is_some() => Uint128::zero(),
+        None => {
+            return Err(ContractError::MissingRegisteredContract {
+                contract: "token".to_string(),
+            })
+        }
+    };
 
-    Ok(token_info.total_supply)
+    Ok(token_supply
+        + native_token
+            .map(|native_token| native_token.basset_supply())
+            .unwrap_or_default())
 }
 
 fn query_unbond_requests(deps: Deps, address: String) -> StdResult<UnbondRequestsResponse> {
@@ -430,6 +640,87 @@ fn query_unbond_requests_limitation(
     Ok(res)
 }
 
+fn query_exchange_rate_history(
+    deps: Deps,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<ExchangeRateHistoryResponse> {
+    let snapshots = read_exchange_rate_snapshots(deps.storage, start_after, limit)?;
+    Ok(ExchangeRateHistoryResponse { snapshots })
+}
+
+/// Annualizes the growth since the last snapshot taken before the window, or the first
+/// snapshot if the history is shorter than the window
+fn query_apr(deps: Deps, env: Env, window_seconds: u64) -> StdResult<AprResponse> {
+    let now = env.block.time.seconds();
+    let window_start = now.saturating_sub(window_seconds);
+
+    let start = match EXCHANGE_RATE_SNAPSHOTS
+        .range(
+            deps.storage,
+            None,
+            Some(Bound::inclusive(window_start)),
+            Order::Descending,
+        )
+        .next()
+    {
+        Some(item) => Some(item?.1),
+        None => EXCHANGE_RATE_SNAPSHOTS
+            .range(deps.storage, None, None, Order::Ascending)
+            .next()
+            .transpose()?
+            .map(|(_, snapshot)| snapshot),
+    };
+
+    let mut res = AprResponse {
+        from: now,
+        to: now,
+        exchange_rate_apr: Decimal::zero(),
+        annual_rewards_per_token: Decimal256::zero(),
+    };
+    let start = match start {
+        Some(snapshot) if snapshot.time < now => snapshot,
+        _ => return Ok(res),
+    };
+    res.from = start.time;
+    let elapsed = now - start.time;
+
+    // the exchange rate only decreases on slashing, which counts as no yield
+    let exchange_rate = STATE.load(deps.storage)?.exchange_rate;
+    if !start.exchange_rate.is_zero() && exchange_rate > start.exchange_rate {
+        res.exchange_rate_apr = Decimal::from_ratio(
+            (exchange_rate - start.exchange_rate).atomics() * Uint128::from(SECONDS_PER_YEAR),
+            start.exchange_rate.atomics() * Uint128::from(elapsed),
+        );
+    }
+
+    let reward_index = query_reward_index(deps)?;
+    if reward_index > start.reward_index {
+        res.annual_rewards_per_token =
+            (reward_index - start.reward_index) * Decimal256::from_ratio(SECONDS_PER_YEAR, elapsed);
+    }
+
+    Ok(res)
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(deps: DepsMut, _env: Env, msg: Reply) -> Result<Response, ContractError> {
+    match msg.id {
+        TRANSFER_REPLY_ID => reply_ibc_transfer(deps, msg),
+        id => Err(ContractError::Std(StdError::generic_err(format!(
+            "Unknown reply id {}",
+            id
+        )))),
+    }
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn sudo(deps: DepsMut, _env: Env, msg: SudoMsg) -> Result<Response, ContractError> {
+    match msg {
+        SudoMsg::IbcLifecycleComplete(msg) => sudo_ibc_lifecycle_complete(deps, msg),
+    }
+}
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn migrate(_deps: DepsMut, _env: Env, _msg: Empty) -> StdResult<Response> {
     Ok(Response::default())
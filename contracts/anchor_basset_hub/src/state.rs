@@ -1,13 +1,17 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::{
-    from_slice, to_vec, Addr, Decimal, Order, StdError, StdResult, Storage, Uint128,
-};
+use cosmwasm_std::{from_slice, to_vec, Addr, Decimal, Empty, Order, StdResult, Storage, Uint128};
 use cosmwasm_storage::{Bucket, PrefixedStorage, ReadonlyBucket, ReadonlyPrefixedStorage};
-use cw_storage_plus::Item;
+use cw_storage_plus::{Bound, Item, Map};
 
-use basset::hub::{Config, State, UnbondHistory, UnbondRequest};
+use basset::contract_error::ContractError;
+use basset::hub::{
+    Config, ExchangeRateSnapshot, NativeToken, PausableOperation, State, UnbondHistory,
+    UnbondRequest,
+};
+use basset::ibc::PendingTransfer;
+use basset::ownership::OwnershipProposal;
 
 pub type LastBatch = u64;
 
@@ -23,6 +27,38 @@ pub struct Parameters {
     pub peg_recovery_fee: Decimal,
     pub er_threshold: Decimal,
     pub reward_denom: String,
+    /// Fraction of the underlying staking rewards restaked by the hub, raising the exchange
+    /// rate. The rest goes through the reward contract
+    #[serde(default)]
+    pub compound_ratio: Decimal,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct PauseStatus {
+    pub bond: bool,
+    pub unbond: bool,
+    pub withdraw: bool,
+    pub update_global_index: bool,
+}
+
+impl PauseStatus {
+    pub fn is_paused(&self, operation: PausableOperation) -> bool {
+        match operation {
+            PausableOperation::Bond => self.bond,
+            PausableOperation::Unbond => self.unbond,
+            PausableOperation::Withdraw => self.withdraw,
+            PausableOperation::UpdateGlobalIndex => self.update_global_index,
+        }
+    }
+
+    pub fn set(&mut self, operation: PausableOperation, paused: bool) {
+        match operation {
+            PausableOperation::Bond => self.bond = paused,
+            PausableOperation::Unbond => self.unbond = paused,
+            PausableOperation::Withdraw => self.withdraw = paused,
+            PausableOperation::UpdateGlobalIndex => self.update_global_index = paused,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -35,6 +71,25 @@ pub const CONFIG: Item<Config> = Item::new("\u{0}\u{6}config");
 pub const PARAMETERS: Item<Parameters> = Item::new("\u{0}\u{b}parameteres");
 pub const CURRENT_BATCH: Item<CurrentBatch> = Item::new("\u{0}\u{d}current_batch");
 pub const STATE: Item<State> = Item::new("\u{0}\u{5}state");
+pub const PENDING_OWNER: Item<OwnershipProposal> = Item::new("pending_owner");
+pub const GUARDIAN: Item<Addr> = Item::new("guardian");
+pub const PAUSE_STATUS: Item<PauseStatus> = Item::new("pause_status");
+/// The native token is only stored once created
+pub const NATIVE_TOKEN: Item<NativeToken> = Item::new("native_token");
+/// Exchange rate snapshots taken on every global index update and unbond epoch, by block time
+pub const EXCHANGE_RATE_SNAPSHOTS: Map<u64, ExchangeRateSnapshot> =
+    Map::new("exchange_rate_snapshots");
+/// Transfer channels unbonded coins can be withdrawn through
+pub const IBC_CHANNELS: Map<&str, Empty> = Map::new("ibc_channels");
+/// Withdrawal transferred by the current transaction, until its packet is known
+pub const PENDING_TRANSFER: Item<PendingTransfer> = Item::new("pending_transfer");
+/// Withdrawals transferred over IBC and not acknowledged yet, by channel and packet sequence
+pub const PENDING_TRANSFERS: Map<(&str, u64), PendingTransfer> = Map::new("pending_transfers");
+
+/// Pause flags are not stored until the first pause, so a missing entry means nothing is paused
+pub fn read_pause_status(storage: &dyn Storage) -> StdResult<PauseStatus> {
+    Ok(PAUSE_STATUS.may_load(storage)?.unwrap_or_default())
+}
 
 /// Store undelegation wait list per each batch
 /// HashMap<user's address, <batch_id, requested_amount>
@@ -100,7 +155,12 @@ pub fn get_unbond_requests(storage: &dyn Storage, sender_addr: String) -> StdRes
     Ok(requests)
 }
 
-pub fn get_unbond_batches(storage: &dyn Storage, sender_addr: String) -> StdResult<Vec<u64>> {
+/// Return the released batches of the user, among `batch_ids` if set
+pub fn get_unbond_batches(
+    storage: &dyn Storage,
+    sender_addr: String,
+    batch_ids: Option<&[u64]>,
+) -> StdResult<Vec<u64>> {
     let vec = to_vec(&sender_addr)?;
     let mut deprecated_batches: Vec<u64> = vec![];
     let res: ReadonlyBucket<Uint128> =
@@ -110,6 +170,9 @@ pub fn get_unbond_batches(storage: &dyn Storage, sender_addr: String) -> StdResu
         .map(|item| {
             let (k, _) = item.unwrap();
             let user_batch: u64 = from_slice(&k).unwrap();
+            if !is_selected(batch_ids, user_batch) {
+                return;
+            }
             let history = read_unbond_history(storage, user_batch);
             if let Ok(h) = history {
                 if h.released {
@@ -124,8 +187,12 @@ pub fn get_unbond_batches(storage: &dyn Storage, sender_addr: String) -> StdResu
 /// Return all requested unbond amount.
 /// This needs to be called after process withdraw rate function.
 /// If the batch is released, this will return user's requested
-/// amount proportional to withdraw rate.
-pub fn get_finished_amount(storage: &dyn Storage, sender_addr: String) -> StdResult<Uint128> {
+/// amount proportional to withdraw rate. Only `batch_ids` are counted if set.
+pub fn get_finished_amount(
+    storage: &dyn Storage,
+    sender_addr: String,
+    batch_ids: Option<&[u64]>,
+) -> StdResult<Uint128> {
     let vec = to_vec(&sender_addr)?;
     let mut withdrawable_amount: Uint128 = Uint128::zero();
     let res: ReadonlyBucket<Uint128> =
@@ -135,6 +202,9 @@ pub fn get_finished_amount(storage: &dyn Storage, sender_addr: String) -> StdRes
         .map(|item| {
             let (k, v) = item.unwrap();
             let user_batch: u64 = from_slice(&k).unwrap();
+            if !is_selected(batch_ids, user_batch) {
+                return;
+            }
             let history = read_unbond_history(storage, user_batch);
             if let Ok(h) = history {
                 if h.released {
@@ -146,6 +216,13 @@ pub fn get_finished_amount(storage: &dyn Storage, sender_addr: String) -> StdRes
     Ok(withdrawable_amount)
 }
 
+fn is_selected(batch_ids: Option<&[u64]>, batch_id: u64) -> bool {
+    match batch_ids {
+        Some(ids) => ids.contains(&batch_id),
+        None => true,
+    }
+}
+
 /// Return the finished amount for all batches that has been before the given block time.
 pub fn query_get_finished_amount(
     storage: &dyn Storage,
@@ -185,14 +262,15 @@ pub fn store_unbond_history(
     Ok(())
 }
 
-pub fn read_unbond_history(storage: &dyn Storage, epoc_id: u64) -> StdResult<UnbondHistory> {
+pub fn read_unbond_history(
+    storage: &dyn Storage,
+    epoc_id: u64,
+) -> Result<UnbondHistory, ContractError> {
     let vec = epoc_id.to_be_bytes().to_vec();
     let res = ReadonlyPrefixedStorage::new(storage, UNBOND_HISTORY_MAP).get(&vec);
     match res {
-        Some(data) => from_slice(&data),
-        None => Err(StdError::generic_err(
-            "Burn requests not found for the specified time period",
-        )),
+        Some(data) => Ok(from_slice(&data)?),
+        None => Err(ContractError::UnbondHistoryNotFound { batch_id: epoc_id }),
     }
 }
 
@@ -221,6 +299,25 @@ pub fn all_unbond_history(
     res
 }
 
+/// Return the exchange rate snapshots taken after `start_after`
+pub fn read_exchange_rate_snapshots(
+    storage: &dyn Storage,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<Vec<ExchangeRateSnapshot>> {
+    let lim = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    EXCHANGE_RATE_SNAPSHOTS
+        .range(
+            storage,
+            start_after.map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .take(lim)
+        .map(|item| Ok(item?.1))
+        .collect()
+}
+
 fn convert(start_after: Option<u64>) -> Option<Vec<u8>> {
     start_after.map(|idx| {
         let mut v = idx.to_be_bytes().to_vec();
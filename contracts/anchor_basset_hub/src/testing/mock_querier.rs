@@ -1,9 +1,16 @@
+use basset::dex_router::{
+    OperationFlavour, QueryMsg as SwapQueryMsg, SimulateSwapOperationsResponse,
+};
 use basset::hub::Config;
+use basset::reward::{
+    ConfigResponse as RewardConfigResponse, DexResponse, DexesResponse, QueryMsg as RewardQueryMsg,
+    StateResponse as RewardStateResponse,
+};
 use cosmwasm_std::testing::{MockApi, MockQuerier, MockStorage};
 use cosmwasm_std::Empty;
 use cosmwasm_std::{
     from_binary, from_slice, to_binary, AllBalanceResponse, Api, BalanceResponse, BankQuery,
-    CanonicalAddr, Coin, ContractResult, Decimal, FullDelegation, OwnedDeps, Querier,
+    CanonicalAddr, Coin, ContractResult, Decimal, Decimal256, FullDelegation, OwnedDeps, Querier,
     QuerierResult, QueryRequest, SystemError, SystemResult, Uint128, Validator, WasmQuery,
 };
 use cosmwasm_storage::to_length_prefixed;
@@ -39,6 +46,8 @@ pub struct WasmMockQuerier {
     token_querier: TokenQuerier,
     balance_querier: BalanceQuerier,
     validators_querier: ValidatorsQuerier,
+    reward_index: Decimal256,
+    reward_protocol_fee_bps: u64,
 }
 
 impl Querier for WasmMockQuerier {
@@ -139,7 +148,7 @@ impl WasmMockQuerier {
                     let all_balances = AllBalanceResponse { amount: coins };
                     SystemResult::Ok(ContractResult::from(to_binary(&all_balances)))
                 } else {
-                    unimplemented!()
+                    self.base.handle_query(request)
                 }
             }
             QueryRequest::Bank(BankQuery::Balance { address, denom }) => {
@@ -185,6 +194,8 @@ impl WasmMockQuerier {
                                     ValidatorResponse {
                                         total_delegated: *delegation,
                                         address: validator.clone(),
+                                        weight: 1,
+                                        max_cap: None,
                                     }
                                 })
                                 .collect();
@@ -197,6 +208,78 @@ impl WasmMockQuerier {
                     }
                 }
 
+                // The reward contract shares its dexes and its global index
+                if contract_addr == "reward" {
+                    return match from_binary(msg).unwrap() {
+                        RewardQueryMsg::Dexes {} => {
+                            let dex = |name: &str, flavour: OperationFlavour, enabled: bool| {
+                                DexResponse {
+                                    name: name.to_string(),
+                                    router: format!("{}_addr", name),
+                                    flavour,
+                                    enabled,
+                                }
+                            };
+                            SystemResult::Ok(ContractResult::from(to_binary(&DexesResponse {
+                                dexes: vec![
+                                    dex("astroport", OperationFlavour::AstroSwap, true),
+                                    dex("disabled", OperationFlavour::AstroSwap, false),
+                                    dex("phoenix", OperationFlavour::TokenSwap, true),
+                                    dex("terraswap", OperationFlavour::TerraSwap, true),
+                                ],
+                            })))
+                        }
+                        RewardQueryMsg::State {} => SystemResult::Ok(ContractResult::from(
+                            to_binary(&RewardStateResponse {
+                                global_index: self.reward_index,
+                                total_balance: Uint128::zero(),
+                                prev_reward_balance: Uint128::zero(),
+                                total_protocol_fee: Uint128::zero(),
+                                total_compound_protocol_fee: Uint128::zero(),
+                            }),
+                        )),
+                        RewardQueryMsg::Config {} => SystemResult::Ok(ContractResult::from(
+                            to_binary(&RewardConfigResponse {
+                                owner: "owner1".to_string(),
+                                hub_contract: MOCK_CONTRACT_ADDR.to_string(),
+                                reward_denom: "uusd".to_string(),
+                                max_spread: None,
+                                slippage_tolerance: Decimal::percent(1),
+                                split_chunks: None,
+                                protocol_fee_bps: self.reward_protocol_fee_bps,
+                                fee_collector: (self.reward_protocol_fee_bps > 0)
+                                    .then(|| "fee_collector".to_string()),
+                            }),
+                        )),
+                        _ => panic!("No this isn't implemented in tests"),
+                    };
+                }
+
+                // Astroport gives 90% of the offer, Phoenix 95% and TerraSwap has no pool.
+                // The disabled dex would give the best price
+                let swap_rate = match contract_addr.as_str() {
+                    "astroport_addr" => Some(Decimal::percent(90)),
+                    "disabled_addr" => Some(Decimal::percent(200)),
+                    "phoenix_addr" => Some(Decimal::percent(95)),
+                    "terraswap_addr" => None,
+                    _ => Some(Decimal::zero()),
+                };
+                if let Ok(SwapQueryMsg::SimulateSwapOperations { offer_amount, .. }) =
+                    from_binary(msg)
+                {
+                    return match swap_rate {
+                        Some(rate) => SystemResult::Ok(ContractResult::from(to_binary(
+                            &SimulateSwapOperationsResponse {
+                                amount: offer_amount * rate,
+                            },
+                        ))),
+                        None => SystemResult::Err(SystemError::InvalidRequest {
+                            error: "no pool".to_string(),
+                            request: msg.clone(),
+                        }),
+                    };
+                }
+
                 match from_binary(msg).unwrap() {
                     Cw20QueryMsg::TokenInfo {} => {
                         let balances: &HashMap<String, Uint128> =
@@ -271,6 +354,10 @@ impl WasmMockQuerier {
     ) {
         self.base.update_staking(denom, validators, delegations);
     }
+
+    pub fn update_balance(&mut self, addr: impl Into<String>, balance: Vec<Coin>) {
+        self.base.update_balance(addr, balance);
+    }
 }
 
 #[derive(Clone, Default)]
@@ -355,9 +442,19 @@ impl WasmMockQuerier {
             //tax_querier: TaxQuerier::default(),
             balance_querier: BalanceQuerier::default(),
             validators_querier: ValidatorsQuerier::default(),
+            reward_index: Decimal256::zero(),
+            reward_protocol_fee_bps: 0,
         }
     }
 
+    pub fn with_reward_index(&mut self, reward_index: Decimal256) {
+        self.reward_index = reward_index;
+    }
+
+    pub fn with_reward_protocol_fee(&mut self, protocol_fee_bps: u64) {
+        self.reward_protocol_fee_bps = protocol_fee_bps;
+    }
+
     pub fn with_native_balances(&mut self, balances: &[(String, Coin)]) {
         self.balance_querier = BalanceQuerier::new(balances);
     }
@@ -5,50 +5,70 @@
 //! You can easily convert unit tests to integration tests as follows:
 //! 1. Copy them over verbatim
 //! 2. Then change
-//!      let mut deps = mock_dependencies(20, &[]);
+//!    ```text
+//!    let mut deps = mock_dependencies(20, &[]);
+//!    ```
 //!    to
-//!      let mut deps = mock_instance(WASM, &[]);
+//!    ```text
+//!    let mut deps = mock_instance(WASM, &[]);
+//!    ```
 //! 3. If you access raw storage, where ever you see something like:
-//!      deps.storage.get(CONFIG_KEY).expect("no data stored");
+//!    ```text
+//!    deps.storage.get(CONFIG_KEY).expect("no data stored");
+//!    ```
 //!    replace it with:
-//!      deps.with_storage(|store| {
-//!          let data = store.get(CONFIG_KEY).expect("no data stored");
-//!          //...
-//!      });
+//!    ```text
+//!    deps.with_storage(|store| {
+//!        let data = store.get(CONFIG_KEY).expect("no data stored");
+//!        //...
+//!    });
+//!    ```
 //! 4. Anywhere you see query(deps.as_ref(), ...) you must replace it with query(&mut deps, ...)
 use cosmwasm_std::QueryRequest;
 use cosmwasm_std::{
-    coin, from_binary, to_binary, Addr, Api, BankMsg, Coin, CosmosMsg, Decimal, DepsMut,
-    DistributionMsg, Env, FullDelegation, MessageInfo, OwnedDeps, Querier, Response, StakingMsg,
-    StdError, Storage, SubMsg, Uint128, Validator, WasmMsg, WasmQuery,
+    coin, coins, from_binary, to_binary, Addr, Api, BankMsg, Coin, CosmosMsg, Decimal, Decimal256,
+    DepsMut, DistributionMsg, Env, Event, FullDelegation, MessageInfo, OwnedDeps, Querier, Reply,
+    Response, StakingMsg, StdResult, Storage, SubMsg, SubMsgResponse, SubMsgResult, Uint128,
+    Validator, WasmMsg, WasmQuery,
 };
 
-use schemars::JsonSchema;
-use serde::{Deserialize, Serialize};
-
 use cosmwasm_std::testing::{mock_env, mock_info};
 use lido_terra_validators_registry::msg::QueryMsg as QueryValidators;
 use lido_terra_validators_registry::registry::ValidatorResponse as RegistryValidator;
 
-use crate::contract::{execute, instantiate, query};
+use crate::contract::{execute, instantiate, query, reply, sudo, TRANSFER_REPLY_ID};
 use crate::unbond::execute_unbond;
+use basset::contract_error::ContractError;
 use basset::hub::QueryMsg;
 use basset::hub::{
-    AllHistoryResponse, ConfigResponse, CurrentBatchResponse, ExecuteMsg, InstantiateMsg,
-    StateResponse, UnbondRequestsResponse, WithdrawableUnbondedResponse,
+    AllHistoryResponse, AprResponse, ConfigResponse, CurrentBatchResponse,
+    ExchangeRateHistoryResponse, ExchangeRateSnapshot, ExecuteMsg, InstantiateMsg,
+    NativeTokenResponse, StateResponse, SudoMsg, UnbondRequestsResponse,
+    WithdrawableUnbondedResponse,
 };
+use basset::ownership::PendingOwnerResponse;
 
-use basset::hub::Cw20HookMsg::Unbond;
-use basset::hub::ExecuteMsg::{CheckSlashing, Receive, UpdateConfig, UpdateParams};
+use basset::dex_router::{AssetInfo, Cw20HookMsg as RouterCw20HookMsg, SwapOperation};
+use basset::hub::Cw20HookMsg::{InstantUnbond, Unbond};
+use basset::hub::ExecuteMsg::{
+    AcceptOwnership, CancelOwnershipTransfer, CheckSlashing, Pause, ProposeNewOwner, Receive,
+    Unpause, UpdateConfig, UpdateGuardian, UpdateParams,
+};
+use basset::hub::{PausableOperation, PauseStatusResponse};
 use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg};
 use cw20_base::msg::ExecuteMsg::{Burn, Mint};
 
 use super::mock_querier::{mock_dependencies as dependencies, WasmMockQuerier};
 use crate::math::decimal_division;
-use crate::state::{read_unbond_wait_list, Parameters, CONFIG};
+use crate::state::{read_unbond_wait_list, Parameters, CONFIG, STATE};
 
 use basset::hub::QueryMsg::{AllHistory, UnbondRequests, WithdrawableUnbonded};
+use basset::ibc::{
+    transfer_msg, IbcChannelsResponse, IbcLifecycleComplete, IbcTransfer, MIN_TIMEOUT,
+};
+use basset::reward::ExecuteMsg as RewardExecuteMsg;
 use basset::reward::ExecuteMsg::{SwapToRewardDenom, UpdateGlobalIndex};
+use basset::token_factory::{burn_msg, create_denom_msg, mint_msg};
 use std::borrow::BorrowMut;
 
 const DEFAULT_VALIDATOR: &str = "default-validator";
@@ -101,7 +121,6 @@ pub fn init<S: Storage, A: Api, Q: Querier>(
     instantiate(deps.as_mut(), mock_env(), owner_info.clone(), msg).unwrap();
 
     let register_msg = ExecuteMsg::UpdateConfig {
-        owner: None,
         reward_contract: Some(reward_contract),
         token_contract: Some(token_contract),
         validators_registry_contract: Some(MOCK_REGISTRY_CONTRACT.to_string()),
@@ -319,7 +338,9 @@ fn proper_bond() {
     let res = execute(deps.as_mut(), mock_env(), info, failed_bond);
     assert_eq!(
         res.unwrap_err(),
-        StdError::generic_err("No uluna assets are provided to bond")
+        ContractError::WrongDenom {
+            denom: "uluna".to_string()
+        }
     );
 
     //send other tokens than luna funds
@@ -331,7 +352,9 @@ fn proper_bond() {
     let res = execute(deps.as_mut(), mock_env(), info, failed_bond.clone());
     assert_eq!(
         res.unwrap_err(),
-        StdError::generic_err("No uluna assets are provided to bond")
+        ContractError::WrongDenom {
+            denom: "uluna".to_string()
+        }
     );
 
     //bond with more than one coin is not possible
@@ -344,10 +367,59 @@ fn proper_bond() {
     );
 
     let res = execute(deps.as_mut(), mock_env(), info, failed_bond).unwrap_err();
+    assert_eq!(res, ContractError::MultipleCoins {});
+}
+
+/// Covers the typed errors returned when the hub is not fully configured.
+#[test]
+fn proper_bond_missing_registered_contract() {
+    let mut deps = dependencies(&[]);
+    set_validator_mock(&mut deps.querier);
+
+    let msg = InstantiateMsg {
+        epoch_period: 30,
+        underlying_coin_denom: "uluna".to_string(),
+        unbonding_period: 2,
+        peg_recovery_fee: Decimal::zero(),
+        er_threshold: Decimal::one(),
+        reward_denom: "uusd".to_string(),
+    };
+    let owner_info = mock_info("owner1", &[]);
+    instantiate(deps.as_mut(), mock_env(), owner_info, msg).unwrap();
+
+    let info = mock_info("bob", &[coin(10, "uluna")]);
+    let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Bond {}).unwrap_err();
     assert_eq!(
         res,
-        StdError::generic_err("More than one coin is sent; only one asset is supported")
+        ContractError::MissingRegisteredContract {
+            contract: "validators registry".to_string()
+        }
     );
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("bob", &[]),
+        ExecuteMsg::UpdateGlobalIndex {},
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::MissingRegisteredContract {
+            contract: "reward".to_string()
+        }
+    );
+
+    let msg = InstantiateMsg {
+        epoch_period: 30,
+        underlying_coin_denom: "uluna".to_string(),
+        unbonding_period: 2,
+        peg_recovery_fee: Decimal::percent(101),
+        er_threshold: Decimal::one(),
+        reward_denom: "uusd".to_string(),
+    };
+    let res = instantiate(deps.as_mut(), mock_env(), mock_info("owner1", &[]), msg).unwrap_err();
+    assert_eq!(res, ContractError::InvalidPegRecoveryFee {});
 }
 
 /// Covers if Withdraw message, swap message, and update global index are sent.
@@ -382,7 +454,7 @@ pub fn proper_update_global_index() {
 
     let info = mock_info(&addr1, &[]);
     let res = execute(deps.as_mut(), mock_env(), info, reward_msg).unwrap();
-    assert_eq!(res.messages.len(), 2);
+    assert_eq!(res.messages.len(), 3);
 
     // bond
     do_bond(deps.as_mut(), addr1.clone(), bond_amount, validator.clone());
@@ -405,7 +477,7 @@ pub fn proper_update_global_index() {
 
     let info = mock_info(&addr1, &[]);
     let res = execute(deps.as_mut(), mock_env(), info, reward_msg).unwrap();
-    assert_eq!(3, res.messages.len());
+    assert_eq!(4, res.messages.len());
 
     let last_index_query = QueryMsg::State {};
     let last_modification: StateResponse =
@@ -450,6 +522,638 @@ pub fn proper_update_global_index() {
     }
 }
 
+/// Covers if the underlying rewards are partially restaked when compound_ratio is set, less the
+/// protocol fee.
+#[test]
+pub fn proper_compound_rewards() {
+    let mut deps = dependencies(&[]);
+    let validator = sample_validator(DEFAULT_VALIDATOR.to_string());
+    set_validator_mock(&mut deps.querier);
+
+    let addr1 = "addr1000".to_string();
+    let bond_amount = Uint128::new(10);
+
+    let owner = "owner1".to_string();
+    let token_contract = "token".to_string();
+    let reward_contract = "reward".to_string();
+
+    init(
+        deps.borrow_mut(),
+        owner.clone(),
+        reward_contract.clone(),
+        token_contract,
+    );
+
+    deps.querier
+        .with_validators(&[(&validator.address, Uint128::zero())]);
+    do_bond(deps.as_mut(), addr1.clone(), bond_amount, validator.clone());
+
+    let delegations: [FullDelegation; 1] =
+        [(sample_delegation(validator.address.clone(), coin(bond_amount.u128(), "uluna")))];
+    let validators: [Validator; 1] = [(validator.clone())];
+    set_delegation_query(&mut deps.querier, &delegations, &validators);
+    deps.querier
+        .with_token_balances(&[(&"token".to_string(), &[(&addr1, &bond_amount)])]);
+
+    // the ratio can not be greater than one
+    let update_params = UpdateParams {
+        epoch_period: None,
+        peg_recovery_fee: None,
+        er_threshold: None,
+        compound_ratio: Some(Decimal::percent(150)),
+    };
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(&owner, &[]),
+        update_params,
+    )
+    .unwrap_err();
+    assert_eq!(res, ContractError::InvalidCompoundRatio {});
+
+    let update_params = UpdateParams {
+        epoch_period: None,
+        peg_recovery_fee: None,
+        er_threshold: None,
+        compound_ratio: Some(Decimal::percent(50)),
+    };
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(&owner, &[]),
+        update_params,
+    )
+    .unwrap();
+
+    // the rewards are withdrawn to the hub, then compounded
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(&addr1, &[]),
+        ExecuteMsg::UpdateGlobalIndex {},
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(DistributionMsg::SetWithdrawAddress {
+                address: MOCK_CONTRACT_ADDR.to_string()
+            }),
+            SubMsg::new(DistributionMsg::WithdrawDelegatorReward {
+                validator: validator.address.clone()
+            }),
+            SubMsg::new(DistributionMsg::SetWithdrawAddress {
+                address: reward_contract.clone()
+            }),
+            SubMsg::new(WasmMsg::Execute {
+                contract_addr: MOCK_CONTRACT_ADDR.to_string(),
+                msg: to_binary(&ExecuteMsg::CompoundRewards {
+                    prev_balances: vec![]
+                })
+                .unwrap(),
+                funds: vec![],
+            }),
+            SubMsg::new(WasmMsg::Execute {
+                contract_addr: reward_contract.clone(),
+                msg: to_binary(&SwapToRewardDenom {}).unwrap(),
+                funds: vec![],
+            }),
+            SubMsg::new(WasmMsg::Execute {
+                contract_addr: reward_contract.clone(),
+                msg: to_binary(&UpdateGlobalIndex {}).unwrap(),
+                funds: vec![],
+            }),
+            SubMsg::new(WasmMsg::Execute {
+                contract_addr: MOCK_CONTRACT_ADDR.to_string(),
+                msg: to_binary(&ExecuteMsg::StoreExchangeRateSnapshot {}).unwrap(),
+                funds: vec![],
+            }),
+        ]
+    );
+
+    // only the hub itself can compound
+    let compound = ExecuteMsg::CompoundRewards {
+        prev_balances: vec![],
+    };
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(&addr1, &[]),
+        compound.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    deps.querier.update_balance(
+        MOCK_CONTRACT_ADDR,
+        vec![coin(100, "uluna"), coin(20, "ukrw")],
+    );
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(MOCK_CONTRACT_ADDR, &[]),
+        compound,
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(StakingMsg::Delegate {
+                validator: validator.address.clone(),
+                amount: coin(50, "uluna"),
+            }),
+            SubMsg::new(BankMsg::Send {
+                to_address: reward_contract.clone(),
+                amount: vec![coin(50, "uluna"), coin(20, "ukrw")],
+            }),
+        ]
+    );
+
+    let state: StateResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::State {}).unwrap()).unwrap();
+    assert_eq!(state.total_bond_amount, Uint128::new(60));
+    assert_eq!(state.exchange_rate, Decimal::from_ratio(60u128, 10u128));
+
+    // the protocol fee of the reward contract is taken from the compounded rewards too
+    deps.querier.with_reward_protocol_fee(1000);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(MOCK_CONTRACT_ADDR, &[]),
+        ExecuteMsg::CompoundRewards {
+            prev_balances: vec![],
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(StakingMsg::Delegate {
+                validator: validator.address.clone(),
+                amount: coin(45, "uluna"),
+            }),
+            SubMsg::new(WasmMsg::Execute {
+                contract_addr: reward_contract.clone(),
+                msg: to_binary(&RewardExecuteMsg::CollectCompoundProtocolFee {}).unwrap(),
+                funds: vec![coin(5, "uluna")],
+            }),
+            SubMsg::new(BankMsg::Send {
+                to_address: reward_contract,
+                amount: vec![coin(50, "uluna"), coin(20, "ukrw")],
+            }),
+        ]
+    );
+
+    let state: StateResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::State {}).unwrap()).unwrap();
+    assert_eq!(state.total_bond_amount, Uint128::new(105));
+}
+
+/// Covers if the exchange rate snapshots are recorded and the APR is derived from them.
+#[test]
+pub fn proper_exchange_rate_history() {
+    let mut deps = dependencies(&[]);
+    let validator = sample_validator(DEFAULT_VALIDATOR.to_string());
+    set_validator_mock(&mut deps.querier);
+
+    let addr1 = "addr1000".to_string();
+    let bond_amount = Uint128::new(10);
+
+    init(
+        deps.borrow_mut(),
+        "owner1".to_string(),
+        "reward".to_string(),
+        "token".to_string(),
+    );
+
+    deps.querier
+        .with_validators(&[(&validator.address, Uint128::zero())]);
+    do_bond(deps.as_mut(), addr1.clone(), bond_amount, validator.clone());
+
+    let delegations: [FullDelegation; 1] =
+        [(sample_delegation(validator.address.clone(), coin(bond_amount.u128(), "uluna")))];
+    let validators: [Validator; 1] = [(validator.clone())];
+    set_delegation_query(&mut deps.querier, &delegations, &validators);
+    deps.querier
+        .with_token_balances(&[(&"token".to_string(), &[(&addr1, &bond_amount)])]);
+
+    let start = mock_env();
+    deps.querier.with_reward_index(Decimal256::one());
+    let res = execute(
+        deps.as_mut(),
+        start.clone(),
+        mock_info(&addr1, &[]),
+        ExecuteMsg::UpdateGlobalIndex {},
+    )
+    .unwrap();
+    // the snapshot is taken after the global index update, by the hub only
+    assert_eq!(
+        res.messages.last().unwrap().msg,
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: MOCK_CONTRACT_ADDR.to_string(),
+            msg: to_binary(&ExecuteMsg::StoreExchangeRateSnapshot {}).unwrap(),
+            funds: vec![],
+        })
+    );
+    let res = execute(
+        deps.as_mut(),
+        start.clone(),
+        mock_info(&addr1, &[]),
+        ExecuteMsg::StoreExchangeRateSnapshot {},
+    );
+    assert_eq!(res.unwrap_err(), ContractError::Unauthorized {});
+    execute(
+        deps.as_mut(),
+        start.clone(),
+        mock_info(MOCK_CONTRACT_ADDR, &[]),
+        ExecuteMsg::StoreExchangeRateSnapshot {},
+    )
+    .unwrap();
+
+    // half a year later, the exchange rate grew by 10% and 0.5 rewards were paid per token
+    STATE
+        .update(deps.as_mut().storage, |mut state| -> StdResult<_> {
+            state.exchange_rate = Decimal::percent(110);
+            state.total_bond_amount = Uint128::new(11);
+            Ok(state)
+        })
+        .unwrap();
+    let mut end = mock_env();
+    end.block.time = end.block.time.plus_seconds(15_768_000);
+    deps.querier
+        .with_reward_index(Decimal256::from_ratio(3u128, 2u128));
+    execute(
+        deps.as_mut(),
+        end.clone(),
+        mock_info(&addr1, &[]),
+        ExecuteMsg::UpdateGlobalIndex {},
+    )
+    .unwrap();
+    execute(
+        deps.as_mut(),
+        end.clone(),
+        mock_info(MOCK_CONTRACT_ADDR, &[]),
+        ExecuteMsg::StoreExchangeRateSnapshot {},
+    )
+    .unwrap();
+
+    let history: ExchangeRateHistoryResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            end.clone(),
+            QueryMsg::ExchangeRateHistory {
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        history.snapshots,
+        vec![
+            ExchangeRateSnapshot {
+                time: start.block.time.seconds(),
+                exchange_rate: Decimal::one(),
+                total_bond_amount: Uint128::new(10),
+                reward_index: Decimal256::one(),
+            },
+            ExchangeRateSnapshot {
+                time: end.block.time.seconds(),
+                exchange_rate: Decimal::percent(110),
+                total_bond_amount: Uint128::new(11),
+                reward_index: Decimal256::from_ratio(3u128, 2u128),
+            },
+        ]
+    );
+
+    let history: ExchangeRateHistoryResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            end.clone(),
+            QueryMsg::ExchangeRateHistory {
+                start_after: Some(start.block.time.seconds()),
+                limit: Some(1),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(history.snapshots.len(), 1);
+    assert_eq!(history.snapshots[0].time, end.block.time.seconds());
+
+    let apr: AprResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            end.clone(),
+            QueryMsg::Apr {
+                window_seconds: 15_768_000,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        apr,
+        AprResponse {
+            from: start.block.time.seconds(),
+            to: end.block.time.seconds(),
+            exchange_rate_apr: Decimal::percent(20),
+            annual_rewards_per_token: Decimal256::one(),
+        }
+    );
+
+    // there is no growth to measure over an empty window
+    let apr: AprResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            end.clone(),
+            QueryMsg::Apr { window_seconds: 0 },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(apr.from, end.block.time.seconds());
+    assert_eq!(apr.exchange_rate_apr, Decimal::zero());
+}
+
+/// Covers if the native token is created, minted on bond and burnt on unbond.
+#[test]
+pub fn proper_native_token() {
+    let mut deps = dependencies(&[]);
+    let validator = sample_validator(DEFAULT_VALIDATOR.to_string());
+    set_validator_mock(&mut deps.querier);
+
+    let addr1 = "addr1000".to_string();
+    let owner = "owner1".to_string();
+    let reward_contract = "reward".to_string();
+
+    init(
+        deps.borrow_mut(),
+        owner.clone(),
+        reward_contract.clone(),
+        "token".to_string(),
+    );
+    deps.querier
+        .with_validators(&[(&validator.address, Uint128::zero())]);
+    deps.querier
+        .with_token_balances(&[(&"token".to_string(), &[])]);
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(&addr1, &[coin(100, "uluna")]),
+        ExecuteMsg::BondNative {},
+    )
+    .unwrap_err();
+    assert_eq!(res, ContractError::NoNativeToken {});
+
+    // only the owner can create the native token
+    let create = ExecuteMsg::CreateNativeToken {
+        subdenom: "ualuna".to_string(),
+    };
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(&addr1, &[]),
+        create.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(&owner, &[]),
+        create.clone(),
+    )
+    .unwrap();
+    let denom = format!("factory/{}/ualuna", MOCK_CONTRACT_ADDR);
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(create_denom_msg(MOCK_CONTRACT_ADDR, "ualuna"))]
+    );
+    let res = execute(deps.as_mut(), mock_env(), mock_info(&owner, &[]), create).unwrap_err();
+    assert_eq!(res, ContractError::NativeTokenAlreadyCreated {});
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(&addr1, &[coin(100, "uluna")]),
+        ExecuteMsg::BondNative {},
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(StakingMsg::Delegate {
+                validator: validator.address,
+                amount: coin(100, "uluna"),
+            }),
+            SubMsg::new(mint_msg(MOCK_CONTRACT_ADDR, &coin(100, &denom))),
+            SubMsg::new(BankMsg::Send {
+                to_address: addr1.clone(),
+                amount: vec![coin(100, &denom)],
+            }),
+        ]
+    );
+
+    let native_token: NativeTokenResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::NativeToken {}).unwrap()).unwrap();
+    assert_eq!(
+        native_token,
+        NativeTokenResponse {
+            denom: Some(denom.clone()),
+            supply: Uint128::new(100),
+            basset_rate: Decimal::one(),
+        }
+    );
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(&addr1, &[coin(40, "uluna")]),
+        ExecuteMsg::UnbondNative {},
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::WrongDenom {
+            denom: denom.clone()
+        }
+    );
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(&addr1, &[coin(40, &denom)]),
+        ExecuteMsg::UnbondNative {},
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(burn_msg(MOCK_CONTRACT_ADDR, &coin(40, &denom)))]
+    );
+
+    let native_token: NativeTokenResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::NativeToken {}).unwrap()).unwrap();
+    assert_eq!(native_token.supply, Uint128::new(60));
+
+    let current_batch: CurrentBatchResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::CurrentBatch {}).unwrap()).unwrap();
+    assert_eq!(current_batch.requested_with_fee, Uint128::new(40));
+
+    let state: StateResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::State {}).unwrap()).unwrap();
+    assert_eq!(state.exchange_rate, Decimal::one());
+
+    // the reward contract doesn't track the native token, so there is nothing to auto-claim
+    // on its balance changes
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(&addr1, &[coin(10, "uluna")]),
+        ExecuteMsg::BondNative {},
+    )
+    .unwrap();
+    let mut messages = res.messages;
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(&addr1, &[coin(10, &denom)]),
+        ExecuteMsg::UnbondNative {},
+    )
+    .unwrap();
+    messages.extend(res.messages);
+    assert!(!messages.iter().any(|msg| matches!(
+        &msg.msg,
+        CosmosMsg::Wasm(WasmMsg::Execute { contract_addr, .. }) if contract_addr == &reward_contract
+    )));
+}
+
+/// Covers if the share of the native token in the underlying rewards is restaked for its
+/// holders, raising the bAsset each native token stands for.
+#[test]
+pub fn proper_native_token_compound() {
+    let mut deps = dependencies(&[]);
+    let validator = sample_validator(DEFAULT_VALIDATOR.to_string());
+    set_validator_mock(&mut deps.querier);
+
+    let addr1 = "addr1000".to_string();
+    let addr2 = "addr2000".to_string();
+    let bond_amount = Uint128::new(10);
+
+    let owner = "owner1".to_string();
+    let reward_contract = "reward".to_string();
+
+    init(
+        deps.borrow_mut(),
+        owner.clone(),
+        reward_contract.clone(),
+        "token".to_string(),
+    );
+    deps.querier
+        .with_validators(&[(&validator.address, Uint128::zero())]);
+    do_bond(deps.as_mut(), addr1.clone(), bond_amount, validator.clone());
+    deps.querier
+        .with_token_balances(&[(&"token".to_string(), &[(&addr1, &bond_amount)])]);
+
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(&owner, &[]),
+        ExecuteMsg::CreateNativeToken {
+            subdenom: "ualuna".to_string(),
+        },
+    )
+    .unwrap();
+    let denom = format!("factory/{}/ualuna", MOCK_CONTRACT_ADDR);
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(&addr2, &[coin(10, "uluna")]),
+        ExecuteMsg::BondNative {},
+    )
+    .unwrap();
+
+    let delegations: [FullDelegation; 1] =
+        [(sample_delegation(validator.address.clone(), coin(20, "uluna")))];
+    let validators: [Validator; 1] = [(validator.clone())];
+    set_delegation_query(&mut deps.querier, &delegations, &validators);
+
+    // the rewards are withdrawn to the hub for the native token even without compound_ratio
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(&addr1, &[]),
+        ExecuteMsg::UpdateGlobalIndex {},
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages[0],
+        SubMsg::new(DistributionMsg::SetWithdrawAddress {
+            address: MOCK_CONTRACT_ADDR.to_string()
+        })
+    );
+
+    // half of the bAsset is native, so half of the underlying rewards is restaked
+    deps.querier.update_balance(
+        MOCK_CONTRACT_ADDR,
+        vec![coin(100, "uluna"), coin(20, "ukrw")],
+    );
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(MOCK_CONTRACT_ADDR, &[]),
+        ExecuteMsg::CompoundRewards {
+            prev_balances: vec![],
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(StakingMsg::Delegate {
+                validator: validator.address.clone(),
+                amount: coin(50, "uluna"),
+            }),
+            SubMsg::new(BankMsg::Send {
+                to_address: reward_contract,
+                amount: vec![coin(50, "uluna"), coin(20, "ukrw")],
+            }),
+        ]
+    );
+
+    // the exchange rate of the CW20 holders doesn't change
+    let state: StateResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::State {}).unwrap()).unwrap();
+    assert_eq!(state.total_bond_amount, Uint128::new(70));
+    assert_eq!(state.exchange_rate, Decimal::one());
+
+    let native_token: NativeTokenResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::NativeToken {}).unwrap()).unwrap();
+    assert_eq!(
+        native_token,
+        NativeTokenResponse {
+            denom: Some(denom.clone()),
+            supply: Uint128::new(10),
+            basset_rate: Decimal::from_ratio(6u128, 1u128),
+        }
+    );
+
+    // the native token is unbonded for the bAsset it stands for
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(&addr2, &[coin(10, &denom)]),
+        ExecuteMsg::UnbondNative {},
+    )
+    .unwrap();
+    let current_batch: CurrentBatchResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::CurrentBatch {}).unwrap()).unwrap();
+    assert_eq!(current_batch.requested_with_fee, Uint128::new(60));
+}
+
 /// Covers update_global_index when there is more than one validator.
 /// Checks if more than one Withdraw message is sent.
 #[test]
@@ -517,7 +1221,7 @@ pub fn proper_update_global_index_two_validators() {
 
     let info = mock_info(&addr1, &[]);
     let res = execute(deps.as_mut(), mock_env(), info, reward_msg).unwrap();
-    assert_eq!(4, res.messages.len());
+    assert_eq!(5, res.messages.len());
 
     let withdraw = &res.messages[0].msg;
     match withdraw {
@@ -593,7 +1297,7 @@ pub fn proper_update_global_index_respect_one_registered_validator() {
 
     let info = mock_info(&addr1, &[]);
     let res = execute(deps.as_mut(), mock_env(), info, reward_msg).unwrap();
-    assert_eq!(3, res.messages.len());
+    assert_eq!(4, res.messages.len());
 
     let withdraw = &res.messages[0].msg;
     match withdraw {
@@ -666,7 +1370,7 @@ pub fn proper_receive() {
 
     let invalid_info = mock_info(&invalid, &[]);
     let res = execute(deps.as_mut(), mock_env(), invalid_info, receive);
-    assert_eq!(res.unwrap_err(), StdError::generic_err("unauthorized"));
+    assert_eq!(res.unwrap_err(), ContractError::Unauthorized {});
 
     // successful call
     let successful_unbond = Unbond {};
@@ -1330,7 +2034,11 @@ pub fn proper_slashing() {
 
     env.block.time = env.block.time.plus_seconds(90);
     //check withdrawUnbonded message
-    let withdraw_unbond_msg = ExecuteMsg::WithdrawUnbonded {};
+    let withdraw_unbond_msg = ExecuteMsg::WithdrawUnbonded {
+        recipient: None,
+        batch_ids: None,
+        ibc_transfer: None,
+    };
     let wdraw_unbonded_res = execute(deps.as_mut(), env, info, withdraw_unbond_msg).unwrap();
     assert_eq!(wdraw_unbonded_res.messages.len(), 1);
 
@@ -1419,7 +2127,11 @@ pub fn proper_withdraw_unbonded() {
     //set the block time 30 seconds from now.
     env.block.time = env.block.time.plus_seconds(31);
 
-    let wdraw_unbonded_msg = ExecuteMsg::WithdrawUnbonded {};
+    let wdraw_unbonded_msg = ExecuteMsg::WithdrawUnbonded {
+        recipient: None,
+        batch_ids: None,
+        ibc_transfer: None,
+    };
     let wdraw_unbonded_res = execute(
         deps.as_mut(),
         env.clone(),
@@ -1431,7 +2143,9 @@ pub fn proper_withdraw_unbonded() {
     assert!(wdraw_unbonded_res.is_err());
     assert_eq!(
         wdraw_unbonded_res.unwrap_err(),
-        StdError::generic_err("No withdrawable uluna assets are available yet")
+        ContractError::NoWithdrawableAssets {
+            denom: "uluna".to_string()
+        }
     );
 
     let res = execute_unbond(
@@ -1454,86 +2168,442 @@ pub fn proper_withdraw_unbonded() {
     let res: WithdrawableUnbondedResponse = from_binary(&query_with).unwrap();
     assert_eq!(res.withdrawable, Uint128::new(0));
 
-    env.block.time = env.block.time.plus_seconds(91);
+    env.block.time = env.block.time.plus_seconds(91);
+
+    // fabricate balance of the hub contract
+    deps.querier.with_native_balances(&[(
+        MOCK_CONTRACT_ADDR.to_string(),
+        Coin {
+            denom: "uluna".to_string(),
+            amount: Uint128::new(20),
+        },
+    )]);
+    //first query AllUnbondedRequests
+    let all_unbonded = UnbondRequests {
+        address: bob.clone(),
+    };
+    let query_unbonded = query(deps.as_ref(), mock_env(), all_unbonded).unwrap();
+    let res: UnbondRequestsResponse = from_binary(&query_unbonded).unwrap();
+    assert_eq!(res.requests.len(), 1);
+    //the amount should be 10
+    assert_eq!(&res.address, &bob);
+    assert_eq!(res.requests[0].1, Uint128::new(20));
+    assert_eq!(res.requests[0].0, 1);
+
+    let all_batches = AllHistory {
+        start_from: None,
+        limit: None,
+    };
+    let res: AllHistoryResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), all_batches).unwrap()).unwrap();
+    assert_eq!(res.history[0].amount, Uint128::new(20));
+    assert_eq!(res.history[0].batch_id, 1);
+
+    //check with query
+    let withdrawable = WithdrawableUnbonded {
+        address: bob.clone(),
+    };
+    let query_with = query(deps.as_ref(), env.clone(), withdrawable).unwrap();
+    let res: WithdrawableUnbondedResponse = from_binary(&query_with).unwrap();
+    assert_eq!(res.withdrawable, Uint128::new(20));
+
+    let success_res = execute(deps.as_mut(), env, info, wdraw_unbonded_msg).unwrap();
+
+    assert_eq!(success_res.messages.len(), 1);
+
+    let sent_message = &success_res.messages[0].msg;
+    match sent_message {
+        CosmosMsg::Bank(BankMsg::Send { to_address, amount }) => {
+            assert_eq!(to_address, &bob);
+            assert_eq!(amount[0].amount, Uint128::new(20))
+        }
+
+        _ => panic!("Unexpected message: {:?}", sent_message),
+    }
+
+    //it should be removed
+    let withdrawable = WithdrawableUnbonded {
+        address: bob.clone(),
+    };
+    let query_with: WithdrawableUnbondedResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), withdrawable).unwrap()).unwrap();
+    assert_eq!(query_with.withdrawable, Uint128::new(0));
+
+    let waitlist = UnbondRequests {
+        address: bob.clone(),
+    };
+    let query_unbond: UnbondRequestsResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), waitlist).unwrap()).unwrap();
+    assert_eq!(
+        query_unbond,
+        UnbondRequestsResponse {
+            address: bob,
+            requests: vec![]
+        }
+    );
+
+    // because of one that we add for each batch
+    let state = QueryMsg::State {};
+    let state_query: StateResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), state).unwrap()).unwrap();
+    assert_eq!(state_query.prev_hub_balance, Uint128::new(0));
+    assert_eq!(state_query.exchange_rate, Decimal::one());
+}
+
+/// Covers if the unbonded coins are transferred over IBC through the allowed channels only,
+/// and sent back to the user when the transfer times out.
+#[test]
+pub fn proper_withdraw_unbonded_ibc() {
+    let mut deps = dependencies(&[]);
+
+    let validator = sample_validator(DEFAULT_VALIDATOR.to_string());
+    set_validator_mock(&mut deps.querier);
+
+    let owner = "owner1".to_string();
+    init(
+        &mut deps,
+        owner.clone(),
+        "reward".to_string(),
+        "token".to_string(),
+    );
+    deps.querier
+        .with_validators(&[(&validator.address, Uint128::zero())]);
+
+    let bob = "bob".to_string();
+    let info = mock_info(&bob, &[coin(100, "uluna")]);
+    deps.querier
+        .with_token_balances(&[(&"token".to_string(), &[(&bob, &Uint128::new(100u128))])]);
+    execute(deps.as_mut(), mock_env(), info.clone(), ExecuteMsg::Bond {}).unwrap();
+    set_delegation(&mut deps.querier, validator, 100, "uluna");
+
+    // the second unbond passes the epoch and undelegates the batch
+    let mut env = mock_env();
+    execute_unbond(
+        deps.as_mut(),
+        env.clone(),
+        info.clone(),
+        Uint128::new(10),
+        bob.clone(),
+    )
+    .unwrap();
+    deps.querier
+        .with_token_balances(&[(&"token".to_string(), &[(&bob, &Uint128::new(90u128))])]);
+    env.block.time = env.block.time.plus_seconds(31);
+    deps.querier.with_native_balances(&[(
+        MOCK_CONTRACT_ADDR.to_string(),
+        Coin {
+            denom: "uluna".to_string(),
+            amount: Uint128::new(0),
+        },
+    )]);
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(&bob, &[]),
+        ExecuteMsg::WithdrawUnbonded {
+            recipient: None,
+            batch_ids: None,
+            ibc_transfer: None,
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::NoWithdrawableAssets {
+            denom: "uluna".to_string()
+        }
+    );
+    execute_unbond(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        Uint128::new(10),
+        bob.clone(),
+    )
+    .unwrap();
+    deps.querier
+        .with_token_balances(&[(&"token".to_string(), &[(&bob, &Uint128::new(80u128))])]);
+
+    env.block.time = env.block.time.plus_seconds(91);
+    deps.querier.with_native_balances(&[(
+        MOCK_CONTRACT_ADDR.to_string(),
+        Coin {
+            denom: "uluna".to_string(),
+            amount: Uint128::new(20),
+        },
+    )]);
+
+    let withdrawable: WithdrawableUnbondedResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            env.clone(),
+            WithdrawableUnbonded {
+                address: bob.clone(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+
+    let transfer = IbcTransfer {
+        channel_id: "channel-1".to_string(),
+        remote_address: "osmo1bob".to_string(),
+        timeout: 600,
+    };
+    let withdraw = ExecuteMsg::WithdrawUnbonded {
+        recipient: None,
+        batch_ids: None,
+        ibc_transfer: Some(transfer.clone()),
+    };
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(&bob, &[]),
+        withdraw.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(
+        res,
+        ContractError::IbcChannelNotAllowed {
+            channel_id: "channel-1".to_string()
+        }
+    );
+
+    // only the owner can allow channels
+    let allow = ExecuteMsg::AllowIbcChannel {
+        channel_id: "channel-1".to_string(),
+    };
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(&bob, &[]),
+        allow.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(res, ContractError::Unauthorized {});
+    execute(deps.as_mut(), mock_env(), mock_info(&owner, &[]), allow).unwrap();
+
+    let channels: IbcChannelsResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::IbcChannels {}).unwrap()).unwrap();
+    assert_eq!(channels.channels, vec!["channel-1".to_string()]);
+
+    // the transfer must leave time for the packet to be relayed
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(&bob, &[]),
+        ExecuteMsg::WithdrawUnbonded {
+            recipient: None,
+            batch_ids: None,
+            ibc_transfer: Some(IbcTransfer {
+                timeout: 0,
+                ..transfer.clone()
+            }),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(res, ContractError::IbcTimeoutTooShort { min: MIN_TIMEOUT });
+
+    let amount = coin(withdrawable.withdrawable.u128(), "uluna");
+    let res = execute(deps.as_mut(), env.clone(), mock_info(&bob, &[]), withdraw).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![transfer_msg(&env, transfer, &amount, TRANSFER_REPLY_ID)]
+    );
+
+    // the packet of the transfer is recorded from the reply
+    let send_packet = Event::new("send_packet")
+        .add_attribute("packet_src_channel", "channel-1")
+        .add_attribute("packet_sequence", "7");
+    reply(
+        deps.as_mut(),
+        mock_env(),
+        Reply {
+            id: TRANSFER_REPLY_ID,
+            result: SubMsgResult::Ok(SubMsgResponse {
+                events: vec![send_packet],
+                data: None,
+            }),
+        },
+    )
+    .unwrap();
+
+    // the coins refunded on a timeout are sent back to the user
+    let res = sudo(
+        deps.as_mut(),
+        mock_env(),
+        SudoMsg::IbcLifecycleComplete(IbcLifecycleComplete::IbcTimeout {
+            channel: "channel-1".to_string(),
+            sequence: 7,
+        }),
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(BankMsg::Send {
+            to_address: bob.clone(),
+            amount: vec![amount],
+        })]
+    );
+
+    // the transfer is only refunded once
+    let res = sudo(
+        deps.as_mut(),
+        mock_env(),
+        SudoMsg::IbcLifecycleComplete(IbcLifecycleComplete::IbcTimeout {
+            channel: "channel-1".to_string(),
+            sequence: 7,
+        }),
+    )
+    .unwrap();
+    assert!(res.messages.is_empty());
+
+    let disallow = ExecuteMsg::DisallowIbcChannel {
+        channel_id: "channel-1".to_string(),
+    };
+    execute(deps.as_mut(), mock_env(), mock_info(&owner, &[]), disallow).unwrap();
+    let channels: IbcChannelsResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::IbcChannels {}).unwrap()).unwrap();
+    assert!(channels.channels.is_empty());
+}
+
+/// Covers if the selected batches only are withdrawn, to another recipient if set.
+#[test]
+pub fn proper_withdraw_unbonded_batches() {
+    let mut deps = dependencies(&[]);
+
+    let validator = sample_validator(DEFAULT_VALIDATOR.to_string());
+    set_validator_mock(&mut deps.querier);
+
+    init(
+        &mut deps,
+        "owner1".to_string(),
+        "reward".to_string(),
+        "token".to_string(),
+    );
+    deps.querier
+        .with_validators(&[(&validator.address, Uint128::zero())]);
+
+    let bob = "bob".to_string();
+    let info = mock_info(&bob, &[coin(100, "uluna")]);
+    deps.querier
+        .with_token_balances(&[(&"token".to_string(), &[(&bob, &Uint128::new(100u128))])]);
+    execute(deps.as_mut(), mock_env(), info.clone(), ExecuteMsg::Bond {}).unwrap();
+    set_delegation(&mut deps.querier, validator, 100, "uluna");
+
+    // the second unbond undelegates batch 1 with 20, the third one batch 2 with 10
+    let mut env = mock_env();
+    for balance in [90u128, 80, 70] {
+        execute_unbond(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            Uint128::new(10),
+            bob.clone(),
+        )
+        .unwrap();
+        deps.querier
+            .with_token_balances(&[(&"token".to_string(), &[(&bob, &Uint128::new(balance))])]);
+        env.block.time = env.block.time.plus_seconds(31);
+    }
+
+    deps.querier.with_native_balances(&[(
+        MOCK_CONTRACT_ADDR.to_string(),
+        Coin {
+            denom: "uluna".to_string(),
+            amount: Uint128::new(30),
+        },
+    )]);
+
+    let withdraw =
+        |batch_ids: Option<Vec<u64>>, recipient: Option<String>| ExecuteMsg::WithdrawUnbonded {
+            recipient,
+            batch_ids,
+            ibc_transfer: None,
+        };
+
+    // bob has nothing in the current batch
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(&bob, &[]),
+        withdraw(Some(vec![1, 3]), None),
+    )
+    .unwrap_err();
+    assert_eq!(res, ContractError::BatchNotWithdrawable { batch_id: 3 });
+
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(&bob, &[]),
+        ExecuteMsg::WithdrawUnbonded {
+            recipient: Some("alice".to_string()),
+            batch_ids: None,
+            ibc_transfer: Some(IbcTransfer {
+                channel_id: "channel-1".to_string(),
+                remote_address: "osmo1bob".to_string(),
+                timeout: 600,
+            }),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(res, ContractError::RecipientAndIbcTransfer {});
+
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(&bob, &[]),
+        withdraw(Some(vec![1]), Some("alice".to_string())),
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(BankMsg::Send {
+            to_address: "alice".to_string(),
+            amount: coins(20, "uluna"),
+        })]
+    );
+
+    // the other batch is left in the waitlist
+    let requests: UnbondRequestsResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            UnbondRequests {
+                address: bob.clone(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(requests.requests, vec![(2, Uint128::new(10))]);
 
-    // fabricate balance of the hub contract
     deps.querier.with_native_balances(&[(
         MOCK_CONTRACT_ADDR.to_string(),
         Coin {
             denom: "uluna".to_string(),
-            amount: Uint128::new(20),
+            amount: Uint128::new(10),
         },
     )]);
-    //first query AllUnbondedRequests
-    let all_unbonded = UnbondRequests {
-        address: bob.clone(),
-    };
-    let query_unbonded = query(deps.as_ref(), mock_env(), all_unbonded).unwrap();
-    let res: UnbondRequestsResponse = from_binary(&query_unbonded).unwrap();
-    assert_eq!(res.requests.len(), 1);
-    //the amount should be 10
-    assert_eq!(&res.address, &bob);
-    assert_eq!(res.requests[0].1, Uint128::new(20));
-    assert_eq!(res.requests[0].0, 1);
-
-    let all_batches = AllHistory {
-        start_from: None,
-        limit: None,
-    };
-    let res: AllHistoryResponse =
-        from_binary(&query(deps.as_ref(), mock_env(), all_batches).unwrap()).unwrap();
-    assert_eq!(res.history[0].amount, Uint128::new(20));
-    assert_eq!(res.history[0].batch_id, 1);
-
-    //check with query
-    let withdrawable = WithdrawableUnbonded {
-        address: bob.clone(),
-    };
-    let query_with = query(deps.as_ref(), env.clone(), withdrawable).unwrap();
-    let res: WithdrawableUnbondedResponse = from_binary(&query_with).unwrap();
-    assert_eq!(res.withdrawable, Uint128::new(20));
-
-    let success_res = execute(deps.as_mut(), env, info, wdraw_unbonded_msg).unwrap();
-
-    assert_eq!(success_res.messages.len(), 1);
-
-    let sent_message = &success_res.messages[0].msg;
-    match sent_message {
-        CosmosMsg::Bank(BankMsg::Send { to_address, amount }) => {
-            assert_eq!(to_address, &bob);
-            assert_eq!(amount[0].amount, Uint128::new(20))
-        }
-
-        _ => panic!("Unexpected message: {:?}", sent_message),
-    }
-
-    //it should be removed
-    let withdrawable = WithdrawableUnbonded {
-        address: bob.clone(),
-    };
-    let query_with: WithdrawableUnbondedResponse =
-        from_binary(&query(deps.as_ref(), mock_env(), withdrawable).unwrap()).unwrap();
-    assert_eq!(query_with.withdrawable, Uint128::new(0));
-
-    let waitlist = UnbondRequests {
-        address: bob.clone(),
-    };
-    let query_unbond: UnbondRequestsResponse =
-        from_binary(&query(deps.as_ref(), mock_env(), waitlist).unwrap()).unwrap();
+    let res = execute(
+        deps.as_mut(),
+        env,
+        mock_info(&bob, &[]),
+        withdraw(None, None),
+    )
+    .unwrap();
     assert_eq!(
-        query_unbond,
-        UnbondRequestsResponse {
-            address: bob,
-            requests: vec![]
-        }
+        res.messages,
+        vec![SubMsg::new(BankMsg::Send {
+            to_address: bob.clone(),
+            amount: coins(10, "uluna"),
+        })]
     );
 
-    // because of one that we add for each batch
-    let state = QueryMsg::State {};
-    let state_query: StateResponse =
-        from_binary(&query(deps.as_ref(), mock_env(), state).unwrap()).unwrap();
-    assert_eq!(state_query.prev_hub_balance, Uint128::new(0));
-    assert_eq!(state_query.exchange_rate, Decimal::one());
+    let requests: UnbondRequestsResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), UnbondRequests { address: bob }).unwrap())
+            .unwrap();
+    assert!(requests.requests.is_empty());
 }
 
 /// Covers slashing during the unbonded period and its effect on the finished amount.
@@ -1599,7 +2669,11 @@ pub fn proper_withdraw_unbonded_respect_slashing() {
 
     env.block.time = env.block.time.plus_seconds(31);
 
-    let wdraw_unbonded_msg = ExecuteMsg::WithdrawUnbonded {};
+    let wdraw_unbonded_msg = ExecuteMsg::WithdrawUnbonded {
+        recipient: None,
+        batch_ids: None,
+        ibc_transfer: None,
+    };
     let wdraw_unbonded_res = execute(
         deps.as_mut(),
         env.clone(),
@@ -1609,7 +2683,9 @@ pub fn proper_withdraw_unbonded_respect_slashing() {
     assert!(wdraw_unbonded_res.is_err());
     assert_eq!(
         wdraw_unbonded_res.unwrap_err(),
-        StdError::generic_err("No withdrawable uluna assets are available yet")
+        ContractError::NoWithdrawableAssets {
+            denom: "uluna".to_string()
+        }
     );
 
     // trigger undelegation message
@@ -1755,7 +2831,11 @@ pub fn proper_withdraw_unbonded_respect_inactivity_slashing() {
     assert_eq!(query_batch.requested_with_fee, unbond_amount);
 
     env.block.time = env.block.time.plus_seconds(1000);
-    let wdraw_unbonded_msg = ExecuteMsg::WithdrawUnbonded {};
+    let wdraw_unbonded_msg = ExecuteMsg::WithdrawUnbonded {
+        recipient: None,
+        batch_ids: None,
+        ibc_transfer: None,
+    };
     let wdraw_unbonded_res = execute(
         deps.as_mut(),
         env.clone(),
@@ -1765,7 +2845,9 @@ pub fn proper_withdraw_unbonded_respect_inactivity_slashing() {
     assert!(wdraw_unbonded_res.is_err());
     assert_eq!(
         wdraw_unbonded_res.unwrap_err(),
-        StdError::generic_err("No withdrawable uluna assets are available yet")
+        ContractError::NoWithdrawableAssets {
+            denom: "uluna".to_string()
+        }
     );
 
     // trigger undelegation message
@@ -1988,7 +3070,11 @@ pub fn proper_withdraw_unbond_with_dummies() {
     )]);
 
     env.block.time = env.block.time.plus_seconds(120);
-    let wdraw_unbonded_msg = ExecuteMsg::WithdrawUnbonded {};
+    let wdraw_unbonded_msg = ExecuteMsg::WithdrawUnbonded {
+        recipient: None,
+        batch_ids: None,
+        ibc_transfer: None,
+    };
     let success_res = execute(deps.as_mut(), env, info, wdraw_unbonded_msg).unwrap();
 
     assert_eq!(success_res.messages.len(), 1);
@@ -2041,6 +3127,7 @@ pub fn test_update_params() {
         epoch_period: Some(20),
         peg_recovery_fee: None,
         er_threshold: None,
+        compound_ratio: None,
     };
     let owner = "owner1".to_string();
     let token_contract = "token".to_string();
@@ -2055,7 +3142,7 @@ pub fn test_update_params() {
         invalid_info,
         update_prams.clone(),
     );
-    assert_eq!(res.unwrap_err(), StdError::generic_err("unauthorized"));
+    assert_eq!(res.unwrap_err(), ContractError::Unauthorized {});
     let creator_info = mock_info("owner1", &[]);
     let res = execute(deps.as_mut(), mock_env(), creator_info, update_prams).unwrap();
     assert_eq!(res.messages.len(), 0);
@@ -2074,6 +3161,7 @@ pub fn test_update_params() {
         epoch_period: None,
         peg_recovery_fee: Some(Decimal::one()),
         er_threshold: Some(Decimal::zero()),
+        compound_ratio: None,
     };
 
     //the result must be 1
@@ -2103,6 +3191,7 @@ pub fn proper_recovery_fee() {
         epoch_period: None,
         peg_recovery_fee: Some(Decimal::from_ratio(Uint128::new(1), Uint128::new(1000))),
         er_threshold: Some(Decimal::from_ratio(Uint128::new(99), Uint128::new(100))),
+        compound_ratio: None,
     };
     let owner = "owner1".to_string();
     let token_contract = "token".to_string();
@@ -2270,7 +3359,11 @@ pub fn proper_recovery_fee() {
 
     token_env.block.time = token_env.block.time.plus_seconds(90);
     //check withdrawUnbonded message
-    let withdraw_unbond_msg = ExecuteMsg::WithdrawUnbonded {};
+    let withdraw_unbond_msg = ExecuteMsg::WithdrawUnbonded {
+        recipient: None,
+        batch_ids: None,
+        ibc_transfer: None,
+    };
     let wdraw_unbonded_res =
         execute(deps.as_mut(), token_env, token_info, withdraw_unbond_msg).unwrap();
     assert_eq!(wdraw_unbonded_res.messages.len(), 1);
@@ -2340,26 +3433,26 @@ pub fn proper_update_config() {
 
     // only the owner can call this message
     let update_config = UpdateConfig {
-        owner: Some(new_owner.clone()),
         reward_contract: None,
-        token_contract: None,
+        token_contract: Some(token_contract.clone()),
         validators_registry_contract: None,
         //airdrop_registry_contract: None,
     };
     let info = mock_info(&invalid_owner, &[]);
     let res = execute(deps.as_mut(), mock_env(), info, update_config);
-    assert_eq!(res.unwrap_err(), StdError::generic_err("unauthorized"));
+    assert_eq!(res.unwrap_err(), ContractError::Unauthorized {});
 
     // change the owner
-    let update_config = UpdateConfig {
-        owner: Some(new_owner.clone()),
-        reward_contract: None,
-        token_contract: None,
-        validators_registry_contract: None,
-        //airdrop_registry_contract: None,
+    let propose = ProposeNewOwner {
+        new_owner: new_owner.clone(),
+        expires_in: None,
     };
     let info = mock_info(&owner, &[]);
-    let res = execute(deps.as_mut(), mock_env(), info, update_config).unwrap();
+    let res = execute(deps.as_mut(), mock_env(), info, propose).unwrap();
+    assert_eq!(res.messages.len(), 0);
+
+    let info = mock_info(&new_owner, &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, AcceptOwnership {}).unwrap();
     assert_eq!(res.messages.len(), 0);
 
     let config = CONFIG.load(&deps.storage).unwrap();
@@ -2371,6 +3464,7 @@ pub fn proper_update_config() {
         epoch_period: None,
         peg_recovery_fee: None,
         er_threshold: None,
+        compound_ratio: None,
     };
 
     let new_owner_info = mock_info(new_owner.as_ref(), &[]);
@@ -2382,14 +3476,14 @@ pub fn proper_update_config() {
         epoch_period: None,
         peg_recovery_fee: None,
         er_threshold: None,
+        compound_ratio: None,
     };
 
     let new_owner_info = mock_info(&owner, &[]);
     let res = execute(deps.as_mut(), mock_env(), new_owner_info, update_prams);
-    assert_eq!(res.unwrap_err(), StdError::generic_err("unauthorized"));
+    assert_eq!(res.unwrap_err(), ContractError::Unauthorized {});
 
     let update_config = UpdateConfig {
-        owner: None,
         reward_contract: Some("new reward".to_string()),
         token_contract: None,
         validators_registry_contract: None,
@@ -2415,7 +3509,6 @@ pub fn proper_update_config() {
     );
 
     let update_config = UpdateConfig {
-        owner: None,
         reward_contract: None,
         token_contract: Some("new token".to_string()),
         validators_registry_contract: None,
@@ -2441,7 +3534,6 @@ pub fn proper_update_config() {
     assert_eq!(config_query.owner, new_owner);
 
     let update_config = UpdateConfig {
-        owner: None,
         reward_contract: None,
         token_contract: None,
         validators_registry_contract: None,
@@ -2452,6 +3544,263 @@ pub fn proper_update_config() {
     assert_eq!(res.messages.len(), 0);
 }
 
+/// Covers the propose/accept/cancel ownership transfer flow
+#[test]
+pub fn proper_ownership_transfer() {
+    let mut deps = dependencies(&[]);
+
+    set_validator_mock(&mut deps.querier);
+
+    let owner = "owner1".to_string();
+    let new_owner = "new_owner".to_string();
+    init(
+        &mut deps,
+        owner.clone(),
+        "reward".to_string(),
+        "token".to_string(),
+    );
+
+    // nothing to accept yet
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(&new_owner, &[]),
+        AcceptOwnership {},
+    );
+    assert_eq!(res.unwrap_err(), ContractError::NoPendingOwner {});
+
+    // only the owner can propose
+    let propose = ProposeNewOwner {
+        new_owner: new_owner.clone(),
+        expires_in: Some(100),
+    };
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(&new_owner, &[]),
+        propose.clone(),
+    );
+    assert_eq!(res.unwrap_err(), ContractError::Unauthorized {});
+
+    // the expiry can't overflow the block time
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(&owner, &[]),
+        ProposeNewOwner {
+            new_owner: new_owner.clone(),
+            expires_in: Some(u64::MAX),
+        },
+    );
+    assert!(matches!(res, Err(ContractError::OverflowError(_))));
+
+    execute(deps.as_mut(), mock_env(), mock_info(&owner, &[]), propose).unwrap();
+
+    let pending: PendingOwnerResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::PendingOwner {}).unwrap()).unwrap();
+    assert_eq!(
+        pending,
+        PendingOwnerResponse {
+            pending_owner: Some(new_owner.clone()),
+            expiry: Some(mock_env().block.time.seconds() + 100),
+        }
+    );
+
+    // only the proposed owner can accept
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("invalid_owner", &[]),
+        AcceptOwnership {},
+    );
+    assert_eq!(res.unwrap_err(), ContractError::Unauthorized {});
+
+    // the proposal can not be accepted after it has expired
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(100);
+    let res = execute(
+        deps.as_mut(),
+        env,
+        mock_info(&new_owner, &[]),
+        AcceptOwnership {},
+    );
+    assert_eq!(res.unwrap_err(), ContractError::OwnershipProposalExpired {});
+
+    // the owner can cancel the proposal
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(&owner, &[]),
+        CancelOwnershipTransfer {},
+    )
+    .unwrap();
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(&new_owner, &[]),
+        AcceptOwnership {},
+    );
+    assert_eq!(res.unwrap_err(), ContractError::NoPendingOwner {});
+
+    let pending: PendingOwnerResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::PendingOwner {}).unwrap()).unwrap();
+    assert_eq!(pending.pending_owner, None);
+
+    let config = CONFIG.load(&deps.storage).unwrap();
+    assert_eq!(config.creator, owner);
+}
+
+/// Covers that paused operations are rejected and that only the owner can resume them
+#[test]
+pub fn proper_pause() {
+    let mut deps = dependencies(&[]);
+
+    let validator = sample_validator(DEFAULT_VALIDATOR.to_string());
+    set_validator_mock(&mut deps.querier);
+
+    let owner = "owner1".to_string();
+    let guardian = "guardian".to_string();
+    init(
+        &mut deps,
+        owner.clone(),
+        "reward".to_string(),
+        "token".to_string(),
+    );
+    set_delegation(&mut deps.querier, validator.clone(), 10, "uluna");
+    deps.querier
+        .with_token_balances(&[(&"token".to_string(), &[])]);
+    deps.querier
+        .with_validators(&[(&validator.address, Uint128::zero())]);
+
+    // only the owner can set the guardian
+    let update_guardian = UpdateGuardian {
+        guardian: Some(guardian.clone()),
+    };
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(&guardian, &[]),
+        update_guardian.clone(),
+    );
+    assert_eq!(res.unwrap_err(), ContractError::Unauthorized {});
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(&owner, &[]),
+        update_guardian,
+    )
+    .unwrap();
+
+    // random addresses can not pause
+    let pause = Pause {
+        operations: vec![
+            PausableOperation::Bond,
+            PausableOperation::Unbond,
+            PausableOperation::Withdraw,
+            PausableOperation::UpdateGlobalIndex,
+        ],
+    };
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("villain", &[]),
+        pause.clone(),
+    );
+    assert_eq!(res.unwrap_err(), ContractError::Unauthorized {});
+
+    let res = execute(deps.as_mut(), mock_env(), mock_info(&guardian, &[]), pause).unwrap();
+    assert_eq!(
+        res.attributes[1].value,
+        "bond,unbond,withdraw,update_global_index"
+    );
+
+    let status: PauseStatusResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::PauseStatus {}).unwrap()).unwrap();
+    assert_eq!(
+        status,
+        PauseStatusResponse {
+            guardian: Some(guardian.clone()),
+            bond: true,
+            unbond: true,
+            withdraw: true,
+            update_global_index: true,
+        }
+    );
+
+    let info = mock_info("addr1000", &[coin(10, "uluna")]);
+    let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Bond {});
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::OperationPaused {
+            operation: "bond".to_string()
+        }
+    );
+
+    let receive = Receive(Cw20ReceiveMsg {
+        sender: "addr1000".to_string(),
+        amount: Uint128::new(10),
+        msg: to_binary(&Unbond {}).unwrap(),
+    });
+    let res = execute(deps.as_mut(), mock_env(), mock_info("token", &[]), receive);
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::OperationPaused {
+            operation: "unbond".to_string()
+        }
+    );
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr1000", &[]),
+        ExecuteMsg::WithdrawUnbonded {
+            recipient: None,
+            batch_ids: None,
+            ibc_transfer: None,
+        },
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::OperationPaused {
+            operation: "withdraw".to_string()
+        }
+    );
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr1000", &[]),
+        ExecuteMsg::UpdateGlobalIndex {},
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::OperationPaused {
+            operation: "update_global_index".to_string()
+        }
+    );
+
+    // the guardian can not resume the operations
+    let unpause = Unpause {
+        operations: vec![PausableOperation::Bond],
+    };
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(&guardian, &[]),
+        unpause.clone(),
+    );
+    assert_eq!(res.unwrap_err(), ContractError::Unauthorized {});
+    execute(deps.as_mut(), mock_env(), mock_info(&owner, &[]), unpause).unwrap();
+
+    let status: PauseStatusResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::PauseStatus {}).unwrap()).unwrap();
+    assert!(!status.bond);
+    assert!(status.unbond);
+
+    let info = mock_info("addr1000", &[coin(10, "uluna")]);
+    execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Bond {}).unwrap();
+}
+
 #[test]
 fn proper_update_global_index_with_airdrop() {
     let mut deps = dependencies(&[]);
@@ -2493,14 +3842,17 @@ fn proper_update_global_index_with_airdrop() {
 
     let info = mock_info(&addr1, &[]);
     let res = execute(deps.as_mut(), mock_env(), info, reward_msg).unwrap();
-    assert_eq!(3, res.messages.len());
+    assert_eq!(4, res.messages.len());
 }
 
 fn set_delegation(querier: &mut WasmMockQuerier, validator: Validator, amount: u128, denom: &str) {
     querier.update_staking(
         "uluna",
-        &[validator.clone()],
-        &[sample_delegation(validator.address, coin(amount, denom))],
+        std::slice::from_ref(&validator),
+        &[sample_delegation(
+            validator.address.clone(),
+            coin(amount, denom),
+        )],
     );
 }
 
@@ -2524,9 +3876,72 @@ fn sample_delegation(addr: String, amount: Coin) -> FullDelegation {
     }
 }
 
-// sample MIR claim msg
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
-#[serde(rename_all = "snake_case")]
-pub enum MIRMsg {
-    MIRClaim {},
+/// Covers that instant unbonding sells the tokens on the best priced router with slippage protection
+#[test]
+pub fn proper_instant_unbond() {
+    let mut deps = dependencies(&[]);
+
+    set_validator_mock(&mut deps.querier);
+    let owner = "owner1".to_string();
+    init(&mut deps, owner, "reward".to_string(), "token".to_string());
+
+    // only the token contract can send the hook
+    let receive = Receive(Cw20ReceiveMsg {
+        sender: "addr1000".to_string(),
+        amount: Uint128::new(1000),
+        msg: to_binary(&InstantUnbond {
+            min_receive: Uint128::new(900),
+        })
+        .unwrap(),
+    });
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("villain", &[]),
+        receive.clone(),
+    );
+    assert_eq!(res.unwrap_err(), ContractError::Unauthorized {});
+
+    // Phoenix gives the best price
+    let res = execute(deps.as_mut(), mock_env(), mock_info("token", &[]), receive).unwrap();
+    assert_eq!(res.messages.len(), 1);
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "token".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Send {
+                contract: "phoenix_addr".to_string(),
+                amount: Uint128::new(1000),
+                msg: to_binary(&RouterCw20HookMsg::ExecuteSwapOperations {
+                    operations: vec![SwapOperation::TokenSwap {
+                        offer_asset_info: AssetInfo::Token {
+                            contract_addr: Addr::unchecked("token"),
+                        },
+                        ask_asset_info: AssetInfo::NativeToken {
+                            denom: "uluna".to_string(),
+                        },
+                    }],
+                    minimum_receive: Some(Uint128::new(900)),
+                    to: Some("addr1000".to_string()),
+                    max_spread: None,
+                })
+                .unwrap(),
+            })
+            .unwrap(),
+            funds: vec![],
+        })
+    );
+    assert_eq!(res.attributes[4].value, "950");
+
+    // the best price is below the minimum to receive
+    let receive = Receive(Cw20ReceiveMsg {
+        sender: "addr1000".to_string(),
+        amount: Uint128::new(1000),
+        msg: to_binary(&InstantUnbond {
+            min_receive: Uint128::new(951),
+        })
+        .unwrap(),
+    });
+    let res = execute(deps.as_mut(), mock_env(), mock_info("token", &[]), receive);
+    assert_eq!(res.unwrap_err(), ContractError::MaxSlippageAssertion {});
 }
@@ -1,17 +1,33 @@
-use crate::contract::{query_total_issued, slashing};
+use crate::config::assert_not_paused;
+use crate::contract::{
+    query_total_issued, slashing, store_exchange_rate_snapshot, TRANSFER_REPLY_ID,
+};
 use crate::state::{
     get_finished_amount, get_unbond_batches, read_unbond_history, remove_unbond_wait_list,
-    store_unbond_history, store_unbond_wait_list, CONFIG, CURRENT_BATCH, PARAMETERS, STATE,
+    store_unbond_history, store_unbond_wait_list, CONFIG, CURRENT_BATCH, IBC_CHANNELS,
+    NATIVE_TOKEN, PARAMETERS, PENDING_TRANSFER, PENDING_TRANSFERS, STATE,
+};
+use basset::contract_error::ContractError;
+use basset::dex_router::{
+    find_best_venue, AssetInfo, Cw20HookMsg as RouterCw20HookMsg, OperationFlavour,
 };
-use basset::hub::{State, UnbondHistory};
+use basset::hub::{PausableOperation, State, UnbondHistory};
+use basset::ibc::{
+    transfer_msg, transfer_packet, IbcLifecycleComplete, IbcTransfer, PendingTransfer, MIN_TIMEOUT,
+};
+use basset::reward::{DexesResponse, QueryMsg as RewardQueryMsg};
+use basset::token_factory::burn_msg;
 use cosmwasm_std::{
     attr, coin, coins, to_binary, BankMsg, CosmosMsg, Decimal, Deps, DepsMut, Env, MessageInfo,
-    Response, StakingMsg, StdError, StdResult, Storage, Uint128, WasmMsg,
+    QueryRequest, Reply, Response, StakingMsg, StdResult, Storage, SubMsg, Uint128, WasmMsg,
+    WasmQuery,
 };
 use cw20::Cw20ExecuteMsg;
 use lido_terra_validators_registry::common::calculate_undelegations;
+use lido_terra_validators_registry::msg::QueryMsg as QueryValidators;
 use lido_terra_validators_registry::registry::ValidatorResponse;
 use signed_integer::SignedInt;
+use std::collections::HashMap;
 
 /// This message must be call by receive_cw20
 /// This message will undelegate coin and burn basset token
@@ -21,7 +37,88 @@ pub(crate) fn execute_unbond(
     _info: MessageInfo,
     amount: Uint128,
     sender: String,
-) -> StdResult<Response> {
+) -> Result<Response, ContractError> {
+    let (amount_with_fee, mut messages) = unbond(deps.branch(), env, amount, sender.clone())?;
+
+    // Send Burn message to token contract
+    let config = CONFIG.load(deps.storage)?;
+    let token_address = &config
+        .token_contract
+        .ok_or(ContractError::MissingRegisteredContract {
+            contract: "token".to_string(),
+        })?;
+
+    let burn_msg = Cw20ExecuteMsg::Burn { amount };
+    messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: token_address.to_string(),
+        msg: to_binary(&burn_msg)?,
+        funds: vec![],
+    }));
+
+    Ok(Response::new().add_messages(messages).add_attributes(vec![
+        attr("action", "burn"),
+        attr("from", sender),
+        attr("burnt_amount", amount),
+        attr("unbonded_amount", amount_with_fee),
+    ]))
+}
+
+/// Unbond like `execute_unbond`, burning the native token sent along. The bAsset it stands for
+/// is unbonded
+pub(crate) fn execute_unbond_native(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let mut native_token = NATIVE_TOKEN
+        .may_load(deps.storage)?
+        .ok_or(ContractError::NoNativeToken {})?;
+
+    if info.funds.len() > 1usize {
+        return Err(ContractError::MultipleCoins {});
+    }
+    let payment = info
+        .funds
+        .iter()
+        .find(|x| x.denom == native_token.denom && !x.amount.is_zero())
+        .ok_or_else(|| ContractError::WrongDenom {
+            denom: native_token.denom.clone(),
+        })?
+        .clone();
+
+    let sender = info.sender;
+    let hub = env.contract.address.to_string();
+    // the native token stays in the supply while the exchange rate is computed by unbond
+    let (amount_with_fee, mut messages) = unbond(
+        deps.branch(),
+        env,
+        payment.amount * native_token.basset_rate,
+        sender.to_string(),
+    )?;
+
+    native_token.supply = native_token.supply.checked_sub(payment.amount)?;
+    NATIVE_TOKEN.save(deps.storage, &native_token)?;
+
+    messages.push(burn_msg(&hub, &payment));
+
+    Ok(Response::new().add_messages(messages).add_attributes(vec![
+        attr("action", "burn_native"),
+        attr("from", sender),
+        attr("burnt_amount", payment.amount),
+        attr("unbonded_amount", amount_with_fee),
+    ]))
+}
+
+/// Add `amount` of basset to the current batch and undelegate the batch once the epoch period
+/// has passed. Returns the amount requested with the peg recovery fee and the undelegations
+fn unbond(
+    mut deps: DepsMut,
+    env: Env,
+    amount: Uint128,
+    sender: String,
+) -> Result<(Uint128, Vec<CosmosMsg>), ContractError> {
+    assert_not_paused(deps.storage, PausableOperation::Unbond)?;
+
     // Read params
     let params = PARAMETERS.load(deps.storage)?;
     let epoch_period = params.epoch_period;
@@ -75,9 +172,9 @@ pub(crate) fn execute_unbond(
 
         // the contract must stop if
         if undelegation_amount <= Uint128::new(1) {
-            return Err(StdError::generic_err(
-                "Burn amount must be greater than 1 ubluna",
-            ));
+            return Err(ContractError::BatchTooSmall {
+                denom: params.underlying_coin_denom,
+            });
         }
 
         let delegator = env.contract.address;
@@ -107,6 +204,8 @@ pub(crate) fn execute_unbond(
 
         // state.last_unbonded_time must be updated to the current block time
         state.last_unbonded_time = env.block.time.seconds();
+
+        store_exchange_rate_snapshot(deps.branch(), current_time, &state)?;
     }
 
     // Store the new requested_with_fee or id in the current batch
@@ -115,33 +214,37 @@ pub(crate) fn execute_unbond(
     // Store state's new exchange rate
     STATE.save(deps.storage, &state)?;
 
-    // Send Burn message to token contract
-    let config = CONFIG.load(deps.storage)?;
-    let token_address = &config
-        .token_contract
-        .ok_or_else(|| StdError::generic_err("the token contract must have been registered"))?;
-
-    let burn_msg = Cw20ExecuteMsg::Burn { amount };
-    messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
-        contract_addr: token_address.to_string(),
-        msg: to_binary(&burn_msg)?,
-        funds: vec![],
-    }));
-
-    Ok(Response::new().add_messages(messages).add_attributes(vec![
-        attr("action", "burn"),
-        attr("from", sender),
-        attr("burnt_amount", amount),
-        attr("unbonded_amount", amount_with_fee),
-    ]))
+    Ok((amount_with_fee, messages))
 }
 
 pub fn execute_withdraw_unbonded(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
-) -> StdResult<Response> {
+    recipient: Option<String>,
+    batch_ids: Option<Vec<u64>>,
+    ibc_transfer: Option<IbcTransfer>,
+) -> Result<Response, ContractError> {
+    assert_not_paused(deps.storage, PausableOperation::Withdraw)?;
+    if recipient.is_some() && ibc_transfer.is_some() {
+        return Err(ContractError::RecipientAndIbcTransfer {});
+    }
+    if let Some(transfer) = &ibc_transfer {
+        if !IBC_CHANNELS.has(deps.storage, &transfer.channel_id) {
+            return Err(ContractError::IbcChannelNotAllowed {
+                channel_id: transfer.channel_id.clone(),
+            });
+        }
+        if transfer.timeout < MIN_TIMEOUT {
+            return Err(ContractError::IbcTimeoutTooShort { min: MIN_TIMEOUT });
+        }
+    }
+
     let sender_human = info.sender;
+    let recipient = match recipient {
+        Some(recipient) => deps.api.addr_validate(&recipient)?,
+        None => sender_human.clone(),
+    };
 
     // read params
     let params = PARAMETERS.load(deps.storage)?;
@@ -159,17 +262,27 @@ pub fn execute_withdraw_unbonded(
     // calculate withdraw rate for user requests
     process_withdraw_rate(deps.storage, historical_time, hub_balance)?;
 
-    let withdraw_amount = get_finished_amount(deps.storage, sender_human.to_string()).unwrap();
+    // only the released batches can be withdrawn
+    let deprecated_batches =
+        get_unbond_batches(deps.storage, sender_human.to_string(), batch_ids.as_deref())?;
+    if let Some(batch_id) = batch_ids
+        .iter()
+        .flatten()
+        .find(|batch_id| !deprecated_batches.contains(batch_id))
+    {
+        return Err(ContractError::BatchNotWithdrawable {
+            batch_id: *batch_id,
+        });
+    }
+
+    let withdraw_amount =
+        get_finished_amount(deps.storage, sender_human.to_string(), batch_ids.as_deref()).unwrap();
 
     if withdraw_amount.is_zero() {
-        return Err(StdError::generic_err(format!(
-            "No withdrawable {} assets are available yet",
-            coin_denom
-        )));
+        return Err(ContractError::NoWithdrawableAssets { denom: coin_denom });
     }
 
-    // remove the previous batches for the user
-    let deprecated_batches = get_unbond_batches(deps.storage, sender_human.to_string())?;
+    // remove the withdrawn batches for the user
     remove_unbond_wait_list(deps.storage, deprecated_batches, sender_human.clone())?;
 
     // Update previous balance used for calculation in next Luna batch release
@@ -180,19 +293,93 @@ pub fn execute_withdraw_unbonded(
     })?;
 
     // Send the money to the user
-    let bank_msg: CosmosMsg = BankMsg::Send {
-        to_address: sender_human.to_string(),
-        amount: coins(withdraw_amount.u128(), &*coin_denom),
+    let mut attrs = vec![
+        attr("action", "finish_burn"),
+        attr("from", sender_human.as_str()),
+        attr("amount", withdraw_amount),
+    ];
+    if let Some(batch_ids) = &batch_ids {
+        let batch_ids: Vec<String> = batch_ids.iter().map(|id| id.to_string()).collect();
+        attrs.push(attr("batch_ids", batch_ids.join(",")));
     }
-    .into();
+    let msg = match ibc_transfer {
+        Some(transfer) => {
+            attrs.push(attr("channel_id", transfer.channel_id.as_str()));
+            attrs.push(attr("remote_address", transfer.remote_address.as_str()));
+            // the coins are sent back to the user if the transfer fails
+            let amount = coin(withdraw_amount.u128(), &*coin_denom);
+            PENDING_TRANSFER.save(
+                deps.storage,
+                &PendingTransfer {
+                    owner: sender_human,
+                    amount: amount.clone(),
+                },
+            )?;
+            transfer_msg(&env, transfer, &amount, TRANSFER_REPLY_ID)
+        }
+        None => {
+            attrs.push(attr("recipient", recipient.as_str()));
+            SubMsg::new(BankMsg::Send {
+                to_address: recipient.to_string(),
+                amount: coins(withdraw_amount.u128(), &*coin_denom),
+            })
+        }
+    };
 
-    Ok(Response::new()
-        .add_attributes(vec![
-            attr("action", "finish_burn"),
-            attr("from", sender_human),
-            attr("amount", withdraw_amount),
-        ])
-        .add_message(bank_msg))
+    Ok(Response::new().add_attributes(attrs).add_submessage(msg))
+}
+
+/// Record the packet of a withdrawal transferred over IBC
+pub fn reply_ibc_transfer(deps: DepsMut, msg: Reply) -> Result<Response, ContractError> {
+    let pending_transfer = PENDING_TRANSFER.load(deps.storage)?;
+    PENDING_TRANSFER.remove(deps.storage);
+
+    let (channel, sequence) = transfer_packet(msg)?;
+    PENDING_TRANSFERS.save(deps.storage, (&channel, sequence), &pending_transfer)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "ibc_transfer"),
+        attr("channel_id", channel),
+        attr("sequence", sequence.to_string()),
+    ]))
+}
+
+/// Send the coins of a failed withdrawal transfer, refunded to the hub, back to the user.
+/// The refund and the send cancel out in the balance the withdraw rate is calculated from
+pub fn sudo_ibc_lifecycle_complete(
+    deps: DepsMut,
+    msg: IbcLifecycleComplete,
+) -> Result<Response, ContractError> {
+    let (channel, sequence, success) = match msg {
+        IbcLifecycleComplete::IbcAck {
+            channel,
+            sequence,
+            success,
+            ..
+        } => (channel, sequence, success),
+        IbcLifecycleComplete::IbcTimeout { channel, sequence } => (channel, sequence, false),
+    };
+    let pending_transfer = match PENDING_TRANSFERS.may_load(deps.storage, (&channel, sequence))? {
+        Some(pending_transfer) => pending_transfer,
+        None => return Ok(Response::new()),
+    };
+    PENDING_TRANSFERS.remove(deps.storage, (&channel, sequence));
+
+    let mut res = Response::new().add_attributes(vec![
+        attr("action", "ibc_transfer_complete"),
+        attr("channel_id", channel),
+        attr("sequence", sequence.to_string()),
+        attr("success", success.to_string()),
+    ]);
+    if !success {
+        res = res
+            .add_attribute("refunded", pending_transfer.owner.as_str())
+            .add_message(BankMsg::Send {
+                to_address: pending_transfer.owner.to_string(),
+                amount: vec![pending_transfer.amount],
+            });
+    }
+    Ok(res)
 }
 
 /// This is designed for an accurate unbonded amount calculation.
@@ -307,25 +494,138 @@ fn process_withdraw_rate(
     Ok(())
 }
 
-fn pick_validator(deps: Deps, claim: Uint128, delegator: String) -> StdResult<Vec<CosmosMsg>> {
+/// This message must be call by receive_cw20
+/// This message sells the received basset tokens on the liquidity pool with the best price,
+/// the underlying coins are sent to the sender by the dex router
+pub(crate) fn execute_instant_unbond(
+    deps: DepsMut,
+    _env: Env,
+    amount: Uint128,
+    sender: String,
+    min_receive: Uint128,
+) -> Result<Response, ContractError> {
+    assert_not_paused(deps.storage, PausableOperation::Unbond)?;
+
+    let params = PARAMETERS.load(deps.storage)?;
+    let config = CONFIG.load(deps.storage)?;
+    let token_address = config
+        .token_contract
+        .ok_or(ContractError::MissingRegisteredContract {
+            contract: "token".to_string(),
+        })?;
+    let reward_address =
+        config
+            .reward_contract
+            .ok_or(ContractError::MissingRegisteredContract {
+                contract: "reward".to_string(),
+            })?;
+
+    // the dexes are the enabled ones of the reward contract
+    let dexes: DexesResponse = deps
+        .querier
+        .query_wasm_smart(reward_address, &RewardQueryMsg::Dexes {})?;
+    let venues: Vec<(OperationFlavour, String)> = dexes
+        .dexes
+        .into_iter()
+        .filter(|dex| dex.enabled)
+        .map(|dex| (dex.flavour, dex.router))
+        .collect();
+
+    let offer_asset_info = AssetInfo::Token {
+        contract_addr: token_address.clone(),
+    };
+    let ask_asset_info = AssetInfo::NativeToken {
+        denom: params.underlying_coin_denom.clone(),
+    };
+    let (best_index, best_amount) = find_best_venue(
+        &deps.querier,
+        &venues,
+        amount,
+        &offer_asset_info,
+        &ask_asset_info,
+    )
+    .filter(|(_, best_amount)| !best_amount.is_zero())
+    .ok_or_else(|| ContractError::NoSwapVenue {
+        offer: token_address.to_string(),
+        ask: params.underlying_coin_denom.clone(),
+    })?;
+    if best_amount < min_receive {
+        return Err(ContractError::MaxSlippageAssertion {});
+    }
+
+    let (flavour, router) = venues[best_index].clone();
+    let swap_msg = Cw20ExecuteMsg::Send {
+        contract: router.clone(),
+        amount,
+        msg: to_binary(&RouterCw20HookMsg::ExecuteSwapOperations {
+            operations: vec![flavour.swap_operation(offer_asset_info, ask_asset_info)],
+            minimum_receive: Some(min_receive),
+            to: Some(sender.clone()),
+            max_spread: None,
+        })?,
+    };
+
+    Ok(Response::new()
+        .add_message(WasmMsg::Execute {
+            contract_addr: token_address.to_string(),
+            msg: to_binary(&swap_msg)?,
+            funds: vec![],
+        })
+        .add_attributes(vec![
+            attr("action", "instant_unbond"),
+            attr("from", sender),
+            attr("amount", amount),
+            attr("router", router),
+            attr("expected_receive", best_amount),
+            attr("min_receive", min_receive),
+        ]))
+}
+
+fn pick_validator(
+    deps: Deps,
+    claim: Uint128,
+    delegator: String,
+) -> Result<Vec<CosmosMsg>, ContractError> {
     //read params
     let params = PARAMETERS.load(deps.storage)?;
     let coin_denom = params.underlying_coin_denom;
+    let config = CONFIG.load(deps.storage)?;
 
     let mut messages: Vec<CosmosMsg> = vec![];
 
     let all_delegations = deps.querier.query_all_delegations(delegator)?;
 
-    // Instead of picking a random validator, we undelegate across all validators
-
+    let validators_registry_contract =
+        config
+            .validators_registry_contract
+            .ok_or(ContractError::MissingRegisteredContract {
+                contract: "validators registry".to_string(),
+            })?;
+    let registered_validators: Vec<ValidatorResponse> =
+        deps.querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+            contract_addr: validators_registry_contract.to_string(),
+            msg: to_binary(&QueryValidators::GetValidatorsForDelegation {})?,
+        }))?;
+    let registered_validators: HashMap<String, ValidatorResponse> = registered_validators
+        .into_iter()
+        .map(|v| (v.address.clone(), v))
+        .collect();
+
+    // Instead of picking a random validator, we undelegate across all validators.
+    // Validators that are no longer registered have no weight so they are drained first.
     let mut validators = all_delegations
         .iter()
-        .map(|d| ValidatorResponse {
-            total_delegated: d.amount.amount,
-            address: d.validator.clone(),
+        .map(|d| {
+            let registered = registered_validators.get(&d.validator);
+            ValidatorResponse {
+                total_delegated: d.amount.amount,
+                address: d.validator.clone(),
+                weight: registered.map_or(0, |v| v.weight),
+                max_cap: registered.and_then(|v| v.max_cap),
+            }
         })
         .collect::<Vec<ValidatorResponse>>();
-    validators.sort_by(|v1, v2| v2.total_delegated.cmp(&v1.total_delegated));
+    validators.sort_by_key(|v| std::cmp::Reverse(v.total_delegated));
 
     let undelegations = calculate_undelegations(claim, validators.clone())?;
 
@@ -1,51 +1,100 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 
-use crate::global::{execute_swap, execute_update_global_index};
+use crate::global::{
+    execute_collect_compound_protocol_fee, execute_swap, execute_update_global_index,
+    query_index_history, query_rewards_for_period, reply_failed_swap,
+};
+use crate::owner::{
+    assert_protocol_fee, assert_slippage_tolerance, assert_split_chunks, execute_accept_ownership,
+    execute_add_dex, execute_cancel_ownership_transfer, execute_clear_swap_denom,
+    execute_clear_swap_route, execute_propose_new_owner, execute_remove_dex,
+    execute_set_swap_denom, execute_set_swap_route, execute_update_dex, execute_update_fee_config,
+    execute_update_ibc_channel, execute_update_swap_config, register_dex,
+};
 use crate::state::{
-    read_config, read_state, store_config, store_state, Config, State, SwapConfig, CONFIG,
-    OLD_CONFIG, SWAP_CONFIG,
+    default_slippage_tolerance, read_config, read_state, store_config, store_index_update,
+    store_state, Config, Dex, State, SwapConfig, CONFIG, DEXES, FAILED_SWAPS, IBC_CHANNELS,
+    OLD_CONFIG, OLD_SWAP_CONFIG, PENDING_OWNER, SWAP_CONFIG, SWAP_DENOMS, SWAP_ROUTES,
+    UNOWNED_CONFIG,
 };
 use crate::user::{
-    execute_claim_rewards, execute_decrease_balance, execute_increase_balance,
-    query_accrued_rewards, query_holder, query_holders,
+    execute_approve_claimer, execute_claim_rewards, execute_claim_rewards_for,
+    execute_decrease_balance, execute_increase_balance, execute_revoke_claimer,
+    execute_set_auto_claim, query_accrued_rewards, query_auto_claim, query_claimers, query_holder,
+    query_holders, reply_ibc_transfer, sudo_ibc_lifecycle_complete,
 };
 use cosmwasm_std::{
-    to_binary, Binary, Decimal256, Deps, DepsMut, Env, MessageInfo, Response, StdResult, Uint128,
+    to_binary, Binary, Decimal256, Deps, DepsMut, Env, MessageInfo, Order, Reply, Response,
+    StdError, StdResult, Uint128,
 };
 
+use basset::dex_router::OperationFlavour;
+use basset::ibc::IbcChannelsResponse;
+use basset::ownership::PendingOwnerResponse;
 use basset::reward::{
-    ConfigResponse, ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg, StateResponse,
+    ConfigResponse, DexResponse, DexesResponse, ExecuteMsg, FailedSwapResponse,
+    FailedSwapsResponse, InstantiateMsg, MigrateMsg, QueryMsg, StateResponse, SudoMsg,
+    SwapDenomResponse, SwapDenomsResponse, SwapRouteResponse, SwapRoutesResponse,
 };
 
+/// Id of the IBC transfer submessages of the claims. The swap submessages are numbered from 0,
+/// one per swapped chunk, and stay far below it
+pub const TRANSFER_REPLY_ID: u64 = 1 << 32;
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
     deps: DepsMut,
-    _env: Env,
-    _info: MessageInfo,
+    env: Env,
+    info: MessageInfo,
     msg: InstantiateMsg,
 ) -> StdResult<Response> {
+    if let Some(tolerance) = msg.slippage_tolerance {
+        assert_slippage_tolerance(tolerance)?;
+    }
+    assert_split_chunks(msg.split_chunks)?;
+    let protocol_fee_bps = msg.protocol_fee_bps.unwrap_or_default();
+    let fee_collector = msg
+        .fee_collector
+        .map(|fee_collector| deps.api.addr_validate(&fee_collector))
+        .transpose()?;
+    assert_protocol_fee(protocol_fee_bps, &fee_collector)?;
+    for dex in msg.dexes {
+        register_dex(deps.storage, deps.api, dex)?;
+    }
+
     let conf = Config {
+        owner: info.sender,
         hub_contract: deps.api.addr_validate(&msg.hub_contract)?,
         reward_denom: msg.reward_denom,
+        protocol_fee_bps,
+        fee_collector,
     };
 
     store_config(deps.storage, &conf)?;
-    store_state(
+    let state = State {
+        global_index: Decimal256::zero(),
+        total_balance: Uint128::zero(),
+        prev_reward_balance: Uint128::zero(),
+        total_protocol_fee: Uint128::zero(),
+        total_compound_protocol_fee: Uint128::zero(),
+    };
+    store_state(deps.storage, &state)?;
+    store_index_update(
         deps.storage,
-        &State {
-            global_index: Decimal256::zero(),
-            total_balance: Uint128::zero(),
-            prev_reward_balance: Uint128::zero(),
-        },
+        env.block.time.seconds(),
+        Uint128::zero(),
+        &state,
     )?;
 
     SWAP_CONFIG.save(
         deps.storage,
         &SwapConfig {
-            astroport_addr: deps.api.addr_validate(&msg.astroport_addr)?,
-            phoenix_addr: deps.api.addr_validate(&msg.phoenix_addr)?,
-            terraswap_addr: deps.api.addr_validate(&msg.terraswap_addr)?,
+            max_spread: msg.max_spread,
+            slippage_tolerance: msg
+                .slippage_tolerance
+                .unwrap_or_else(default_slippage_tolerance),
+            split_chunks: msg.split_chunks,
         },
     )?;
 
@@ -55,15 +104,96 @@ pub fn instantiate(
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn execute(deps: DepsMut, env: Env, info: MessageInfo, msg: ExecuteMsg) -> StdResult<Response> {
     match msg {
-        ExecuteMsg::ClaimRewards { recipient } => execute_claim_rewards(deps, env, info, recipient),
+        ExecuteMsg::ClaimRewards {
+            recipient,
+            ibc_transfer,
+        } => execute_claim_rewards(deps, env, info, recipient, ibc_transfer),
+        ExecuteMsg::ClaimRewardsFor { holders } => {
+            execute_claim_rewards_for(deps, env, info, holders)
+        }
+        ExecuteMsg::ApproveClaimer { claimer, recipient } => {
+            execute_approve_claimer(deps, env, info, claimer, recipient)
+        }
+        ExecuteMsg::RevokeClaimer { claimer } => execute_revoke_claimer(deps, env, info, claimer),
+        ExecuteMsg::SetAutoClaim { enabled, recipient } => {
+            execute_set_auto_claim(deps, env, info, enabled, recipient)
+        }
         ExecuteMsg::SwapToRewardDenom {} => execute_swap(deps, env, info),
         ExecuteMsg::UpdateGlobalIndex {} => execute_update_global_index(deps, env, info),
+        ExecuteMsg::CollectCompoundProtocolFee {} => {
+            execute_collect_compound_protocol_fee(deps, env, info)
+        }
         ExecuteMsg::IncreaseBalance { address, amount } => {
             execute_increase_balance(deps, env, info, address, amount)
         }
         ExecuteMsg::DecreaseBalance { address, amount } => {
             execute_decrease_balance(deps, env, info, address, amount)
         }
+        ExecuteMsg::ProposeNewOwner {
+            new_owner,
+            expires_in,
+        } => execute_propose_new_owner(deps, env, info, new_owner, expires_in),
+        ExecuteMsg::CancelOwnershipTransfer {} => {
+            execute_cancel_ownership_transfer(deps, env, info)
+        }
+        ExecuteMsg::AcceptOwnership {} => execute_accept_ownership(deps, env, info),
+        ExecuteMsg::UpdateSwapConfig {
+            max_spread,
+            slippage_tolerance,
+            split_chunks,
+        } => execute_update_swap_config(
+            deps,
+            env,
+            info,
+            max_spread,
+            slippage_tolerance,
+            split_chunks,
+        ),
+        ExecuteMsg::UpdateFeeConfig {
+            protocol_fee_bps,
+            fee_collector,
+        } => execute_update_fee_config(deps, env, info, protocol_fee_bps, fee_collector),
+        ExecuteMsg::SetSwapRoute {
+            offer_denom,
+            dex,
+            operations,
+        } => execute_set_swap_route(deps, env, info, offer_denom, dex, operations),
+        ExecuteMsg::ClearSwapRoute { offer_denom, dex } => {
+            execute_clear_swap_route(deps, env, info, offer_denom, dex)
+        }
+        ExecuteMsg::AddDex { dex } => execute_add_dex(deps, env, info, dex),
+        ExecuteMsg::RemoveDex { name } => execute_remove_dex(deps, env, info, name),
+        ExecuteMsg::UpdateDex { name, enabled } => {
+            execute_update_dex(deps, env, info, name, enabled)
+        }
+        ExecuteMsg::SetSwapDenom {
+            denom,
+            listing,
+            min_amount,
+        } => execute_set_swap_denom(deps, env, info, denom, listing, min_amount),
+        ExecuteMsg::ClearSwapDenom { denom } => execute_clear_swap_denom(deps, env, info, denom),
+        ExecuteMsg::AllowIbcChannel { channel_id } => {
+            execute_update_ibc_channel(deps, env, info, channel_id, true)
+        }
+        ExecuteMsg::DisallowIbcChannel { channel_id } => {
+            execute_update_ibc_channel(deps, env, info, channel_id, false)
+        }
+    }
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(deps: DepsMut, env: Env, msg: Reply) -> StdResult<Response> {
+    match msg.id {
+        TRANSFER_REPLY_ID => reply_ibc_transfer(deps, msg),
+        _ => reply_failed_swap(deps, env, msg),
+    }
+}
+
+/// The completion of the IBC transfers
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn sudo(deps: DepsMut, _env: Env, msg: SudoMsg) -> StdResult<Response> {
+    match msg {
+        SudoMsg::IbcLifecycleComplete(msg) => sudo_ibc_lifecycle_complete(deps, msg),
     }
 }
 
@@ -77,36 +207,211 @@ pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
         QueryMsg::Holders { start_after, limit } => {
             to_binary(&query_holders(deps, start_after, limit)?)
         }
+        QueryMsg::PendingOwner {} => to_binary(&query_pending_owner(deps)?),
+        QueryMsg::Dexes {} => to_binary(&query_dexes(deps)?),
+        QueryMsg::IbcChannels {} => to_binary(&query_ibc_channels(deps)?),
+        QueryMsg::SwapRoutes {} => to_binary(&query_swap_routes(deps)?),
+        QueryMsg::SwapDenoms {} => to_binary(&query_swap_denoms(deps)?),
+        QueryMsg::FailedSwaps {} => to_binary(&query_failed_swaps(deps)?),
+        QueryMsg::Claimers { holder } => to_binary(&query_claimers(deps, holder)?),
+        QueryMsg::AutoClaim { address } => to_binary(&query_auto_claim(deps, address)?),
+        QueryMsg::IndexHistory { start_after, limit } => {
+            to_binary(&query_index_history(deps, start_after, limit)?)
+        }
+        QueryMsg::RewardsForPeriod {
+            balance,
+            start,
+            end,
+        } => to_binary(&query_rewards_for_period(deps, balance, start, end)?),
     }
 }
 
+fn query_swap_routes(deps: Deps) -> StdResult<SwapRoutesResponse> {
+    let routes = SWAP_ROUTES
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            let ((offer_denom, _), route) = item?;
+            Ok(SwapRouteResponse {
+                offer_denom,
+                dex: route.dex,
+                operations: route.operations,
+            })
+        })
+        .collect::<StdResult<Vec<SwapRouteResponse>>>()?;
+    Ok(SwapRoutesResponse { routes })
+}
+
+fn query_failed_swaps(deps: Deps) -> StdResult<FailedSwapsResponse> {
+    let failed_swaps = FAILED_SWAPS
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            let (denom, failed_swap) = item?;
+            Ok(FailedSwapResponse {
+                denom,
+                amount: failed_swap.amount,
+                error: failed_swap.error,
+                failed_at: failed_swap.failed_at,
+            })
+        })
+        .collect::<StdResult<Vec<FailedSwapResponse>>>()?;
+    Ok(FailedSwapsResponse { failed_swaps })
+}
+
+fn query_swap_denoms(deps: Deps) -> StdResult<SwapDenomsResponse> {
+    let denoms = SWAP_DENOMS
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            let (denom, swap_denom) = item?;
+            Ok(SwapDenomResponse {
+                denom,
+                listing: swap_denom.listing,
+                min_amount: swap_denom.min_amount,
+            })
+        })
+        .collect::<StdResult<Vec<SwapDenomResponse>>>()?;
+    Ok(SwapDenomsResponse { denoms })
+}
+
+fn query_ibc_channels(deps: Deps) -> StdResult<IbcChannelsResponse> {
+    let channels = IBC_CHANNELS
+        .keys(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<String>>>()?;
+    Ok(IbcChannelsResponse { channels })
+}
+
+fn query_dexes(deps: Deps) -> StdResult<DexesResponse> {
+    let dexes = DEXES
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            let (name, dex) = item?;
+            Ok(DexResponse {
+                name,
+                router: dex.router.to_string(),
+                flavour: dex.flavour,
+                enabled: dex.enabled,
+            })
+        })
+        .collect::<StdResult<Vec<DexResponse>>>()?;
+    Ok(DexesResponse { dexes })
+}
+
 fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
     let config: Config = read_config(deps.storage)?;
+    let swap_config = SWAP_CONFIG.load(deps.storage)?;
     Ok(ConfigResponse {
+        owner: config.owner.to_string(),
         hub_contract: config.hub_contract.to_string(),
         reward_denom: config.reward_denom,
+        max_spread: swap_config.max_spread,
+        slippage_tolerance: swap_config.slippage_tolerance,
+        split_chunks: swap_config.split_chunks,
+        protocol_fee_bps: config.protocol_fee_bps,
+        fee_collector: config.fee_collector.map(|addr| addr.to_string()),
     })
 }
 
+fn query_pending_owner(deps: Deps) -> StdResult<PendingOwnerResponse> {
+    Ok(PENDING_OWNER.may_load(deps.storage)?.into())
+}
+
 fn query_state(deps: Deps) -> StdResult<StateResponse> {
     let state: State = read_state(deps.storage)?;
     Ok(StateResponse {
         global_index: state.global_index,
         total_balance: state.total_balance,
         prev_reward_balance: state.prev_reward_balance,
+        total_protocol_fee: state.total_protocol_fee,
+        total_compound_protocol_fee: state.total_compound_protocol_fee,
     })
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> StdResult<Response> {
-    // We need to migrate the hub_contract addr from canonical_addr to addr
-    let old_config = OLD_CONFIG.load(deps.storage)?;
+pub fn migrate(deps: DepsMut, env: Env, msg: MigrateMsg) -> StdResult<Response> {
+    // We need to add the owner, and to migrate the hub_contract addr from canonical_addr to addr
+    // if it is still stored canonical. A config that already has an owner is kept as is
+    if CONFIG.load(deps.storage).is_err() {
+        let owner = msg.owner.ok_or_else(|| {
+            StdError::generic_err("An owner is required to migrate a config without one")
+        })?;
 
-    let new_config = Config {
-        hub_contract: deps.api.addr_humanize(&old_config.hub_contract)?,
-        reward_denom: old_config.reward_denom,
-    };
+        // Both layouts deserialize from a string, only a valid hub address tells them apart
+        let (hub_contract, reward_denom) = match UNOWNED_CONFIG.load(deps.storage) {
+            Ok(config) if deps.api.addr_validate(config.hub_contract.as_str()).is_ok() => {
+                (config.hub_contract, config.reward_denom)
+            }
+            _ => match OLD_CONFIG.load(deps.storage) {
+                Ok(config) => match deps.api.addr_humanize(&config.hub_contract) {
+                    Ok(hub_contract) => (hub_contract, config.reward_denom),
+                    Err(_) => {
+                        return Err(StdError::generic_err(
+                            "The stored hub contract is not a valid address",
+                        ))
+                    }
+                },
+                Err(_) => return Err(StdError::generic_err("Unknown stored config layout")),
+            },
+        };
+
+        let new_config = Config {
+            owner: deps.api.addr_validate(&owner)?,
+            hub_contract,
+            reward_denom,
+            protocol_fee_bps: 0,
+            fee_collector: None,
+        };
+
+        CONFIG.save(deps.storage, &new_config)?;
+    }
+
+    // The dexes used to be fixed fields of the swap config
+    if let Ok(old_swap_config) = OLD_SWAP_CONFIG.load(deps.storage) {
+        let dexes = vec![
+            (
+                "astroport",
+                old_swap_config.astroport_addr,
+                OperationFlavour::AstroSwap,
+            ),
+            (
+                "phoenix",
+                old_swap_config.phoenix_addr,
+                OperationFlavour::TokenSwap,
+            ),
+            (
+                "terra_swap",
+                old_swap_config.terraswap_addr,
+                OperationFlavour::TerraSwap,
+            ),
+        ];
+        for (name, router, flavour) in dexes {
+            DEXES.save(
+                deps.storage,
+                name,
+                &Dex {
+                    router,
+                    flavour,
+                    enabled: true,
+                },
+            )?;
+        }
+        SWAP_CONFIG.save(
+            deps.storage,
+            &SwapConfig {
+                max_spread: old_swap_config.max_spread,
+                slippage_tolerance: old_swap_config.slippage_tolerance,
+                split_chunks: old_swap_config.split_chunks,
+            },
+        )?;
+    }
+
+    // The index history starts with the index at the migration
+    if let Ok(state) = read_state(deps.storage) {
+        store_index_update(
+            deps.storage,
+            env.block.time.seconds(),
+            Uint128::zero(),
+            &state,
+        )?;
+    }
 
-    CONFIG.save(deps.storage, &new_config)?;
     Ok(Response::default())
 }
@@ -1,8 +1,15 @@
-use crate::state::{read_config, read_state, store_state, Config, State};
+use crate::state::{
+    read_config, read_index_history, read_state, store_index_update, store_state, Config,
+    FailedSwap, State, SwapDenom, FAILED_SWAPS, INDEX_HISTORY, SWAP_DENOMS, SWAP_REPLIES,
+};
 
+use basset::reward::{DenomListing, IndexHistoryResponse, RewardsForPeriodResponse};
 use cosmwasm_std::{
-    attr, CosmosMsg, Decimal256, DepsMut, Env, MessageInfo, Response, StdError, StdResult,
+    attr, coins, Attribute, BankMsg, CosmosMsg, Decimal256, Deps, DepsMut, Env, MessageInfo, Order,
+    Reply, Response, StdError, StdResult, Storage, SubMsg, SubMsgResult, Uint128, Uint256,
 };
+use cw_storage_plus::Bound;
+use std::convert::TryInto;
 
 use crate::swap::create_swap_msgs;
 
@@ -19,32 +26,117 @@ pub fn execute_swap(deps: DepsMut, env: Env, info: MessageInfo) -> StdResult<Res
 
     let contr_addr = env.contract.address.clone();
     let balance = deps.querier.query_all_balances(contr_addr)?;
-    let mut messages: Vec<CosmosMsg> = Vec::new();
+    let mut messages: Vec<SubMsg> = Vec::new();
 
     let reward_denom = config.reward_denom;
 
     let denoms: Vec<String> = balance.iter().map(|item| item.denom.clone()).collect();
 
+    let swap_denoms = SWAP_DENOMS
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<(String, SwapDenom)>>>()?;
+    let allowlist = swap_denoms
+        .iter()
+        .any(|(_, swap_denom)| swap_denom.listing == Some(DenomListing::Allowed));
+    let mut skipped: Vec<Attribute> = vec![];
+
+    // only the replies of the swaps dispatched below can still come
+    let reply_ids = SWAP_REPLIES
+        .keys(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<u64>>>()?;
+    for id in reply_ids {
+        SWAP_REPLIES.remove(deps.storage, id);
+    }
+
     for coin in balance {
         if coin.denom == reward_denom.clone() || !denoms.contains(&coin.denom) {
             continue;
         }
 
-        messages.append(&mut create_swap_msgs(
+        let swap_denom = swap_denoms
+            .iter()
+            .find(|(denom, _)| *denom == coin.denom)
+            .map(|(_, swap_denom)| swap_denom);
+        if let Some(reason) = skip_reason(swap_denom, allowlist, coin.amount) {
+            skipped.push(attr("skipped", format!("{}:{}", coin, reason)));
+            continue;
+        }
+
+        // a failing swap must not revert the others, its coins are kept for a later swap
+        FAILED_SWAPS.remove(deps.storage, &coin.denom);
+        for (chunk, msg) in create_swap_msgs(
             deps.as_ref(),
             env.clone(),
-            coin,
+            coin.clone(),
             reward_denom.to_string(),
-        )?);
+        )? {
+            let id = messages.len() as u64;
+            SWAP_REPLIES.save(deps.storage, id, &chunk)?;
+            messages.push(SubMsg::reply_on_error(msg, id));
+        }
     }
 
     let res = Response::new()
-        .add_messages(messages)
-        .add_attributes(vec![attr("action", "swap")]);
+        .add_submessages(messages)
+        .add_attributes(vec![attr("action", "swap")])
+        .add_attributes(skipped);
 
     Ok(res)
 }
 
+/// Record the failure of a swap submessage. The failed chunks of a split swap add up
+pub fn reply_failed_swap(deps: DepsMut, env: Env, msg: Reply) -> StdResult<Response> {
+    let coin = SWAP_REPLIES.load(deps.storage, msg.id)?;
+    SWAP_REPLIES.remove(deps.storage, msg.id);
+    let error = match msg.result {
+        SubMsgResult::Err(error) => error,
+        SubMsgResult::Ok(_) => return Err(StdError::generic_err("Unexpected swap reply")),
+    };
+
+    // the failure of an earlier swap of the denom was removed when it was swapped again
+    let failed_amount = FAILED_SWAPS
+        .may_load(deps.storage, &coin.denom)?
+        .map_or(Uint128::zero(), |failed_swap| failed_swap.amount);
+    FAILED_SWAPS.save(
+        deps.storage,
+        &coin.denom,
+        &FailedSwap {
+            amount: failed_amount + coin.amount,
+            error: error.clone(),
+            failed_at: env.block.time.seconds(),
+        },
+    )?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "failed_swap"),
+        attr("denom", coin.denom),
+        attr("error", error),
+    ]))
+}
+
+/// The coins of a denied denom, of a denom missing from the allowlist or below the minimum
+/// swap amount are kept for a later swap
+fn skip_reason(
+    swap_denom: Option<&SwapDenom>,
+    allowlist: bool,
+    amount: Uint128,
+) -> Option<&'static str> {
+    let listing = swap_denom.and_then(|swap_denom| swap_denom.listing);
+    if listing == Some(DenomListing::Denied) {
+        return Some("denied");
+    }
+    if allowlist && listing != Some(DenomListing::Allowed) {
+        return Some("not_allowed");
+    }
+    if swap_denom
+        .and_then(|swap_denom| swap_denom.min_amount)
+        .is_some_and(|min_amount| amount < min_amount)
+    {
+        return Some("dust");
+    }
+    None
+}
+
 /// Increase global_index according to claimed rewards amount
 /// Only hub_contract is allowed to execute
 pub fn execute_update_global_index(
@@ -77,17 +169,134 @@ pub fn execute_update_global_index(
     // claimed_rewards = current_balance - prev_balance;
     let claimed_rewards = balance.amount.checked_sub(previous_balance)?;
 
-    state.prev_reward_balance = balance.amount;
+    // the protocol fee is taken out of the claimed rewards and leaves the contract
+    let protocol_fee = claimed_rewards.multiply_ratio(config.protocol_fee_bps, 10_000u128);
+    let distributed_rewards = claimed_rewards - protocol_fee;
+
+    state.prev_reward_balance = balance.amount - protocol_fee;
+    state.total_protocol_fee += protocol_fee;
 
-    // global_index += claimed_rewards / total_balance;
-    state.global_index += Decimal256::from_ratio(claimed_rewards, state.total_balance);
+    // global_index += distributed_rewards / total_balance;
+    state.global_index += Decimal256::from_ratio(distributed_rewards, state.total_balance);
     store_state(deps.storage, &state)?;
+    store_index_update(
+        deps.storage,
+        env.block.time.seconds(),
+        claimed_rewards,
+        &state,
+    )?;
+
+    let mut messages: Vec<CosmosMsg> = vec![];
+    if !protocol_fee.is_zero() {
+        if let Some(fee_collector) = config.fee_collector {
+            messages.push(
+                BankMsg::Send {
+                    to_address: fee_collector.to_string(),
+                    amount: coins(protocol_fee.u128(), reward_denom),
+                }
+                .into(),
+            );
+        }
+    }
 
     let attributes = vec![
         attr("action", "update_global_index"),
         attr("claimed_rewards", claimed_rewards),
+        attr("protocol_fee", protocol_fee),
     ];
-    let res = Response::new().add_attributes(attributes);
+    let res = Response::new()
+        .add_messages(messages)
+        .add_attributes(attributes);
 
     Ok(res)
 }
+
+/// Send the protocol fee taken by the hub on the compounded rewards to the fee collector, so
+/// that it is accounted for like the fee taken on the rewards
+/// Only hub_contract is allowed to execute
+pub fn execute_collect_compound_protocol_fee(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+) -> StdResult<Response> {
+    let config: Config = read_config(deps.storage)?;
+    if info.sender.as_str() != config.hub_contract {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+
+    let fee_collector = config
+        .fee_collector
+        .ok_or_else(|| StdError::generic_err("No fee collector to send the protocol fee to"))?;
+    let protocol_fee = match info.funds.as_slice() {
+        [protocol_fee] if !protocol_fee.amount.is_zero() => protocol_fee.clone(),
+        _ => {
+            return Err(StdError::generic_err(
+                "The protocol fee must be sent in a single denom",
+            ))
+        }
+    };
+
+    let mut state: State = read_state(deps.storage)?;
+    state.total_compound_protocol_fee += protocol_fee.amount;
+    store_state(deps.storage, &state)?;
+
+    Ok(Response::new()
+        .add_message(BankMsg::Send {
+            to_address: fee_collector.to_string(),
+            amount: vec![protocol_fee.clone()],
+        })
+        .add_attributes(vec![
+            attr("action", "collect_compound_protocol_fee"),
+            attr("protocol_fee", protocol_fee.to_string()),
+        ]))
+}
+
+pub fn query_index_history(
+    deps: Deps,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<IndexHistoryResponse> {
+    let updates = read_index_history(deps.storage, start_after, limit)?;
+    Ok(IndexHistoryResponse { updates })
+}
+
+/// Rewards earned by `balance` between the global indexes at `start` and at `end`
+pub fn query_rewards_for_period(
+    deps: Deps,
+    balance: Uint128,
+    start: u64,
+    end: u64,
+) -> StdResult<RewardsForPeriodResponse> {
+    if start > end {
+        return Err(StdError::generic_err(
+            "The period can not end before it starts",
+        ));
+    }
+
+    let start_index = index_at(deps.storage, start)?;
+    let end_index = index_at(deps.storage, end)?;
+    let rewards = (end_index - start_index)
+        * Decimal256::from_ratio(balance, Uint128::new(1))
+        * Uint256::one();
+
+    Ok(RewardsForPeriodResponse {
+        rewards: rewards.try_into()?,
+        start_index,
+        end_index,
+    })
+}
+
+/// Global index after the last update at or before `time`
+fn index_at(storage: &dyn Storage, time: u64) -> StdResult<Decimal256> {
+    INDEX_HISTORY
+        .range(
+            storage,
+            None,
+            Some(Bound::inclusive(time)),
+            Order::Descending,
+        )
+        .next()
+        .transpose()?
+        .map(|(_, update)| update.global_index)
+        .ok_or_else(|| StdError::generic_err(format!("No global index is recorded at {}", time)))
+}
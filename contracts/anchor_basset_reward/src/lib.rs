@@ -2,6 +2,7 @@ pub mod contract;
 pub mod state;
 
 mod global;
+mod owner;
 mod querier;
 mod swap;
 mod user;
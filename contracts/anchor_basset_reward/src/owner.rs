@@ -0,0 +1,460 @@
+use crate::state::{
+    read_config, store_config, Dex, SwapDenom, SwapRoute, DEXES, IBC_CHANNELS, PENDING_OWNER,
+    SWAP_CONFIG, SWAP_DENOMS, SWAP_ROUTES,
+};
+use basset::dex_router::{AssetInfo, SwapOperation};
+use basset::ownership::OwnershipProposal;
+use basset::reward::{DenomListing, DexInfo};
+use cosmwasm_std::{
+    attr, Addr, Api, Decimal, DepsMut, Empty, Env, MessageInfo, Order, Response, StdError,
+    StdResult, Storage, Uint128,
+};
+
+const MAX_SPLIT_CHUNKS: u32 = 10;
+// 30% of the rewards
+const MAX_PROTOCOL_FEE_BPS: u64 = 3_000;
+// Every dex costs a simulation per swapped coin, or per chunk in split mode
+const MAX_DEXES: usize = 10;
+
+/// Propose a new owner. The ownership transfer only happens once the new owner accepts it.
+/// Only the owner is allowed to execute
+pub fn execute_propose_new_owner(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    new_owner: String,
+    expires_in: Option<u64>,
+) -> StdResult<Response> {
+    let config = read_config(deps.storage)?;
+    if info.sender != config.owner {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+
+    PENDING_OWNER.save(
+        deps.storage,
+        &OwnershipProposal {
+            owner: deps.api.addr_validate(&new_owner)?,
+            expiry: OwnershipProposal::expiry(env.block.time.seconds(), expires_in)?,
+        },
+    )?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "propose_new_owner"),
+        attr("new_owner", new_owner),
+    ]))
+}
+
+/// Cancel the pending ownership transfer.
+/// Only the owner is allowed to execute
+pub fn execute_cancel_ownership_transfer(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+) -> StdResult<Response> {
+    let config = read_config(deps.storage)?;
+    if info.sender != config.owner {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+
+    PENDING_OWNER.remove(deps.storage);
+
+    Ok(Response::new().add_attributes(vec![attr("action", "cancel_ownership_transfer")]))
+}
+
+/// Accept the pending ownership transfer.
+/// Only the proposed owner is allowed to execute
+pub fn execute_accept_ownership(deps: DepsMut, env: Env, info: MessageInfo) -> StdResult<Response> {
+    let proposal = PENDING_OWNER
+        .may_load(deps.storage)?
+        .ok_or_else(|| StdError::generic_err("There is no pending ownership transfer"))?;
+
+    if info.sender != proposal.owner {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+    if proposal.is_expired(env.block.time.seconds()) {
+        return Err(StdError::generic_err(
+            "The ownership transfer proposal has expired",
+        ));
+    }
+
+    let mut config = read_config(deps.storage)?;
+    config.owner = proposal.owner;
+    store_config(deps.storage, &config)?;
+    PENDING_OWNER.remove(deps.storage);
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "accept_ownership"),
+        attr("new_owner", config.owner),
+    ]))
+}
+
+/// Update the slippage protection and the split mode of the reward swaps, keeping the fields
+/// that are not set.
+/// Only the owner is allowed to execute
+pub fn execute_update_swap_config(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    max_spread: Option<Decimal>,
+    slippage_tolerance: Option<Decimal>,
+    split_chunks: Option<u32>,
+) -> StdResult<Response> {
+    let config = read_config(deps.storage)?;
+    if info.sender != config.owner {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+
+    let mut swap_config = SWAP_CONFIG.load(deps.storage)?;
+    if let Some(max_spread) = max_spread {
+        swap_config.max_spread = if max_spread >= Decimal::one() {
+            None
+        } else {
+            Some(max_spread)
+        };
+    }
+    if let Some(slippage_tolerance) = slippage_tolerance {
+        assert_slippage_tolerance(slippage_tolerance)?;
+        swap_config.slippage_tolerance = slippage_tolerance;
+    }
+    if let Some(chunks) = split_chunks {
+        assert_split_chunks(split_chunks)?;
+        swap_config.split_chunks = if chunks == 1 { None } else { Some(chunks) };
+    }
+    SWAP_CONFIG.save(deps.storage, &swap_config)?;
+
+    Ok(Response::new().add_attributes(vec![attr("action", "update_swap_config")]))
+}
+
+/// Set the swap operations used to swap `offer_denom` to the reward denom on `dex`.
+/// Only the owner is allowed to execute
+pub fn execute_set_swap_route(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    offer_denom: String,
+    dex: String,
+    operations: Vec<SwapOperation>,
+) -> StdResult<Response> {
+    let config = read_config(deps.storage)?;
+    if info.sender != config.owner {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+    let flavour = load_dex(deps.storage, &dex)?.flavour;
+    if offer_denom == config.reward_denom {
+        return Err(StdError::generic_err(
+            "The reward denom doesn't need a swap route",
+        ));
+    }
+
+    // the operations must go from offer_denom to the reward denom, hop by hop, on dex
+    let mut asset_info = AssetInfo::NativeToken {
+        denom: offer_denom.clone(),
+    };
+    for operation in operations.iter() {
+        let (offer_asset_info, ask_asset_info) = operation.asset_infos();
+        if offer_asset_info != asset_info
+            || flavour.swap_operation(offer_asset_info, ask_asset_info.clone()) != *operation
+        {
+            return Err(StdError::generic_err("Invalid swap route"));
+        }
+        asset_info = ask_asset_info;
+    }
+    let reward_asset_info = AssetInfo::NativeToken {
+        denom: config.reward_denom,
+    };
+    if operations.is_empty() || asset_info != reward_asset_info {
+        return Err(StdError::generic_err("Invalid swap route"));
+    }
+
+    SWAP_ROUTES.save(
+        deps.storage,
+        (&offer_denom, &dex),
+        &SwapRoute {
+            dex: dex.clone(),
+            operations: operations.clone(),
+        },
+    )?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "set_swap_route"),
+        attr("offer_denom", offer_denom),
+        attr("dex", dex),
+        attr("hops", operations.len().to_string()),
+    ]))
+}
+
+/// Remove the swap route of `offer_denom` on `dex`.
+/// Only the owner is allowed to execute
+pub fn execute_clear_swap_route(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    offer_denom: String,
+    dex: String,
+) -> StdResult<Response> {
+    let config = read_config(deps.storage)?;
+    if info.sender != config.owner {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+
+    SWAP_ROUTES.remove(deps.storage, (&offer_denom, &dex));
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "clear_swap_route"),
+        attr("offer_denom", offer_denom),
+        attr("dex", dex),
+    ]))
+}
+
+/// Register a dex the rewards can be swapped on.
+/// Only the owner is allowed to execute
+pub fn execute_add_dex(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    dex: DexInfo,
+) -> StdResult<Response> {
+    let config = read_config(deps.storage)?;
+    if info.sender != config.owner {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+
+    let attributes = vec![
+        attr("action", "add_dex"),
+        attr("name", dex.name.clone()),
+        attr("router", dex.router.clone()),
+    ];
+    register_dex(deps.storage, deps.api, dex)?;
+
+    Ok(Response::new().add_attributes(attributes))
+}
+
+/// Unregister a dex along with its swap routes.
+/// Only the owner is allowed to execute
+pub fn execute_remove_dex(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    name: String,
+) -> StdResult<Response> {
+    let config = read_config(deps.storage)?;
+    if info.sender != config.owner {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+    load_dex(deps.storage, &name)?;
+
+    DEXES.remove(deps.storage, &name);
+    let routes = SWAP_ROUTES
+        .keys(deps.storage, None, None, Order::Ascending)
+        .filter(|key| key.as_ref().map_or(true, |(_, dex)| *dex == name))
+        .collect::<StdResult<Vec<(String, String)>>>()?;
+    for (offer_denom, dex) in routes {
+        SWAP_ROUTES.remove(deps.storage, (&offer_denom, &dex));
+    }
+
+    Ok(Response::new().add_attributes(vec![attr("action", "remove_dex"), attr("name", name)]))
+}
+
+/// Enable or disable a dex.
+/// Only the owner is allowed to execute
+pub fn execute_update_dex(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    name: String,
+    enabled: bool,
+) -> StdResult<Response> {
+    let config = read_config(deps.storage)?;
+    if info.sender != config.owner {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+
+    let mut dex = load_dex(deps.storage, &name)?;
+    dex.enabled = enabled;
+    DEXES.save(deps.storage, &name, &dex)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "update_dex"),
+        attr("name", name),
+        attr("enabled", enabled.to_string()),
+    ]))
+}
+
+/// Allow or deny the swap of a denom, and set its minimum swap amount.
+/// Only the owner is allowed to execute
+pub fn execute_set_swap_denom(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    denom: String,
+    listing: Option<DenomListing>,
+    min_amount: Option<Uint128>,
+) -> StdResult<Response> {
+    let config = read_config(deps.storage)?;
+    if info.sender != config.owner {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+    if denom == config.reward_denom {
+        return Err(StdError::generic_err("The reward denom is never swapped"));
+    }
+
+    SWAP_DENOMS.save(
+        deps.storage,
+        &denom,
+        &SwapDenom {
+            listing,
+            min_amount,
+        },
+    )?;
+
+    Ok(
+        Response::new()
+            .add_attributes(vec![attr("action", "set_swap_denom"), attr("denom", denom)]),
+    )
+}
+
+/// Remove the swap settings of a denom.
+/// Only the owner is allowed to execute
+pub fn execute_clear_swap_denom(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    denom: String,
+) -> StdResult<Response> {
+    let config = read_config(deps.storage)?;
+    if info.sender != config.owner {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+
+    SWAP_DENOMS.remove(deps.storage, &denom);
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "clear_swap_denom"),
+        attr("denom", denom),
+    ]))
+}
+
+pub fn register_dex(storage: &mut dyn Storage, api: &dyn Api, dex: DexInfo) -> StdResult<()> {
+    if DEXES.has(storage, &dex.name) {
+        return Err(StdError::generic_err(format!(
+            "Dex {} is already registered",
+            dex.name
+        )));
+    }
+    if DEXES.keys(storage, None, None, Order::Ascending).count() >= MAX_DEXES {
+        return Err(StdError::generic_err(format!(
+            "Can't have more than {} dexes",
+            MAX_DEXES
+        )));
+    }
+
+    DEXES.save(
+        storage,
+        &dex.name,
+        &Dex {
+            router: api.addr_validate(&dex.router)?,
+            flavour: dex.flavour,
+            enabled: true,
+        },
+    )
+}
+
+fn load_dex(storage: &dyn Storage, name: &str) -> StdResult<Dex> {
+    DEXES
+        .may_load(storage, name)?
+        .ok_or_else(|| StdError::generic_err(format!("Dex {} is not registered", name)))
+}
+
+/// Update the protocol fee and its collector.
+/// Only the owner is allowed to execute
+pub fn execute_update_fee_config(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    protocol_fee_bps: u64,
+    fee_collector: Option<String>,
+) -> StdResult<Response> {
+    let mut config = read_config(deps.storage)?;
+    if info.sender != config.owner {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+
+    let fee_collector = fee_collector
+        .map(|fee_collector| deps.api.addr_validate(&fee_collector))
+        .transpose()?;
+    assert_protocol_fee(protocol_fee_bps, &fee_collector)?;
+
+    config.protocol_fee_bps = protocol_fee_bps;
+    config.fee_collector = fee_collector;
+    store_config(deps.storage, &config)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "update_fee_config"),
+        attr("protocol_fee_bps", protocol_fee_bps.to_string()),
+    ]))
+}
+
+pub fn assert_protocol_fee(protocol_fee_bps: u64, fee_collector: &Option<Addr>) -> StdResult<()> {
+    if protocol_fee_bps > MAX_PROTOCOL_FEE_BPS {
+        return Err(StdError::generic_err(format!(
+            "protocol_fee_bps must not exceed {}",
+            MAX_PROTOCOL_FEE_BPS
+        )));
+    }
+    if protocol_fee_bps > 0 && fee_collector.is_none() {
+        return Err(StdError::generic_err(
+            "A fee collector is required to take a protocol fee",
+        ));
+    }
+    Ok(())
+}
+
+pub fn assert_slippage_tolerance(slippage_tolerance: Decimal) -> StdResult<()> {
+    if slippage_tolerance >= Decimal::one() {
+        return Err(StdError::generic_err(
+            "slippage_tolerance must be less than 1",
+        ));
+    }
+    Ok(())
+}
+
+/// Every chunk costs a simulation per dex, so their number is capped
+pub fn assert_split_chunks(split_chunks: Option<u32>) -> StdResult<()> {
+    if let Some(chunks) = split_chunks {
+        if chunks == 0 || chunks > MAX_SPLIT_CHUNKS {
+            return Err(StdError::generic_err(format!(
+                "split_chunks must be between 1 and {}",
+                MAX_SPLIT_CHUNKS
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Allow or disallow claims through the transfer channel `channel_id`.
+/// Only the owner is allowed to execute
+pub fn execute_update_ibc_channel(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    channel_id: String,
+    allowed: bool,
+) -> StdResult<Response> {
+    let config = read_config(deps.storage)?;
+    if info.sender != config.owner {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+
+    let action = if allowed {
+        IBC_CHANNELS.save(deps.storage, &channel_id, &Empty {})?;
+        "allow_ibc_channel"
+    } else {
+        IBC_CHANNELS.remove(deps.storage, &channel_id);
+        "disallow_ibc_channel"
+    };
+
+    Ok(
+        Response::new()
+            .add_attributes(vec![attr("action", action), attr("channel_id", channel_id)]),
+    )
+}
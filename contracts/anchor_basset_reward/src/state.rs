@@ -1,27 +1,125 @@
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{Addr, CanonicalAddr, Decimal256, Deps, Order, StdResult, Storage, Uint128};
+use cosmwasm_std::{
+    Addr, CanonicalAddr, Coin, Decimal, Decimal256, Deps, Empty, Order, StdResult, Storage, Uint128,
+};
 
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use basset::reward::HolderResponse;
+use basset::dex_router::{OperationFlavour, SwapOperation};
+use basset::ibc::PendingTransfer;
+use basset::ownership::OwnershipProposal;
+use basset::reward::{DenomListing, HolderResponse, IndexUpdateResponse};
 use cw_storage_plus::{Bound, Item, Map};
 
 pub const STATE: Item<State> = Item::new("\u{0}\u{5}state");
 pub const CONFIG: Item<Config> = Item::new("\u{0}\u{6}config");
 pub const HOLDERS: Map<&Addr, Holder> = Map::new("holders");
+pub const PENDING_OWNER: Item<OwnershipProposal> = Item::new("pending_owner");
 
 // New mecanism specific variables
 pub const SWAP_CONFIG: Item<SwapConfig> = Item::new("swap_config");
 
 #[cw_serde]
 pub struct SwapConfig {
+    /// Max spread passed to the dex router on every swap
+    #[serde(default)]
+    pub max_spread: Option<Decimal>,
+    /// The minimum to receive of a swap is its simulated output minus this tolerance
+    #[serde(default = "default_slippage_tolerance")]
+    pub slippage_tolerance: Decimal,
+    /// The swap of each coin is split in this many chunks, allocated to the dexes by marginal
+    /// output. The whole coin goes to the best dex if not set
+    #[serde(default)]
+    pub split_chunks: Option<u32>,
+}
+
+pub fn default_slippage_tolerance() -> Decimal {
+    Decimal::percent(1)
+}
+
+// Swap config layout before the dexes were stored apart
+pub const OLD_SWAP_CONFIG: Item<OldSwapConfig> = Item::new("swap_config");
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OldSwapConfig {
     pub astroport_addr: Addr,
     pub phoenix_addr: Addr,
     pub terraswap_addr: Addr,
+    #[serde(default)]
+    pub max_spread: Option<Decimal>,
+    #[serde(default = "default_slippage_tolerance")]
+    pub slippage_tolerance: Decimal,
+    #[serde(default)]
+    pub split_chunks: Option<u32>,
+}
+
+/// Dexes the rewards are swapped on, by name
+pub const DEXES: Map<&str, Dex> = Map::new("dexes");
+
+#[cw_serde]
+pub struct Dex {
+    pub router: Addr,
+    pub flavour: OperationFlavour,
+    pub enabled: bool,
+}
+
+/// Swap operations to the reward denom, by (offer denom, dex)
+pub const SWAP_ROUTES: Map<(&str, &str), SwapRoute> = Map::new("swap_routes");
+
+/// Transfer channels rewards can be claimed through
+pub const IBC_CHANNELS: Map<&str, Empty> = Map::new("ibc_channels");
+/// Rewards transferred by the current transaction, until their packet is known
+pub const PENDING_TRANSFER: Item<PendingTransfer> = Item::new("pending_transfer");
+/// Rewards transferred over IBC and not acknowledged yet, by channel and packet sequence
+pub const PENDING_TRANSFERS: Map<(&str, u64), PendingTransfer> = Map::new("pending_transfers");
+
+/// Swap settings of the denoms, by denom
+pub const SWAP_DENOMS: Map<&str, SwapDenom> = Map::new("swap_denoms");
+
+#[cw_serde]
+pub struct SwapDenom {
+    pub listing: Option<DenomListing>,
+    pub min_amount: Option<Uint128>,
+}
+
+/// Swapped coin, or chunk of it in split mode, by id of the swap submessage. The entries are
+/// removed by the failure reply, the others at the next swap
+pub const SWAP_REPLIES: Map<u64, Coin> = Map::new("swap_replies");
+
+/// Last failed swap, by denom
+pub const FAILED_SWAPS: Map<&str, FailedSwap> = Map::new("failed_swaps");
+
+#[cw_serde]
+pub struct FailedSwap {
+    pub amount: Uint128,
+    pub error: String,
+    pub failed_at: u64,
+}
+
+/// Recipient of the rewards claimed by an approved claimer, by (holder, claimer)
+pub const CLAIMERS: Map<(&Addr, &Addr), Addr> = Map::new("claimers");
+
+/// Recipient of the rewards paid out on every balance change, by holder
+pub const AUTO_CLAIMS: Map<&Addr, Addr> = Map::new("auto_claims");
+
+/// Global index updates, by block time. The updates of a block are merged
+pub const INDEX_HISTORY: Map<u64, IndexUpdate> = Map::new("index_history");
+
+#[cw_serde]
+pub struct IndexUpdate {
+    pub claimed_rewards: Uint128,
+    pub total_balance: Uint128,
+    pub global_index: Decimal256,
+}
+
+#[cw_serde]
+pub struct SwapRoute {
+    pub dex: String,
+    pub operations: Vec<SwapOperation>,
 }
 // End
 
+// Config layout before the owner was introduced
 pub const OLD_CONFIG: Item<OldConfig> = Item::new("\u{0}\u{6}config");
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct OldConfig {
@@ -29,10 +127,24 @@ pub struct OldConfig {
     pub reward_denom: String,
 }
 
+/// Config stored before the owner was introduced, with the hub already stored as an `Addr`
+pub const UNOWNED_CONFIG: Item<UnownedConfig> = Item::new("\u{0}\u{6}config");
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct UnownedConfig {
+    pub hub_contract: Addr,
+    pub reward_denom: String,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Config {
+    pub owner: Addr,
     pub hub_contract: Addr,
     pub reward_denom: String,
+    /// Share of the rewards sent to the fee collector, in basis points
+    #[serde(default)]
+    pub protocol_fee_bps: u64,
+    #[serde(default)]
+    pub fee_collector: Option<Addr>,
 }
 
 pub fn store_config(storage: &mut dyn Storage, config: &Config) -> StdResult<()> {
@@ -48,6 +160,12 @@ pub struct State {
     pub global_index: Decimal256,
     pub total_balance: Uint128,
     pub prev_reward_balance: Uint128,
+    /// Protocol fee sent to the fee collector so far
+    #[serde(default)]
+    pub total_protocol_fee: Uint128,
+    /// Protocol fee taken by the hub on the compounded rewards so far, in the underlying denom
+    #[serde(default)]
+    pub total_compound_protocol_fee: Uint128,
 }
 
 pub fn store_state(storage: &mut dyn Storage, state: &State) -> StdResult<()> {
@@ -86,6 +204,24 @@ pub fn read_holder(storage: &dyn Storage, holder_address: &Addr) -> StdResult<Ho
     }
 }
 
+/// Record the global index of `state`, adding `claimed_rewards` to those of the block
+pub fn store_index_update(
+    storage: &mut dyn Storage,
+    time: u64,
+    claimed_rewards: Uint128,
+    state: &State,
+) -> StdResult<()> {
+    INDEX_HISTORY.update(storage, time, |update| -> StdResult<_> {
+        Ok(IndexUpdate {
+            claimed_rewards: update.map(|u| u.claimed_rewards).unwrap_or_default()
+                + claimed_rewards,
+            total_balance: state.total_balance,
+            global_index: state.global_index,
+        })
+    })?;
+    Ok(())
+}
+
 // settings for pagination
 const MAX_LIMIT: u32 = 30;
 const DEFAULT_LIMIT: u32 = 10;
@@ -112,3 +248,30 @@ pub fn read_holders(
         })
         .collect()
 }
+
+pub fn read_index_history(
+    storage: &dyn Storage,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<Vec<IndexUpdateResponse>> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+
+    INDEX_HISTORY
+        .range(
+            storage,
+            start_after.map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .take(limit)
+        .map(|elem| {
+            let (time, update) = elem?;
+            Ok(IndexUpdateResponse {
+                time,
+                claimed_rewards: update.claimed_rewards,
+                total_balance: update.total_balance,
+                global_index: update.global_index,
+            })
+        })
+        .collect()
+}
@@ -1,13 +1,12 @@
-use crate::state::SWAP_CONFIG;
+use crate::state::{DEXES, SWAP_CONFIG, SWAP_ROUTES};
 use basset::dex_router::{
-    AssetInfo, ExecuteMsg as AstroportExecuteMsg, QueryMsg as AstroportQueryMsg,
-    SimulateSwapOperationsResponse, SwapMessageType, SwapOperation,
+    find_best_route, split_across_routes, AssetInfo, ExecuteMsg as AstroportExecuteMsg,
+    SwapOperation,
 };
 use cosmwasm_std::{
-    to_binary, Coin, CosmosMsg, Deps, Env, QueryRequest, StdResult, Uint128, WasmMsg, WasmQuery,
+    to_binary, Coin, CosmosMsg, Decimal, Deps, Env, Order, StdResult, Uint128, WasmMsg,
 };
 use serde::Serialize;
-use strum::IntoEnumIterator;
 
 pub fn into_cosmos_msg<M: Serialize, T: Into<String>>(
     message: M,
@@ -23,39 +22,66 @@ pub fn into_cosmos_msg<M: Serialize, T: Into<String>>(
     Ok(execute.into())
 }
 
+/// The swap messages of `c`, each with the chunk of `c` it offers
 pub fn create_swap_msgs(
     deps: Deps,
     env: Env,
     c: Coin,
     stable_denom: String,
-) -> StdResult<Vec<CosmosMsg>> {
-    let best_price_result: Option<(usize, Uint128)> = SwapMessageType::iter()
-        .map(|message_type| {
-            get_swap_result_for(deps, c.clone(), stable_denom.clone(), message_type)
+) -> StdResult<Vec<(Coin, CosmosMsg)>> {
+    // every enabled dex is tried with its stored route, or with the direct swap if it has none
+    let routes = DEXES
+        .range(deps.storage, None, None, Order::Ascending)
+        .filter(|item| item.as_ref().map_or(true, |(_, dex)| dex.enabled))
+        .map(|item| {
+            let (name, dex) = item?;
+            let operations = match SWAP_ROUTES.may_load(deps.storage, (&c.denom, &name))? {
+                Some(route) => route.operations,
+                None => vec![dex.flavour.swap_operation(
+                    AssetInfo::NativeToken {
+                        denom: c.denom.clone(),
+                    },
+                    AssetInfo::NativeToken {
+                        denom: stable_denom.clone(),
+                    },
+                )],
+            };
+            Ok((dex.router.to_string(), operations))
         })
-        .enumerate()
-        .filter(|(_, best_price)| best_price.is_ok())
-        .map(|(best_price_index, best_price)| (best_price_index, best_price.unwrap()))
-        .max_by_key(|&(_, item)| item);
+        .collect::<StdResult<Vec<(String, Vec<SwapOperation>)>>>()?;
+
+    let swap_config = SWAP_CONFIG.load(deps.storage)?;
+    let allocations = match swap_config.split_chunks {
+        Some(chunks) if chunks > 1 => split_across_routes(&deps.querier, &routes, c.amount, chunks),
+        _ => find_best_route(&deps.querier, &routes, c.amount)
+            .map(|(index, best_price)| vec![(index, c.amount, best_price)])
+            .unwrap_or_default(),
+    };
 
-    if let Some((best_price_index, best_price)) = best_price_result {
+    let mut messages = vec![];
+    for (index, amount, best_price) in allocations {
         if best_price == Uint128::zero() {
-            return Ok(vec![]);
+            continue;
         }
 
-        let best_price_marketplace: SwapMessageType =
-            SwapMessageType::iter().nth(best_price_index).unwrap();
+        let (router, operations) = routes[index].clone();
+
+        // the swap must not give less than the simulated price minus the tolerance
+        let minimum_receive = best_price * (Decimal::one() - swap_config.slippage_tolerance);
 
-        Ok(vec![create_swap_message_for(
+        let chunk = Coin::new(amount.u128(), c.denom.clone());
+        let msg = create_swap_message_for(
             deps,
-            env,
-            c,
-            stable_denom,
-            best_price_marketplace,
-        )?])
-    } else {
-        Ok(vec![])
+            env.clone(),
+            chunk.clone(),
+            router,
+            operations,
+            Some(minimum_receive),
+        )?;
+        messages.push((chunk, msg));
     }
+
+    Ok(messages)
 }
 
 // Astroport router smart-contract
@@ -73,81 +99,24 @@ pub fn create_swap_msgs(
 // Testnet : terra1xp6xe6uwqrspumrkazdg90876ns4h78yw03vfxghhcy03yexcrcsdaqvc8
 // Mainnet : terra13ehuhysn5mqjeaheeuew2gjs785f6k7jm8vfsqg3jhtpkwppcmzqcu7chk
 
-fn get_astroport_swap_operation(
-    offer_denom: String,
-    ask_denom: String,
-    message_type: SwapMessageType,
-) -> SwapOperation {
-    match message_type {
-        SwapMessageType::Astroport => SwapOperation::AstroSwap {
-            offer_asset_info: AssetInfo::NativeToken { denom: offer_denom },
-            ask_asset_info: AssetInfo::NativeToken { denom: ask_denom },
-        },
-        SwapMessageType::Phoenix => SwapOperation::TokenSwap {
-            offer_asset_info: AssetInfo::NativeToken { denom: offer_denom },
-            ask_asset_info: AssetInfo::NativeToken { denom: ask_denom },
-        },
-        SwapMessageType::TerraSwap => SwapOperation::TerraSwap {
-            offer_asset_info: AssetInfo::NativeToken { denom: offer_denom },
-            ask_asset_info: AssetInfo::NativeToken { denom: ask_denom },
-        },
-    }
-}
-
-fn get_contract_address(deps: Deps, message_type: SwapMessageType) -> StdResult<String> {
-    let swap_config = SWAP_CONFIG.load(deps.storage)?;
-    Ok(match message_type {
-        SwapMessageType::Astroport => swap_config.astroport_addr,
-        SwapMessageType::Phoenix => swap_config.phoenix_addr,
-        SwapMessageType::TerraSwap => swap_config.terraswap_addr,
-    }
-    .to_string())
-}
-
 pub fn create_swap_message_for(
     deps: Deps,
     _env: Env,
     c: Coin,
-    stable_denom: String,
-    message_type: SwapMessageType,
+    router: String,
+    operations: Vec<SwapOperation>,
+    minimum_receive: Option<Uint128>,
 ) -> StdResult<CosmosMsg> {
-    let contract_address = get_contract_address(deps, message_type)?;
+    let max_spread = SWAP_CONFIG.load(deps.storage)?.max_spread;
 
     into_cosmos_msg(
         AstroportExecuteMsg::ExecuteSwapOperations {
-            operations: vec![get_astroport_swap_operation(
-                c.denom.clone(),
-                stable_denom,
-                message_type,
-            )],
+            operations,
             to: None,
-            max_spread: None,
-            minimum_receive: None,
+            max_spread,
+            minimum_receive,
         },
-        contract_address,
+        router,
         vec![c],
     )
 }
-
-pub fn get_swap_result_for(
-    deps: Deps,
-    c: Coin,
-    stable_denom: String,
-    message_type: SwapMessageType,
-) -> StdResult<Uint128> {
-    let contract_address = get_contract_address(deps, message_type)?;
-
-    let swap_operation_response: SimulateSwapOperationsResponse =
-        deps.querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
-            contract_addr: contract_address,
-            msg: to_binary(&AstroportQueryMsg::SimulateSwapOperations {
-                offer_amount: c.amount,
-                operations: vec![get_astroport_swap_operation(
-                    c.denom,
-                    stable_denom,
-                    message_type,
-                )],
-            })?,
-        }))?;
-    Ok(swap_operation_response.amount)
-}
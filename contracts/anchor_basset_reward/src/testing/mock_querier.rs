@@ -96,19 +96,11 @@ impl WasmMockQuerier {
                             offer_amount,
                             operations,
                         } => {
-                            #[allow(clippy::collapsible_match)]
-                            if let SwapOperation::AstroSwap {
-                                offer_asset_info, ..
-                            } = operations[0].clone()
-                            {
-                                if let AssetInfo::NativeToken { denom: x } = offer_asset_info {
-                                    if x == *"mnt" {
-                                        return SystemResult::Err(SystemError::InvalidRequest {
-                                            error: "not covered".to_string(),
-                                            request: msg.clone(),
-                                        });
-                                    }
-                                }
+                            if missing_pool(&operations) {
+                                return SystemResult::Err(SystemError::InvalidRequest {
+                                    error: "not covered".to_string(),
+                                    request: msg.clone(),
+                                });
                             }
                             SystemResult::Ok(ContractResult::from(to_binary(
                                 &SimulateSwapOperationsResponse {
@@ -122,30 +114,21 @@ impl WasmMockQuerier {
                             request: msg.clone(),
                         }),
                     }
-                } else if *contract_addr == "phoenix_addr" {
+                } else if *contract_addr == "phoenix_addr" || *contract_addr == "phoenix_v2_addr" {
                     match from_binary(msg).unwrap() {
                         SwapQueryMsg::SimulateSwapOperations {
                             offer_amount,
                             operations,
                         } => {
-                            #[allow(clippy::collapsible_match)]
-                            if let SwapOperation::TokenSwap {
-                                offer_asset_info, ..
-                            } = operations[0].clone()
-                            {
-                                if let AssetInfo::NativeToken { denom: x } = offer_asset_info {
-                                    if x == *"mnt" {
-                                        return SystemResult::Err(SystemError::InvalidRequest {
-                                            error: "not covered".to_string(),
-                                            request: msg.clone(),
-                                        });
-                                    }
-                                }
+                            if missing_pool(&operations) {
+                                return SystemResult::Err(SystemError::InvalidRequest {
+                                    error: "not covered".to_string(),
+                                    request: msg.clone(),
+                                });
                             }
                             SystemResult::Ok(ContractResult::from(to_binary(
                                 &SimulateSwapOperationsResponse {
-                                    amount: offer_amount * Uint128::from(11u128)
-                                        / Uint128::from(10u128),
+                                    amount: phoenix_output(offer_amount),
                                 },
                             )))
                         }
@@ -160,19 +143,11 @@ impl WasmMockQuerier {
                             offer_amount,
                             operations,
                         } => {
-                            #[allow(clippy::collapsible_match)]
-                            if let SwapOperation::TerraSwap {
-                                offer_asset_info, ..
-                            } = operations[0].clone()
-                            {
-                                if let AssetInfo::NativeToken { denom: x } = offer_asset_info {
-                                    if x == *"mnt" {
-                                        return SystemResult::Err(SystemError::InvalidRequest {
-                                            error: "not covered".to_string(),
-                                            request: msg.clone(),
-                                        });
-                                    }
-                                }
+                            if missing_pool(&operations) {
+                                return SystemResult::Err(SystemError::InvalidRequest {
+                                    error: "not covered".to_string(),
+                                    request: msg.clone(),
+                                });
                             }
                             SystemResult::Ok(ContractResult::from(to_binary(
                                 &SimulateSwapOperationsResponse {
@@ -194,6 +169,26 @@ impl WasmMockQuerier {
     }
 }
 
+/// Phoenix gives the best price on the first 1000 coins only, and half of it above
+fn phoenix_output(offer_amount: Uint128) -> Uint128 {
+    let depth = Uint128::new(1000);
+    let output = offer_amount.min(depth) * Uint128::from(11u128) / Uint128::from(10u128);
+    output + offer_amount.saturating_sub(depth) / Uint128::from(2u128)
+}
+
+/// There is no mnt/uusd pool on any of the routers
+fn missing_pool(operations: &[SwapOperation]) -> bool {
+    let mnt = AssetInfo::NativeToken {
+        denom: "mnt".to_string(),
+    };
+    let uusd = AssetInfo::NativeToken {
+        denom: "uusd".to_string(),
+    };
+    operations
+        .iter()
+        .any(|operation| operation.asset_infos() == (mnt.clone(), uusd.clone()))
+}
+
 impl WasmMockQuerier {
     pub fn new(base: MockQuerier<Empty>) -> Self {
         WasmMockQuerier { base }
@@ -5,44 +5,82 @@
 //! You can easily convert unit tests to integration tests as follows:
 //! 1. Copy them over verbatim
 //! 2. Then change
-//!      let mut deps = mock_dependencies(&[]);
+//!    ```text
+//!    let mut deps = mock_dependencies(&[]);
+//!    ```
 //!    to
-//!      let mut deps = mock_instance(WASM, &[]);
+//!    ```text
+//!    let mut deps = mock_instance(WASM, &[]);
+//!    ```
 //! 3. If you access raw storage, where ever you see something like:
-//!      deps.storage.get(CONFIG_KEY).expect("no data stored");
+//!    ```text
+//!    deps.storage.get(CONFIG_KEY).expect("no data stored");
+//!    ```
 //!    replace it with:
-//!      deps.with_storage(|store| {
-//!          let data = store.get(CONFIG_KEY).expect("no data stored");
-//!          //...
-//!      });
+//!    ```text
+//!    deps.with_storage(|store| {
+//!        let data = store.get(CONFIG_KEY).expect("no data stored");
+//!        //...
+//!    });
+//!    ```
 //! 4. Anywhere you see query(deps.as_ref(), mock_env(),...) you must replace it with query(&mut deps, ...)
 
 use cosmwasm_std::testing::{mock_env, mock_info};
 use cosmwasm_std::{
-    from_binary, Api, BankMsg, Coin, CosmosMsg, Decimal256, StdError, SubMsg, Uint128,
+    attr, from_binary, to_binary, Addr, Api, BankMsg, Coin, CosmosMsg, Decimal, Decimal256, Event,
+    Order, OwnedDeps, Reply, ReplyOn, StdError, StdResult, Storage, SubMsg, SubMsgResponse,
+    SubMsgResult, Uint128, WasmMsg,
 };
 
-use crate::contract::{execute, instantiate, migrate, query};
-use crate::state::{store_holder, store_state, Holder, OldConfig, State, CONFIG, OLD_CONFIG};
+use crate::contract::{execute, instantiate, migrate, query, reply, sudo, TRANSFER_REPLY_ID};
+use crate::state::{
+    read_state, store_holder, store_state, Holder, OldConfig, OldSwapConfig, State, SwapConfig,
+    CONFIG, OLD_CONFIG, OLD_SWAP_CONFIG, SWAP_CONFIG, SWAP_REPLIES,
+};
 use crate::swap::create_swap_msgs;
 use crate::testing::mock_querier::{
     mock_dependencies, MOCK_HUB_CONTRACT_ADDR, MOCK_TOKEN_CONTRACT_ADDR,
 };
+use basset::dex_router::{
+    AssetInfo, ExecuteMsg as RouterExecuteMsg, OperationFlavour, SwapOperation,
+};
+use basset::ibc::{
+    transfer_msg, IbcChannelsResponse, IbcLifecycleComplete, IbcTransfer, MIN_TIMEOUT,
+};
+use basset::ownership::PendingOwnerResponse;
 use basset::reward::{
-    ConfigResponse, ExecuteMsg, HolderResponse, HoldersResponse, InstantiateMsg, MigrateMsg,
-    QueryMsg, StateResponse,
+    AutoClaimResponse, ClaimerResponse, ClaimersResponse, ConfigResponse, DenomListing, DexInfo,
+    DexResponse, DexesResponse, ExecuteMsg, FailedSwapResponse, FailedSwapsResponse,
+    HolderResponse, HoldersResponse, IndexHistoryResponse, IndexUpdateResponse, InstantiateMsg,
+    MigrateMsg, QueryMsg, RewardsForPeriodResponse, StateResponse, SudoMsg, SwapDenomResponse,
+    SwapDenomsResponse, SwapRouteResponse, SwapRoutesResponse,
 };
 use std::str::FromStr;
 
 const DEFAULT_REWARD_DENOM: &str = "uusd";
 
+fn dex_info(name: &str, flavour: OperationFlavour) -> DexInfo {
+    DexInfo {
+        name: name.to_string(),
+        router: format!("{}_addr", name),
+        flavour,
+    }
+}
+
 fn default_init() -> InstantiateMsg {
     InstantiateMsg {
         hub_contract: String::from(MOCK_HUB_CONTRACT_ADDR),
         reward_denom: DEFAULT_REWARD_DENOM.to_string(),
-        astroport_addr: "astroport_addr".to_string(),
-        phoenix_addr: "phoenix_addr".to_string(),
-        terraswap_addr: "terraswap_addr".to_string(),
+        dexes: vec![
+            dex_info("astroport", OperationFlavour::AstroSwap),
+            dex_info("phoenix", OperationFlavour::TokenSwap),
+            dex_info("terraswap", OperationFlavour::TerraSwap),
+        ],
+        max_spread: None,
+        slippage_tolerance: None,
+        split_chunks: None,
+        protocol_fee_bps: None,
+        fee_collector: None,
     }
 }
 
@@ -61,8 +99,14 @@ fn proper_init() {
     assert_eq!(
         config_response,
         ConfigResponse {
+            owner: String::from("addr0000"),
             hub_contract: String::from(MOCK_HUB_CONTRACT_ADDR),
             reward_denom: DEFAULT_REWARD_DENOM.to_string(),
+            max_spread: None,
+            slippage_tolerance: Decimal::percent(1),
+            split_chunks: None,
+            protocol_fee_bps: 0,
+            fee_collector: None,
         }
     );
 
@@ -73,7 +117,9 @@ fn proper_init() {
         StateResponse {
             global_index: Decimal256::zero(),
             total_balance: Uint128::new(0u128),
-            prev_reward_balance: Uint128::zero()
+            prev_reward_balance: Uint128::zero(),
+            total_protocol_fee: Uint128::zero(),
+            total_compound_protocol_fee: Uint128::zero(),
         }
     );
 }
@@ -115,7 +161,7 @@ pub fn swap_to_reward_denom() {
     assert_eq!(
         res.messages,
         vec![
-            SubMsg::new(
+            SubMsg::reply_on_error(
                 create_swap_msgs(
                     deps.as_ref(),
                     mock_env(),
@@ -126,9 +172,11 @@ pub fn swap_to_reward_denom() {
                     DEFAULT_REWARD_DENOM.to_string()
                 )
                 .unwrap()[0]
-                    .clone()
+                    .1
+                    .clone(),
+                0
             ),
-            SubMsg::new(
+            SubMsg::reply_on_error(
                 create_swap_msgs(
                     deps.as_ref(),
                     mock_env(),
@@ -139,9 +187,11 @@ pub fn swap_to_reward_denom() {
                     DEFAULT_REWARD_DENOM.to_string()
                 )
                 .unwrap()[0]
-                    .clone()
+                    .1
+                    .clone(),
+                1
             ),
-            SubMsg::new(
+            SubMsg::reply_on_error(
                 create_swap_msgs(
                     deps.as_ref(),
                     mock_env(),
@@ -152,12 +202,871 @@ pub fn swap_to_reward_denom() {
                     DEFAULT_REWARD_DENOM.to_string()
                 )
                 .unwrap()[0]
-                    .clone()
+                    .1
+                    .clone(),
+                2
             ),
         ]
     );
 }
 
+#[test]
+fn swap_slippage_protection() {
+    let mut deps = mock_dependencies(&[Coin {
+        denom: "ukrw".to_string(),
+        amount: Uint128::new(1000u128),
+    }]);
+
+    let init_msg = default_init();
+    let info = mock_info("addr0000", &[]);
+    instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+    let expected_swap = |minimum_receive: u128, max_spread: Option<Decimal>| {
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "phoenix_addr".to_string(),
+            msg: to_binary(&RouterExecuteMsg::ExecuteSwapOperations {
+                operations: vec![SwapOperation::TokenSwap {
+                    offer_asset_info: AssetInfo::NativeToken {
+                        denom: "ukrw".to_string(),
+                    },
+                    ask_asset_info: AssetInfo::NativeToken {
+                        denom: DEFAULT_REWARD_DENOM.to_string(),
+                    },
+                }],
+                minimum_receive: Some(Uint128::new(minimum_receive)),
+                to: None,
+                max_spread,
+            })
+            .unwrap(),
+            funds: vec![Coin::new(1000, "ukrw")],
+        })
+    };
+
+    // Phoenix simulates 1100, minus the default tolerance of 1%
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(MOCK_HUB_CONTRACT_ADDR, &[]),
+        ExecuteMsg::SwapToRewardDenom {},
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::reply_on_error(expected_swap(1089, None), 0)]
+    );
+
+    // only the owner can update the swap config
+    let msg = ExecuteMsg::UpdateSwapConfig {
+        max_spread: Some(Decimal::percent(2)),
+        slippage_tolerance: Some(Decimal::percent(5)),
+        split_chunks: None,
+    };
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("villain", &[]),
+        msg.clone(),
+    );
+    match res {
+        Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, "unauthorized"),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        ExecuteMsg::UpdateSwapConfig {
+            max_spread: None,
+            slippage_tolerance: Some(Decimal::one()),
+            split_chunks: None,
+        },
+    );
+    match res {
+        Err(StdError::GenericErr { msg, .. }) => {
+            assert_eq!(msg, "slippage_tolerance must be less than 1")
+        }
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    let config: ConfigResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+    assert_eq!(config.max_spread, Some(Decimal::percent(2)));
+    assert_eq!(config.slippage_tolerance, Decimal::percent(5));
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(MOCK_HUB_CONTRACT_ADDR, &[]),
+        ExecuteMsg::SwapToRewardDenom {},
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::reply_on_error(
+            expected_swap(1045, Some(Decimal::percent(2))),
+            0
+        )]
+    );
+}
+
+#[test]
+fn split_swaps() {
+    let mut deps = mock_dependencies(&[Coin {
+        denom: "ukrw".to_string(),
+        amount: Uint128::new(4000u128),
+    }]);
+
+    let init_msg = default_init();
+    let info = mock_info("addr0000", &[]);
+    instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+    let expected_swap =
+        |router: &str, operation: SwapOperation, amount: u128, minimum: u128, id: u64| {
+            SubMsg::reply_on_error(
+                CosmosMsg::Wasm(WasmMsg::Execute {
+                    contract_addr: router.to_string(),
+                    msg: to_binary(&RouterExecuteMsg::ExecuteSwapOperations {
+                        operations: vec![operation],
+                        minimum_receive: Some(Uint128::new(minimum)),
+                        to: None,
+                        max_spread: None,
+                    })
+                    .unwrap(),
+                    funds: vec![Coin::new(amount, "ukrw")],
+                }),
+                id,
+            )
+        };
+    let offer_asset_info = AssetInfo::NativeToken {
+        denom: "ukrw".to_string(),
+    };
+    let ask_asset_info = AssetInfo::NativeToken {
+        denom: DEFAULT_REWARD_DENOM.to_string(),
+    };
+
+    // without split, terraswap gives the best price for the whole balance: 4000 against
+    // 1100 + 3000 * 0.5 on phoenix
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(MOCK_HUB_CONTRACT_ADDR, &[]),
+        ExecuteMsg::SwapToRewardDenom {},
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![expected_swap(
+            "terraswap_addr",
+            SwapOperation::TerraSwap {
+                offer_asset_info: offer_asset_info.clone(),
+                ask_asset_info: ask_asset_info.clone(),
+            },
+            4000,
+            3960,
+            0
+        )]
+    );
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        ExecuteMsg::UpdateSwapConfig {
+            max_spread: None,
+            slippage_tolerance: None,
+            split_chunks: Some(11),
+        },
+    );
+    match res {
+        Err(StdError::GenericErr { msg, .. }) => {
+            assert_eq!(msg, "split_chunks must be between 1 and 10")
+        }
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::UpdateSwapConfig {
+            max_spread: None,
+            slippage_tolerance: None,
+            split_chunks: Some(4),
+        },
+    )
+    .unwrap();
+
+    // the first chunk goes to phoenix, the others to terraswap once phoenix runs dry,
+    // for 1100 + 3000 in total
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(MOCK_HUB_CONTRACT_ADDR, &[]),
+        ExecuteMsg::SwapToRewardDenom {},
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            expected_swap(
+                "phoenix_addr",
+                SwapOperation::TokenSwap {
+                    offer_asset_info: offer_asset_info.clone(),
+                    ask_asset_info: ask_asset_info.clone(),
+                },
+                1000,
+                1089,
+                0
+            ),
+            expected_swap(
+                "terraswap_addr",
+                SwapOperation::TerraSwap {
+                    offer_asset_info,
+                    ask_asset_info,
+                },
+                3000,
+                2970,
+                1
+            ),
+        ]
+    );
+
+    // only the failed chunk is recorded
+    reply(
+        deps.as_mut(),
+        mock_env(),
+        Reply {
+            id: 1,
+            result: SubMsgResult::Err("Operation exceeds max spread limit".to_string()),
+        },
+    )
+    .unwrap();
+    let failed_swaps: FailedSwapsResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::FailedSwaps {}).unwrap()).unwrap();
+    assert_eq!(
+        failed_swaps.failed_swaps,
+        vec![FailedSwapResponse {
+            denom: "ukrw".to_string(),
+            amount: Uint128::new(3000),
+            error: "Operation exceeds max spread limit".to_string(),
+            failed_at: mock_env().block.time.seconds(),
+        }]
+    );
+
+    // the failed chunks of a denom add up
+    reply(
+        deps.as_mut(),
+        mock_env(),
+        Reply {
+            id: 0,
+            result: SubMsgResult::Err("Operation exceeds max spread limit".to_string()),
+        },
+    )
+    .unwrap();
+    let failed_swaps: FailedSwapsResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::FailedSwaps {}).unwrap()).unwrap();
+    assert_eq!(failed_swaps.failed_swaps[0].amount, Uint128::new(4000));
+}
+
+#[test]
+fn partial_swap_config_update() {
+    let mut deps = mock_dependencies(&[]);
+
+    let init_msg = default_init();
+    let info = mock_info("addr0000", &[]);
+    instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        ExecuteMsg::UpdateSwapConfig {
+            max_spread: Some(Decimal::percent(2)),
+            slippage_tolerance: Some(Decimal::percent(5)),
+            split_chunks: Some(4),
+        },
+    )
+    .unwrap();
+
+    // only the split mode is updated
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        ExecuteMsg::UpdateSwapConfig {
+            max_spread: None,
+            slippage_tolerance: None,
+            split_chunks: Some(6),
+        },
+    )
+    .unwrap();
+    let config: ConfigResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+    assert_eq!(config.max_spread, Some(Decimal::percent(2)));
+    assert_eq!(config.slippage_tolerance, Decimal::percent(5));
+    assert_eq!(config.split_chunks, Some(6));
+
+    // the max spread limit and the split mode can be removed
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::UpdateSwapConfig {
+            max_spread: Some(Decimal::one()),
+            slippage_tolerance: None,
+            split_chunks: Some(1),
+        },
+    )
+    .unwrap();
+    let config: ConfigResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+    assert_eq!(config.max_spread, None);
+    assert_eq!(config.slippage_tolerance, Decimal::percent(5));
+    assert_eq!(config.split_chunks, None);
+}
+
+#[test]
+fn swap_routes() {
+    let mut deps = mock_dependencies(&[Coin {
+        denom: "mnt".to_string(),
+        amount: Uint128::new(1000u128),
+    }]);
+
+    let init_msg = default_init();
+    let info = mock_info("addr0000", &[]);
+    instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+    // there is no direct pool for mnt
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(MOCK_HUB_CONTRACT_ADDR, &[]),
+        ExecuteMsg::SwapToRewardDenom {},
+    )
+    .unwrap();
+    assert_eq!(res.messages.len(), 0);
+
+    let hop = |offer: &str, ask: &str| SwapOperation::AstroSwap {
+        offer_asset_info: AssetInfo::NativeToken {
+            denom: offer.to_string(),
+        },
+        ask_asset_info: AssetInfo::NativeToken {
+            denom: ask.to_string(),
+        },
+    };
+    let operations = vec![hop("mnt", "uluna"), hop("uluna", DEFAULT_REWARD_DENOM)];
+
+    // only the owner can set a route
+    let msg = ExecuteMsg::SetSwapRoute {
+        offer_denom: "mnt".to_string(),
+        dex: "astroport".to_string(),
+        operations: operations.clone(),
+    };
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("villain", &[]),
+        msg.clone(),
+    );
+    match res {
+        Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, "unauthorized"),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    // the route must be made of the dex operations, from the offer denom to the reward denom
+    let invalid_routes = vec![
+        ("phoenix", operations.clone()),
+        ("astroport", vec![hop("mnt", "uluna")]),
+        (
+            "astroport",
+            vec![hop("mnt", "uluna"), hop("ukrw", DEFAULT_REWARD_DENOM)],
+        ),
+        ("astroport", vec![]),
+    ];
+    for (dex, operations) in invalid_routes {
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            ExecuteMsg::SetSwapRoute {
+                offer_denom: "mnt".to_string(),
+                dex: dex.to_string(),
+                operations,
+            },
+        );
+        match res {
+            Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, "Invalid swap route"),
+            _ => panic!("DO NOT ENTER HERE"),
+        }
+    }
+
+    execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+    let routes: SwapRoutesResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::SwapRoutes {}).unwrap()).unwrap();
+    assert_eq!(
+        routes.routes,
+        vec![SwapRouteResponse {
+            offer_denom: "mnt".to_string(),
+            dex: "astroport".to_string(),
+            operations: operations.clone(),
+        }]
+    );
+
+    // astroport simulates 900 through uluna, minus the default tolerance of 1%
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(MOCK_HUB_CONTRACT_ADDR, &[]),
+        ExecuteMsg::SwapToRewardDenom {},
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::reply_on_error(
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "astroport_addr".to_string(),
+                msg: to_binary(&RouterExecuteMsg::ExecuteSwapOperations {
+                    operations,
+                    minimum_receive: Some(Uint128::new(891)),
+                    to: None,
+                    max_spread: None,
+                })
+                .unwrap(),
+                funds: vec![Coin::new(1000, "mnt")],
+            }),
+            0
+        )]
+    );
+
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::ClearSwapRoute {
+            offer_denom: "mnt".to_string(),
+            dex: "astroport".to_string(),
+        },
+    )
+    .unwrap();
+
+    let routes: SwapRoutesResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::SwapRoutes {}).unwrap()).unwrap();
+    assert_eq!(routes.routes, vec![]);
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(MOCK_HUB_CONTRACT_ADDR, &[]),
+        ExecuteMsg::SwapToRewardDenom {},
+    )
+    .unwrap();
+    assert_eq!(res.messages.len(), 0);
+}
+
+#[test]
+fn dexes() {
+    let mut deps = mock_dependencies(&[Coin {
+        denom: "ukrw".to_string(),
+        amount: Uint128::new(1000u128),
+    }]);
+
+    let init_msg = default_init();
+    let info = mock_info("addr0000", &[]);
+    instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+    let swap_router = |deps: &mut OwnedDeps<_, _, _>| -> Vec<String> {
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(MOCK_HUB_CONTRACT_ADDR, &[]),
+            ExecuteMsg::SwapToRewardDenom {},
+        )
+        .unwrap();
+        res.messages
+            .into_iter()
+            .map(|msg| match msg.msg {
+                CosmosMsg::Wasm(WasmMsg::Execute { contract_addr, .. }) => contract_addr,
+                _ => panic!("DO NOT ENTER HERE"),
+            })
+            .collect()
+    };
+    assert_eq!(swap_router(&mut deps), vec!["phoenix_addr".to_string()]);
+
+    // only the owner can manage the dexes
+    let msgs = vec![
+        ExecuteMsg::AddDex {
+            dex: dex_info("other", OperationFlavour::AstroSwap),
+        },
+        ExecuteMsg::RemoveDex {
+            name: "phoenix".to_string(),
+        },
+        ExecuteMsg::UpdateDex {
+            name: "phoenix".to_string(),
+            enabled: false,
+        },
+    ];
+    for msg in msgs {
+        let res = execute(deps.as_mut(), mock_env(), mock_info("villain", &[]), msg);
+        match res {
+            Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, "unauthorized"),
+            _ => panic!("DO NOT ENTER HERE"),
+        }
+    }
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        ExecuteMsg::AddDex {
+            dex: dex_info("phoenix", OperationFlavour::TokenSwap),
+        },
+    );
+    match res {
+        Err(StdError::GenericErr { msg, .. }) => {
+            assert_eq!(msg, "Dex phoenix is already registered")
+        }
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    // a disabled dex is not used
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        ExecuteMsg::UpdateDex {
+            name: "phoenix".to_string(),
+            enabled: false,
+        },
+    )
+    .unwrap();
+    assert_eq!(swap_router(&mut deps), vec!["terraswap_addr".to_string()]);
+
+    // nor is a removed one, along with its routes
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        ExecuteMsg::SetSwapRoute {
+            offer_denom: "mnt".to_string(),
+            dex: "terraswap".to_string(),
+            operations: vec![SwapOperation::TerraSwap {
+                offer_asset_info: AssetInfo::NativeToken {
+                    denom: "mnt".to_string(),
+                },
+                ask_asset_info: AssetInfo::NativeToken {
+                    denom: DEFAULT_REWARD_DENOM.to_string(),
+                },
+            }],
+        },
+    )
+    .unwrap();
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        ExecuteMsg::RemoveDex {
+            name: "terraswap".to_string(),
+        },
+    )
+    .unwrap();
+    assert_eq!(swap_router(&mut deps), vec!["astroport_addr".to_string()]);
+    let routes: SwapRoutesResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::SwapRoutes {}).unwrap()).unwrap();
+    assert_eq!(routes.routes, vec![]);
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        ExecuteMsg::UpdateDex {
+            name: "terraswap".to_string(),
+            enabled: true,
+        },
+    );
+    match res {
+        Err(StdError::GenericErr { msg, .. }) => {
+            assert_eq!(msg, "Dex terraswap is not registered")
+        }
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    // a new dex is used as soon as it is added
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::AddDex {
+            dex: dex_info("phoenix_v2", OperationFlavour::TokenSwap),
+        },
+    )
+    .unwrap();
+    assert_eq!(swap_router(&mut deps), vec!["phoenix_v2_addr".to_string()]);
+
+    let dexes: DexesResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Dexes {}).unwrap()).unwrap();
+    assert_eq!(
+        dexes.dexes,
+        vec![
+            DexResponse {
+                name: "astroport".to_string(),
+                router: "astroport_addr".to_string(),
+                flavour: OperationFlavour::AstroSwap,
+                enabled: true,
+            },
+            DexResponse {
+                name: "phoenix".to_string(),
+                router: "phoenix_addr".to_string(),
+                flavour: OperationFlavour::TokenSwap,
+                enabled: false,
+            },
+            DexResponse {
+                name: "phoenix_v2".to_string(),
+                router: "phoenix_v2_addr".to_string(),
+                flavour: OperationFlavour::TokenSwap,
+                enabled: true,
+            },
+        ]
+    );
+}
+
+#[test]
+fn swap_denoms() {
+    let mut deps = mock_dependencies(&[
+        Coin {
+            denom: "uusd".to_string(),
+            amount: Uint128::new(100u128),
+        },
+        Coin {
+            denom: "ukrw".to_string(),
+            amount: Uint128::new(1000u128),
+        },
+        Coin {
+            denom: "usdr".to_string(),
+            amount: Uint128::new(50u128),
+        },
+        Coin {
+            denom: "uinr".to_string(),
+            amount: Uint128::new(50u128),
+        },
+    ]);
+
+    let init_msg = default_init();
+    let info = mock_info("addr0000", &[]);
+    instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+    let set_swap_denom = |denom: &str, listing: Option<DenomListing>, min_amount: Option<u128>| {
+        ExecuteMsg::SetSwapDenom {
+            denom: denom.to_string(),
+            listing,
+            min_amount: min_amount.map(Uint128::new),
+        }
+    };
+
+    // only the owner can set the swap denoms
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("villain", &[]),
+        set_swap_denom("uinr", Some(DenomListing::Denied), None),
+    );
+    match res {
+        Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, "unauthorized"),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        set_swap_denom(DEFAULT_REWARD_DENOM, Some(DenomListing::Denied), None),
+    );
+    match res {
+        Err(StdError::GenericErr { msg, .. }) => {
+            assert_eq!(msg, "The reward denom is never swapped")
+        }
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        set_swap_denom("uinr", Some(DenomListing::Denied), None),
+    )
+    .unwrap();
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        set_swap_denom("usdr", None, Some(100)),
+    )
+    .unwrap();
+
+    let swap = |deps: &mut OwnedDeps<_, _, _>| {
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(MOCK_HUB_CONTRACT_ADDR, &[]),
+            ExecuteMsg::SwapToRewardDenom {},
+        )
+        .unwrap()
+    };
+
+    // the denied denom and the dust are kept
+    let res = swap(&mut deps);
+    assert_eq!(res.messages.len(), 1);
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "swap"),
+            attr("skipped", "50usdr:dust"),
+            attr("skipped", "50uinr:denied"),
+        ]
+    );
+
+    // once a denom is allowed, the other ones are kept
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        set_swap_denom("ukrw", Some(DenomListing::Allowed), None),
+    )
+    .unwrap();
+    let res = swap(&mut deps);
+    assert_eq!(res.messages.len(), 1);
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "swap"),
+            attr("skipped", "50usdr:not_allowed"),
+            attr("skipped", "50uinr:denied"),
+        ]
+    );
+
+    let denoms: SwapDenomsResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::SwapDenoms {}).unwrap()).unwrap();
+    assert_eq!(
+        denoms.denoms,
+        vec![
+            SwapDenomResponse {
+                denom: "uinr".to_string(),
+                listing: Some(DenomListing::Denied),
+                min_amount: None,
+            },
+            SwapDenomResponse {
+                denom: "ukrw".to_string(),
+                listing: Some(DenomListing::Allowed),
+                min_amount: None,
+            },
+            SwapDenomResponse {
+                denom: "usdr".to_string(),
+                listing: None,
+                min_amount: Some(Uint128::new(100)),
+            },
+        ]
+    );
+
+    for denom in ["uinr", "ukrw", "usdr"] {
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            ExecuteMsg::ClearSwapDenom {
+                denom: denom.to_string(),
+            },
+        )
+        .unwrap();
+    }
+    let res = swap(&mut deps);
+    assert_eq!(res.messages.len(), 3);
+    assert_eq!(res.attributes, vec![attr("action", "swap")]);
+}
+
+#[test]
+fn failed_swaps() {
+    let mut deps = mock_dependencies(&[
+        Coin {
+            denom: "ukrw".to_string(),
+            amount: Uint128::new(1000u128),
+        },
+        Coin {
+            denom: "usdr".to_string(),
+            amount: Uint128::new(50u128),
+        },
+    ]);
+
+    let init_msg = default_init();
+    let info = mock_info("addr0000", &[]);
+    instantiate(deps.as_mut(), mock_env(), info, init_msg).unwrap();
+
+    let swap = |deps: &mut OwnedDeps<_, _, _>| {
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(MOCK_HUB_CONTRACT_ADDR, &[]),
+            ExecuteMsg::SwapToRewardDenom {},
+        )
+        .unwrap()
+    };
+
+    // every swap is dispatched apart, so that a failing one doesn't revert the others
+    let res = swap(&mut deps);
+    assert_eq!(
+        res.messages
+            .iter()
+            .map(|msg| (msg.id, msg.reply_on.clone()))
+            .collect::<Vec<(u64, ReplyOn)>>(),
+        vec![(0, ReplyOn::Error), (1, ReplyOn::Error)]
+    );
+
+    let res = reply(
+        deps.as_mut(),
+        mock_env(),
+        Reply {
+            id: 1,
+            result: SubMsgResult::Err("Operation exceeds max spread limit".to_string()),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "failed_swap"),
+            attr("denom", "usdr"),
+            attr("error", "Operation exceeds max spread limit"),
+        ]
+    );
+
+    let failed_swaps: FailedSwapsResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::FailedSwaps {}).unwrap()).unwrap();
+    assert_eq!(
+        failed_swaps.failed_swaps,
+        vec![FailedSwapResponse {
+            denom: "usdr".to_string(),
+            amount: Uint128::new(50),
+            error: "Operation exceeds max spread limit".to_string(),
+            failed_at: mock_env().block.time.seconds(),
+        }]
+    );
+
+    // the failure is forgotten once the swap is retried, and the swaps of the previous
+    // round can't reply anymore
+    swap(&mut deps);
+    let failed_swaps: FailedSwapsResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::FailedSwaps {}).unwrap()).unwrap();
+    assert_eq!(failed_swaps.failed_swaps, vec![]);
+    assert_eq!(
+        SWAP_REPLIES
+            .keys(deps.as_ref().storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<u64>>>()
+            .unwrap(),
+        vec![0, 1]
+    );
+}
+
 #[test]
 fn update_global_index() {
     let mut deps = mock_dependencies(&[Coin {
@@ -194,6 +1103,8 @@ fn update_global_index() {
             global_index: Decimal256::zero(),
             total_balance: Uint128::from(100u128),
             prev_reward_balance: Uint128::zero(),
+            total_protocol_fee: Uint128::zero(),
+            total_compound_protocol_fee: Uint128::zero(),
         },
     )
     .unwrap();
@@ -209,9 +1120,348 @@ fn update_global_index() {
         StateResponse {
             global_index: Decimal256::one(),
             total_balance: Uint128::from(100u128),
-            prev_reward_balance: Uint128::from(100u128)
+            prev_reward_balance: Uint128::from(100u128),
+            total_protocol_fee: Uint128::zero(),
+            total_compound_protocol_fee: Uint128::zero(),
+        }
+    );
+}
+
+#[test]
+fn index_history() {
+    let mut deps = mock_dependencies(&[Coin {
+        denom: "uusd".to_string(),
+        amount: Uint128::new(100u128),
+    }]);
+
+    let init_msg = default_init();
+    let info = mock_info("addr0000", &[]);
+    let start = mock_env();
+    instantiate(deps.as_mut(), start.clone(), info, init_msg).unwrap();
+    let t0 = start.block.time.seconds();
+
+    let msg = ExecuteMsg::IncreaseBalance {
+        address: String::from("addr0000"),
+        amount: Uint128::from(100u128),
+    };
+    let info = mock_info(MOCK_TOKEN_CONTRACT_ADDR, &[]);
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    // claimed_rewards = 100, total_balance = 100
+    // global_index == 1
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(100);
+    let info = mock_info(MOCK_HUB_CONTRACT_ADDR, &[]);
+    execute(deps.as_mut(), env, info, ExecuteMsg::UpdateGlobalIndex {}).unwrap();
+
+    // the updates of a block are merged
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(200);
+    for prev_reward_balance in [50u128, 75u128] {
+        let mut state = read_state(deps.as_ref().storage).unwrap();
+        state.prev_reward_balance = Uint128::new(prev_reward_balance);
+        store_state(deps.as_mut().storage, &state).unwrap();
+
+        let info = mock_info(MOCK_HUB_CONTRACT_ADDR, &[]);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            info,
+            ExecuteMsg::UpdateGlobalIndex {},
+        )
+        .unwrap();
+    }
+
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::IndexHistory {
+            start_after: None,
+            limit: None,
+        },
+    )
+    .unwrap();
+    let history: IndexHistoryResponse = from_binary(&res).unwrap();
+    assert_eq!(
+        history.updates,
+        vec![
+            IndexUpdateResponse {
+                time: t0,
+                claimed_rewards: Uint128::zero(),
+                total_balance: Uint128::zero(),
+                global_index: Decimal256::zero(),
+            },
+            IndexUpdateResponse {
+                time: t0 + 100,
+                claimed_rewards: Uint128::new(100),
+                total_balance: Uint128::new(100),
+                global_index: Decimal256::one(),
+            },
+            IndexUpdateResponse {
+                time: t0 + 200,
+                claimed_rewards: Uint128::new(75),
+                total_balance: Uint128::new(100),
+                global_index: Decimal256::from_str("1.75").unwrap(),
+            },
+        ]
+    );
+
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::IndexHistory {
+            start_after: Some(t0),
+            limit: Some(1),
+        },
+    )
+    .unwrap();
+    let history: IndexHistoryResponse = from_binary(&res).unwrap();
+    assert_eq!(history.updates.len(), 1);
+    assert_eq!(history.updates[0].time, t0 + 100);
+
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::RewardsForPeriod {
+            balance: Uint128::new(10),
+            start: t0 + 50,
+            end: t0 + 200,
+        },
+    )
+    .unwrap();
+    let rewards: RewardsForPeriodResponse = from_binary(&res).unwrap();
+    assert_eq!(
+        rewards,
+        RewardsForPeriodResponse {
+            rewards: Uint128::new(17),
+            start_index: Decimal256::zero(),
+            end_index: Decimal256::from_str("1.75").unwrap(),
+        }
+    );
+
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::RewardsForPeriod {
+            balance: Uint128::new(10),
+            start: t0 - 1,
+            end: t0 + 200,
+        },
+    );
+    match res {
+        Err(StdError::GenericErr { msg, .. }) => {
+            assert_eq!(msg, format!("No global index is recorded at {}", t0 - 1))
+        }
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::RewardsForPeriod {
+            balance: Uint128::new(10),
+            start: t0 + 200,
+            end: t0 + 100,
+        },
+    );
+    match res {
+        Err(StdError::GenericErr { msg, .. }) => {
+            assert_eq!(msg, "The period can not end before it starts")
+        }
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+}
+
+#[test]
+fn protocol_fee() {
+    let mut deps = mock_dependencies(&[Coin {
+        denom: "uusd".to_string(),
+        amount: Uint128::new(100u128),
+    }]);
+
+    let init_msg = default_init();
+    let info = mock_info("addr0000", &[]);
+    instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+    // only the owner can update the fee config
+    let msg = ExecuteMsg::UpdateFeeConfig {
+        protocol_fee_bps: 1000,
+        fee_collector: Some("collector".to_string()),
+    };
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("villain", &[]),
+        msg.clone(),
+    );
+    match res {
+        Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, "unauthorized"),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    let invalid_msgs = vec![
+        (
+            ExecuteMsg::UpdateFeeConfig {
+                protocol_fee_bps: 10001,
+                fee_collector: Some("collector".to_string()),
+            },
+            "protocol_fee_bps must not exceed 3000",
+        ),
+        (
+            ExecuteMsg::UpdateFeeConfig {
+                protocol_fee_bps: 3001,
+                fee_collector: Some("collector".to_string()),
+            },
+            "protocol_fee_bps must not exceed 3000",
+        ),
+        (
+            ExecuteMsg::UpdateFeeConfig {
+                protocol_fee_bps: 1000,
+                fee_collector: None,
+            },
+            "A fee collector is required to take a protocol fee",
+        ),
+    ];
+    for (invalid_msg, error) in invalid_msgs {
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), invalid_msg);
+        match res {
+            Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, error),
+            _ => panic!("DO NOT ENTER HERE"),
+        }
+    }
+
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    let config: ConfigResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+    assert_eq!(config.protocol_fee_bps, 1000);
+    assert_eq!(config.fee_collector, Some("collector".to_string()));
+
+    store_state(
+        &mut deps.storage,
+        &State {
+            global_index: Decimal256::zero(),
+            total_balance: Uint128::from(100u128),
+            prev_reward_balance: Uint128::zero(),
+            total_protocol_fee: Uint128::zero(),
+            total_compound_protocol_fee: Uint128::zero(),
+        },
+    )
+    .unwrap();
+
+    // claimed_rewards = 100, protocol_fee = 10, total_balance = 100
+    // global_index == 0.9
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(MOCK_HUB_CONTRACT_ADDR, &[]),
+        ExecuteMsg::UpdateGlobalIndex {},
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: "collector".to_string(),
+            amount: vec![Coin::new(10, DEFAULT_REWARD_DENOM)],
+        }))]
+    );
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "update_global_index"),
+            attr("claimed_rewards", "100"),
+            attr("protocol_fee", "10"),
+        ]
+    );
+
+    let state: StateResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::State {}).unwrap()).unwrap();
+    assert_eq!(
+        state,
+        StateResponse {
+            global_index: Decimal256::from_str("0.9").unwrap(),
+            total_balance: Uint128::from(100u128),
+            prev_reward_balance: Uint128::from(90u128),
+            total_protocol_fee: Uint128::from(10u128),
+            total_compound_protocol_fee: Uint128::zero(),
+        }
+    );
+
+    // the hub sends the fee it takes on the compounded rewards through the reward contract
+    let msg = ExecuteMsg::CollectCompoundProtocolFee {};
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("villain", &[Coin::new(5, "uluna")]),
+        msg.clone(),
+    );
+    match res {
+        Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, "unauthorized"),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    let invalid_funds = vec![
+        vec![],
+        vec![Coin::new(0, "uluna")],
+        vec![Coin::new(5, "uluna"), Coin::new(5, "ukrw")],
+    ];
+    for funds in invalid_funds {
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(MOCK_HUB_CONTRACT_ADDR, &funds),
+            msg.clone(),
+        );
+        match res {
+            Err(StdError::GenericErr { msg, .. }) => {
+                assert_eq!(msg, "The protocol fee must be sent in a single denom")
+            }
+            _ => panic!("DO NOT ENTER HERE"),
         }
+    }
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(MOCK_HUB_CONTRACT_ADDR, &[Coin::new(5, "uluna")]),
+        msg.clone(),
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: "collector".to_string(),
+            amount: vec![Coin::new(5, "uluna")],
+        }))]
+    );
+
+    let state: StateResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::State {}).unwrap()).unwrap();
+    assert_eq!(state.total_protocol_fee, Uint128::from(10u128));
+    assert_eq!(state.total_compound_protocol_fee, Uint128::from(5u128));
+
+    // without a fee collector there is nowhere to send it to
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        ExecuteMsg::UpdateFeeConfig {
+            protocol_fee_bps: 0,
+            fee_collector: None,
+        },
+    )
+    .unwrap();
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(MOCK_HUB_CONTRACT_ADDR, &[Coin::new(5, "uluna")]),
+        msg,
     );
+    match res {
+        Err(StdError::GenericErr { msg, .. }) => {
+            assert_eq!(msg, "No fee collector to send the protocol fee to")
+        }
+        _ => panic!("DO NOT ENTER HERE"),
+    }
 }
 
 #[test]
@@ -497,7 +1747,10 @@ fn claim_rewards() {
     let msg = ExecuteMsg::UpdateGlobalIndex {};
     execute(deps.as_mut(), mock_env(), info, msg).unwrap();
 
-    let msg = ExecuteMsg::ClaimRewards { recipient: None };
+    let msg = ExecuteMsg::ClaimRewards {
+        recipient: None,
+        ibc_transfer: None,
+    };
     let info = mock_info("addr0000", &[]);
     let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
     assert_eq!(
@@ -518,21 +1771,203 @@ fn claim_rewards() {
     let msg = ExecuteMsg::UpdateGlobalIndex {};
     execute(deps.as_mut(), mock_env(), info, msg).unwrap();
 
-    let msg = ExecuteMsg::ClaimRewards {
-        recipient: Some(String::from("addr0001")),
-    };
-    let info = mock_info("addr0000", &[]);
-    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    let msg = ExecuteMsg::ClaimRewards {
+        recipient: Some(String::from("addr0001")),
+        ibc_transfer: None,
+    };
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: String::from("addr0001"),
+            amount: vec![Coin {
+                denom: "uusd".to_string(),
+                amount: Uint128::from(100u128), // No tax
+            },]
+        }))]
+    );
+}
+
+#[test]
+fn claim_rewards_ibc() {
+    let mut deps = mock_dependencies(&[Coin {
+        denom: "uusd".to_string(),
+        amount: Uint128::new(100u128),
+    }]);
+
+    let init_msg = default_init();
+    let info = mock_info("addr0000", &[]);
+    instantiate(deps.as_mut(), mock_env(), info, init_msg).unwrap();
+
+    let msg = ExecuteMsg::IncreaseBalance {
+        address: String::from("addr0001"),
+        amount: Uint128::from(100u128),
+    };
+    let info = mock_info(MOCK_TOKEN_CONTRACT_ADDR, &[]);
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let info = mock_info(MOCK_HUB_CONTRACT_ADDR, &[]);
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::UpdateGlobalIndex {},
+    )
+    .unwrap();
+
+    let transfer = IbcTransfer {
+        channel_id: "channel-1".to_string(),
+        remote_address: "osmo1remote".to_string(),
+        timeout: 600,
+    };
+    let claim = ExecuteMsg::ClaimRewards {
+        recipient: None,
+        ibc_transfer: Some(transfer.clone()),
+    };
+
+    // the channel must be allowed first
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0001", &[]),
+        claim.clone(),
+    );
+    match res {
+        Err(StdError::GenericErr { msg, .. }) => {
+            assert_eq!(msg, "IBC channel channel-1 is not allowed")
+        }
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    // only the owner can allow channels
+    let allow = ExecuteMsg::AllowIbcChannel {
+        channel_id: "channel-1".to_string(),
+    };
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0001", &[]),
+        allow.clone(),
+    );
+    match res {
+        Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, "unauthorized"),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+    execute(deps.as_mut(), mock_env(), mock_info("addr0000", &[]), allow).unwrap();
+
+    let res = query(deps.as_ref(), mock_env(), QueryMsg::IbcChannels {}).unwrap();
+    let channels: IbcChannelsResponse = from_binary(&res).unwrap();
+    assert_eq!(channels.channels, vec!["channel-1".to_string()]);
+
+    // the rewards can't go to a local recipient at the same time
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0001", &[]),
+        ExecuteMsg::ClaimRewards {
+            recipient: Some(String::from("addr0002")),
+            ibc_transfer: Some(transfer.clone()),
+        },
+    );
+    match res {
+        Err(StdError::GenericErr { msg, .. }) => {
+            assert_eq!(msg, "Can't claim to both a recipient and an IBC transfer")
+        }
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    // the transfer must leave time for the packet to be relayed
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0001", &[]),
+        ExecuteMsg::ClaimRewards {
+            recipient: None,
+            ibc_transfer: Some(IbcTransfer {
+                timeout: 10,
+                ..transfer.clone()
+            }),
+        },
+    );
+    match res {
+        Err(StdError::GenericErr { msg, .. }) => assert_eq!(
+            msg,
+            format!(
+                "The IBC transfer timeout must be at least {} seconds",
+                MIN_TIMEOUT
+            )
+        ),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    let env = mock_env();
+    let rewards = Coin {
+        denom: "uusd".to_string(),
+        amount: Uint128::from(100u128),
+    };
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("addr0001", &[]),
+        claim.clone(),
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![transfer_msg(&env, transfer, &rewards, TRANSFER_REPLY_ID)]
+    );
+
+    // the packet of the transfer is recorded from the reply
+    let send_packet = Event::new("send_packet")
+        .add_attribute("packet_src_channel", "channel-1")
+        .add_attribute("packet_sequence", "3");
+    reply(
+        deps.as_mut(),
+        mock_env(),
+        Reply {
+            id: TRANSFER_REPLY_ID,
+            result: SubMsgResult::Ok(SubMsgResponse {
+                events: vec![send_packet],
+                data: None,
+            }),
+        },
+    )
+    .unwrap();
+
+    // the rewards refunded on an error acknowledgement go back to the holder
+    let res = sudo(
+        deps.as_mut(),
+        mock_env(),
+        SudoMsg::IbcLifecycleComplete(IbcLifecycleComplete::IbcAck {
+            channel: "channel-1".to_string(),
+            sequence: 3,
+            ack: "error".to_string(),
+            success: false,
+        }),
+    )
+    .unwrap();
     assert_eq!(
         res.messages,
         vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
             to_address: String::from("addr0001"),
-            amount: vec![Coin {
-                denom: "uusd".to_string(),
-                amount: Uint128::from(100u128), // No tax
-            },]
+            amount: vec![rewards],
         }))]
     );
+
+    let disallow = ExecuteMsg::DisallowIbcChannel {
+        channel_id: "channel-1".to_string(),
+    };
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        disallow,
+    )
+    .unwrap();
+    let res = query(deps.as_ref(), mock_env(), QueryMsg::IbcChannels {}).unwrap();
+    let channels: IbcChannelsResponse = from_binary(&res).unwrap();
+    assert!(channels.channels.is_empty());
 }
 
 #[test]
@@ -581,7 +2016,10 @@ fn claim_rewards_with_decimals() {
     let msg = ExecuteMsg::UpdateGlobalIndex {};
     execute(deps.as_mut(), mock_env(), info, msg).unwrap();
 
-    let msg = ExecuteMsg::ClaimRewards { recipient: None };
+    let msg = ExecuteMsg::ClaimRewards {
+        recipient: None,
+        ibc_transfer: None,
+    };
     let info = mock_info("addr0000", &[]);
     let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
     assert_eq!(
@@ -622,9 +2060,238 @@ fn claim_rewards_with_decimals() {
         StateResponse {
             global_index: index,
             total_balance: Uint128::new(11u128),
-            prev_reward_balance: Uint128::new(1)
+            prev_reward_balance: Uint128::new(1),
+            total_protocol_fee: Uint128::zero(),
+            total_compound_protocol_fee: Uint128::zero(),
+        }
+    );
+}
+
+#[test]
+fn claim_rewards_for() {
+    let mut deps = mock_dependencies(&[Coin {
+        denom: "uusd".to_string(),
+        amount: Uint128::new(100u128),
+    }]);
+
+    let init_msg = default_init();
+    let info = mock_info("addr0000", &[]);
+
+    instantiate(deps.as_mut(), mock_env(), info, init_msg).unwrap();
+
+    for (address, amount) in [("addr0000", 60u128), ("addr0001", 40u128)] {
+        let msg = ExecuteMsg::IncreaseBalance {
+            address: String::from(address),
+            amount: Uint128::from(amount),
+        };
+        let info = mock_info(MOCK_TOKEN_CONTRACT_ADDR, &[]);
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    }
+
+    // claimed_rewards = 100, total_balance = 100
+    // global_index == 1
+    let info = mock_info(MOCK_HUB_CONTRACT_ADDR, &[]);
+    let msg = ExecuteMsg::UpdateGlobalIndex {};
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let msg = ExecuteMsg::ApproveClaimer {
+        claimer: String::from("keeper"),
+        recipient: Some(String::from("vault")),
+    };
+    let info = mock_info("addr0000", &[]);
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    // every holder must have approved the claimer
+    let msg = ExecuteMsg::ClaimRewardsFor {
+        holders: vec![String::from("addr0000"), String::from("addr0001")],
+    };
+    let info = mock_info("keeper", &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg.clone());
+    match res {
+        Err(StdError::GenericErr { msg, .. }) => {
+            assert_eq!(msg, "Claimer is not approved by addr0001")
+        }
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    let approve_msg = ExecuteMsg::ApproveClaimer {
+        claimer: String::from("keeper"),
+        recipient: None,
+    };
+    let info = mock_info("addr0001", &[]);
+    execute(deps.as_mut(), mock_env(), info, approve_msg).unwrap();
+
+    let info = mock_info("keeper", &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+                to_address: String::from("vault"),
+                amount: vec![Coin {
+                    denom: "uusd".to_string(),
+                    amount: Uint128::from(60u128),
+                },]
+            })),
+            SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+                to_address: String::from("addr0001"),
+                amount: vec![Coin {
+                    denom: "uusd".to_string(),
+                    amount: Uint128::from(40u128),
+                },]
+            })),
+        ]
+    );
+
+    // holders without rewards are skipped
+    let info = mock_info("keeper", &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap();
+    assert!(res.messages.is_empty());
+
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::Claimers {
+            holder: String::from("addr0000"),
+        },
+    )
+    .unwrap();
+    let claimers_response: ClaimersResponse = from_binary(&res).unwrap();
+    assert_eq!(
+        claimers_response.claimers,
+        vec![ClaimerResponse {
+            claimer: String::from("keeper"),
+            recipient: String::from("vault"),
+        }]
+    );
+
+    let revoke_msg = ExecuteMsg::RevokeClaimer {
+        claimer: String::from("keeper"),
+    };
+    let info = mock_info("addr0000", &[]);
+    execute(deps.as_mut(), mock_env(), info.clone(), revoke_msg.clone()).unwrap();
+    let res = execute(deps.as_mut(), mock_env(), info, revoke_msg);
+    match res {
+        Err(StdError::GenericErr { msg, .. }) => {
+            assert_eq!(msg, "Claimer keeper is not approved")
+        }
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    let info = mock_info("keeper", &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg);
+    match res {
+        Err(StdError::GenericErr { msg, .. }) => {
+            assert_eq!(msg, "Claimer is not approved by addr0000")
         }
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+}
+
+#[test]
+fn auto_claim() {
+    let mut deps = mock_dependencies(&[Coin {
+        denom: "uusd".to_string(),
+        amount: Uint128::new(100u128),
+    }]);
+
+    let init_msg = default_init();
+    let info = mock_info("addr0000", &[]);
+
+    instantiate(deps.as_mut(), mock_env(), info, init_msg).unwrap();
+
+    let msg = ExecuteMsg::IncreaseBalance {
+        address: String::from("addr0000"),
+        amount: Uint128::from(100u128),
+    };
+    let info = mock_info(MOCK_TOKEN_CONTRACT_ADDR, &[]);
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    // claimed_rewards = 100, total_balance = 100
+    // global_index == 1
+    let info = mock_info(MOCK_HUB_CONTRACT_ADDR, &[]);
+    let msg = ExecuteMsg::UpdateGlobalIndex {};
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let msg = ExecuteMsg::SetAutoClaim {
+        enabled: true,
+        recipient: Some(String::from("addr0001")),
+    };
+    let info = mock_info("addr0000", &[]);
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::AutoClaim {
+            address: String::from("addr0000"),
+        },
+    )
+    .unwrap();
+    let auto_claim_response: AutoClaimResponse = from_binary(&res).unwrap();
+    assert_eq!(
+        auto_claim_response.recipient,
+        Some(String::from("addr0001"))
+    );
+
+    // the accrued rewards are paid out on the balance change
+    let msg = ExecuteMsg::DecreaseBalance {
+        address: String::from("addr0000"),
+        amount: Uint128::from(50u128),
+    };
+    let info = mock_info(MOCK_TOKEN_CONTRACT_ADDR, &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: String::from("addr0001"),
+            amount: vec![Coin {
+                denom: "uusd".to_string(),
+                amount: Uint128::from(100u128),
+            },]
+        }))]
     );
+    assert!(res
+        .attributes
+        .contains(&attr("auto_claimed", Uint128::from(100u128))));
+
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::Holder {
+            address: String::from("addr0000"),
+        },
+    )
+    .unwrap();
+    let holder_response: HolderResponse = from_binary(&res).unwrap();
+    assert_eq!(holder_response.pending_rewards, Decimal256::zero());
+
+    // nothing is left to pay out
+    let msg = ExecuteMsg::IncreaseBalance {
+        address: String::from("addr0000"),
+        amount: Uint128::from(50u128),
+    };
+    let info = mock_info(MOCK_TOKEN_CONTRACT_ADDR, &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    assert!(res.messages.is_empty());
+
+    let msg = ExecuteMsg::SetAutoClaim {
+        enabled: false,
+        recipient: None,
+    };
+    let info = mock_info("addr0000", &[]);
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::AutoClaim {
+            address: String::from("addr0000"),
+        },
+    )
+    .unwrap();
+    let auto_claim_response: AutoClaimResponse = from_binary(&res).unwrap();
+    assert_eq!(auto_claim_response.recipient, None);
 }
 
 #[test]
@@ -801,6 +2468,8 @@ fn proper_prev_balance() {
             global_index,
             total_balance: all_balance,
             prev_reward_balance: rewards,
+            total_protocol_fee: Uint128::zero(),
+            total_compound_protocol_fee: Uint128::zero(),
         },
     )
     .unwrap();
@@ -841,15 +2510,24 @@ fn proper_prev_balance() {
     )
     .unwrap();
 
-    let msg = ExecuteMsg::ClaimRewards { recipient: None };
+    let msg = ExecuteMsg::ClaimRewards {
+        recipient: None,
+        ibc_transfer: None,
+    };
     let info = mock_info("addr0000", &[]);
     let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
 
-    let msg = ExecuteMsg::ClaimRewards { recipient: None };
+    let msg = ExecuteMsg::ClaimRewards {
+        recipient: None,
+        ibc_transfer: None,
+    };
     let info = mock_info("addr0001", &[]);
     let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
 
-    let msg = ExecuteMsg::ClaimRewards { recipient: None };
+    let msg = ExecuteMsg::ClaimRewards {
+        recipient: None,
+        ibc_transfer: None,
+    };
     let info = mock_info("addr0002", &[]);
     let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
 
@@ -860,7 +2538,9 @@ fn proper_prev_balance() {
         StateResponse {
             global_index,
             total_balance: all_balance,
-            prev_reward_balance: Uint128::new(1)
+            prev_reward_balance: Uint128::new(1),
+            total_protocol_fee: Uint128::zero(),
+            total_compound_protocol_fee: Uint128::zero(),
         }
     );
 
@@ -940,9 +2620,189 @@ fn test_migrate() {
             },
         )
         .unwrap();
+    OLD_SWAP_CONFIG
+        .save(
+            mut_deps.storage,
+            &OldSwapConfig {
+                astroport_addr: Addr::unchecked("astroport_addr"),
+                phoenix_addr: Addr::unchecked("phoenix_addr"),
+                terraswap_addr: Addr::unchecked("terraswap_addr"),
+                max_spread: None,
+                slippage_tolerance: Decimal::percent(2),
+                split_chunks: None,
+            },
+        )
+        .unwrap();
+
+    // the old config has no owner to keep
+    let res = migrate(mut_deps, mock_env(), MigrateMsg { owner: None });
+    match res {
+        Err(StdError::GenericErr { msg, .. }) => {
+            assert_eq!(msg, "An owner is required to migrate a config without one")
+        }
+        _ => panic!("DO NOT ENTER HERE"),
+    }
 
-    migrate(mut_deps, mock_env(), MigrateMsg {}).unwrap();
+    migrate(
+        deps.as_mut(),
+        mock_env(),
+        MigrateMsg {
+            owner: Some("owner".to_string()),
+        },
+    )
+    .unwrap();
 
     let new_config = CONFIG.load(deps.as_ref().storage).unwrap();
     assert_eq!(new_config.hub_contract.to_string(), "memememe");
+    assert_eq!(new_config.reward_denom, "stable?");
+    assert_eq!(new_config.owner.to_string(), "owner");
+
+    // the owner of a migrated config can't be replaced by a later migration
+    migrate(
+        deps.as_mut(),
+        mock_env(),
+        MigrateMsg {
+            owner: Some("villain".to_string()),
+        },
+    )
+    .unwrap();
+    let config = CONFIG.load(deps.as_ref().storage).unwrap();
+    assert_eq!(config, new_config);
+
+    // the dexes of the swap config are stored apart
+    assert_eq!(
+        SWAP_CONFIG.load(deps.as_ref().storage).unwrap(),
+        SwapConfig {
+            max_spread: None,
+            slippage_tolerance: Decimal::percent(2),
+            split_chunks: None,
+        }
+    );
+    let dexes: DexesResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Dexes {}).unwrap()).unwrap();
+    let dex = |name: &str, router: &str, flavour: OperationFlavour| DexResponse {
+        name: name.to_string(),
+        router: router.to_string(),
+        flavour,
+        enabled: true,
+    };
+    assert_eq!(
+        dexes.dexes,
+        vec![
+            dex("astroport", "astroport_addr", OperationFlavour::AstroSwap),
+            dex("phoenix", "phoenix_addr", OperationFlavour::TokenSwap),
+            dex("terra_swap", "terraswap_addr", OperationFlavour::TerraSwap),
+        ]
+    );
+
+    // a config stored with the hub already as an addr keeps it unchanged
+    let mut deps = mock_dependencies(&[]);
+    deps.storage.set(
+        b"\x00\x06config",
+        br#"{"hub_contract":"memememe","reward_denom":"stable?"}"#,
+    );
+    migrate(
+        deps.as_mut(),
+        mock_env(),
+        MigrateMsg {
+            owner: Some("owner".to_string()),
+        },
+    )
+    .unwrap();
+    let config = CONFIG.load(deps.as_ref().storage).unwrap();
+    assert_eq!(config.hub_contract.to_string(), "memememe");
+    assert_eq!(config.reward_denom, "stable?");
+    assert_eq!(config.owner.to_string(), "owner");
+
+    // a hub contract that is neither layout is not guessed
+    let mut deps = mock_dependencies(&[]);
+    deps.storage.set(
+        b"\x00\x06config",
+        br#"{"hub_contract":"Not an address","reward_denom":"stable?"}"#,
+    );
+    let res = migrate(
+        deps.as_mut(),
+        mock_env(),
+        MigrateMsg {
+            owner: Some("owner".to_string()),
+        },
+    );
+    match res {
+        Err(StdError::GenericErr { msg, .. }) => {
+            assert_eq!(msg, "Unknown stored config layout")
+        }
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+}
+
+#[test]
+fn ownership_transfer() {
+    let mut deps = mock_dependencies(&[]);
+    let init_msg = default_init();
+    let info = mock_info("owner", &[]);
+    instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+    let propose = ExecuteMsg::ProposeNewOwner {
+        new_owner: "new_owner".to_string(),
+        expires_in: None,
+    };
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("new_owner", &[]),
+        propose.clone(),
+    );
+    match res {
+        Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, "unauthorized"),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    // the expiry can't overflow the block time
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        ExecuteMsg::ProposeNewOwner {
+            new_owner: "new_owner".to_string(),
+            expires_in: Some(u64::MAX),
+        },
+    );
+    match res {
+        Err(StdError::Overflow { .. }) => {}
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    execute(deps.as_mut(), mock_env(), info, propose).unwrap();
+
+    let res: PendingOwnerResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::PendingOwner {}).unwrap()).unwrap();
+    assert_eq!(res.pending_owner, Some("new_owner".to_string()));
+    assert_eq!(res.expiry, None);
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("villain", &[]),
+        ExecuteMsg::AcceptOwnership {},
+    );
+    match res {
+        Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, "unauthorized"),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("new_owner", &[]),
+        ExecuteMsg::AcceptOwnership {},
+    )
+    .unwrap();
+
+    let config: ConfigResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+    assert_eq!(config.owner, "new_owner");
+
+    let res: PendingOwnerResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::PendingOwner {}).unwrap()).unwrap();
+    assert_eq!(res.pending_owner, None);
 }
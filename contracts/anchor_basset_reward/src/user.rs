@@ -1,23 +1,54 @@
+use crate::contract::TRANSFER_REPLY_ID;
 use crate::querier::query_token_contract;
 use crate::state::{
     read_config, read_holder, read_holders, read_state, store_holder, store_state, Config, Holder,
-    State,
+    State, AUTO_CLAIMS, CLAIMERS, IBC_CHANNELS, PENDING_TRANSFER, PENDING_TRANSFERS,
+};
+use basset::ibc::{
+    transfer_msg, transfer_packet, IbcLifecycleComplete, IbcTransfer, PendingTransfer, MIN_TIMEOUT,
+};
+use basset::reward::{
+    AccruedRewardsResponse, AutoClaimResponse, ClaimerResponse, ClaimersResponse, HolderResponse,
+    HoldersResponse,
 };
-use basset::reward::{AccruedRewardsResponse, HolderResponse, HoldersResponse};
 use std::convert::TryInto;
 
 use cosmwasm_std::{
-    attr, BankMsg, Coin, CosmosMsg, Decimal256, Deps, DepsMut, Env, MessageInfo, Response,
-    StdError, StdResult, Uint128, Uint256,
+    attr, Addr, BankMsg, Coin, CosmosMsg, Decimal256, Deps, DepsMut, Env, MessageInfo, Order,
+    Reply, Response, StdError, StdResult, Storage, SubMsg, Uint128, Uint256,
 };
 use std::str::FromStr;
 
+/// Maximum number of holders per ClaimRewardsFor
+const MAX_CLAIM_HOLDERS: usize = 30;
+
 pub fn execute_claim_rewards(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
     recipient: Option<String>,
+    ibc_transfer: Option<IbcTransfer>,
 ) -> StdResult<Response> {
+    if let Some(transfer) = &ibc_transfer {
+        if recipient.is_some() {
+            return Err(StdError::generic_err(
+                "Can't claim to both a recipient and an IBC transfer",
+            ));
+        }
+        if !IBC_CHANNELS.has(deps.storage, &transfer.channel_id) {
+            return Err(StdError::generic_err(format!(
+                "IBC channel {} is not allowed",
+                transfer.channel_id
+            )));
+        }
+        if transfer.timeout < MIN_TIMEOUT {
+            return Err(StdError::generic_err(format!(
+                "The IBC transfer timeout must be at least {} seconds",
+                MIN_TIMEOUT
+            )));
+        }
+    }
+
     let holder_addr = info.sender;
     let holder_addr_raw = deps.api.addr_validate(holder_addr.as_str())?;
     let recipient = match recipient {
@@ -25,10 +56,260 @@ pub fn execute_claim_rewards(
         None => holder_addr.clone(),
     };
 
-    let mut holder: Holder = read_holder(deps.storage, &holder_addr_raw)?;
-    let mut state: State = read_state(deps.storage)?;
     let config: Config = read_config(deps.storage)?;
 
+    let rewards = settle_rewards(deps.storage, &holder_addr_raw)?;
+    if rewards.is_zero() {
+        return Err(StdError::generic_err("No rewards have accrued yet"));
+    }
+
+    let reward = Coin {
+        denom: config.reward_denom,
+        amount: rewards,
+    };
+    let mut attrs = vec![
+        attr("action", "claim_reward"),
+        attr("holder_address", holder_addr),
+        attr("rewards", rewards),
+    ];
+    let msg = match ibc_transfer {
+        Some(transfer) => {
+            attrs.push(attr("channel_id", transfer.channel_id.as_str()));
+            attrs.push(attr("remote_address", transfer.remote_address.as_str()));
+            // the rewards are sent back to the holder if the transfer fails
+            PENDING_TRANSFER.save(
+                deps.storage,
+                &PendingTransfer {
+                    owner: holder_addr_raw,
+                    amount: reward.clone(),
+                },
+            )?;
+            transfer_msg(&env, transfer, &reward, TRANSFER_REPLY_ID)
+        }
+        None => SubMsg::new(BankMsg::Send {
+            to_address: recipient.to_string(),
+            amount: vec![reward],
+        }),
+    };
+
+    let res = Response::new().add_attributes(attrs).add_submessage(msg);
+
+    Ok(res)
+}
+
+/// Record the packet of rewards transferred over IBC
+pub fn reply_ibc_transfer(deps: DepsMut, msg: Reply) -> StdResult<Response> {
+    let pending_transfer = PENDING_TRANSFER.load(deps.storage)?;
+    PENDING_TRANSFER.remove(deps.storage);
+
+    let (channel, sequence) = transfer_packet(msg)?;
+    PENDING_TRANSFERS.save(deps.storage, (&channel, sequence), &pending_transfer)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "ibc_transfer"),
+        attr("channel_id", channel),
+        attr("sequence", sequence.to_string()),
+    ]))
+}
+
+/// Send the rewards of a failed transfer, refunded to the contract, back to the holder.
+/// The refund and the send cancel out, so the refund is not taken for new rewards
+pub fn sudo_ibc_lifecycle_complete(
+    deps: DepsMut,
+    msg: IbcLifecycleComplete,
+) -> StdResult<Response> {
+    let (channel, sequence, success) = match msg {
+        IbcLifecycleComplete::IbcAck {
+            channel,
+            sequence,
+            success,
+            ..
+        } => (channel, sequence, success),
+        IbcLifecycleComplete::IbcTimeout { channel, sequence } => (channel, sequence, false),
+    };
+    let pending_transfer = match PENDING_TRANSFERS.may_load(deps.storage, (&channel, sequence))? {
+        Some(pending_transfer) => pending_transfer,
+        None => return Ok(Response::new()),
+    };
+    PENDING_TRANSFERS.remove(deps.storage, (&channel, sequence));
+
+    let mut res = Response::new().add_attributes(vec![
+        attr("action", "ibc_transfer_complete"),
+        attr("channel_id", channel),
+        attr("sequence", sequence.to_string()),
+        attr("success", success.to_string()),
+    ]);
+    if !success {
+        res = res
+            .add_attribute("refunded", pending_transfer.owner.as_str())
+            .add_message(BankMsg::Send {
+                to_address: pending_transfer.owner.to_string(),
+                amount: vec![pending_transfer.amount],
+            });
+    }
+    Ok(res)
+}
+
+pub fn execute_claim_rewards_for(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    holders: Vec<String>,
+) -> StdResult<Response> {
+    if holders.len() > MAX_CLAIM_HOLDERS {
+        return Err(StdError::generic_err(format!(
+            "Can't claim for more than {} holders",
+            MAX_CLAIM_HOLDERS
+        )));
+    }
+
+    let config: Config = read_config(deps.storage)?;
+
+    let approvals = holders
+        .iter()
+        .map(|holder| {
+            let holder_addr = deps.api.addr_validate(holder)?;
+            let recipient = CLAIMERS
+                .may_load(deps.storage, (&holder_addr, &info.sender))?
+                .ok_or_else(|| {
+                    StdError::generic_err(format!("Claimer is not approved by {}", holder_addr))
+                })?;
+            Ok((holder_addr, recipient))
+        })
+        .collect::<StdResult<Vec<(Addr, Addr)>>>()?;
+
+    let mut messages: Vec<CosmosMsg> = vec![];
+    let mut attrs = vec![
+        attr("action", "claim_rewards_for"),
+        attr("claimer", info.sender.as_str()),
+    ];
+    for (holder_addr, recipient) in approvals {
+        // holders without rewards don't fail the whole claim
+        let rewards = settle_rewards(deps.storage, &holder_addr)?;
+        if rewards.is_zero() {
+            continue;
+        }
+
+        messages.push(CosmosMsg::Bank(BankMsg::Send {
+            to_address: recipient.to_string(),
+            amount: vec![Coin {
+                denom: config.reward_denom.clone(),
+                amount: rewards,
+            }],
+        }));
+        attrs.push(attr("claimed", format!("{}:{}", holder_addr, rewards)));
+    }
+
+    Ok(Response::new().add_messages(messages).add_attributes(attrs))
+}
+
+pub fn execute_approve_claimer(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    claimer: String,
+    recipient: Option<String>,
+) -> StdResult<Response> {
+    let claimer = deps.api.addr_validate(&claimer)?;
+    let recipient = match recipient {
+        Some(value) => deps.api.addr_validate(&value)?,
+        None => info.sender.clone(),
+    };
+
+    CLAIMERS.save(deps.storage, (&info.sender, &claimer), &recipient)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "approve_claimer"),
+        attr("holder_address", info.sender),
+        attr("claimer", claimer),
+        attr("recipient", recipient),
+    ]))
+}
+
+pub fn execute_revoke_claimer(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    claimer: String,
+) -> StdResult<Response> {
+    let claimer = deps.api.addr_validate(&claimer)?;
+    if !CLAIMERS.has(deps.storage, (&info.sender, &claimer)) {
+        return Err(StdError::generic_err(format!(
+            "Claimer {} is not approved",
+            claimer
+        )));
+    }
+
+    CLAIMERS.remove(deps.storage, (&info.sender, &claimer));
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "revoke_claimer"),
+        attr("holder_address", info.sender),
+        attr("claimer", claimer),
+    ]))
+}
+
+pub fn execute_set_auto_claim(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    enabled: bool,
+    recipient: Option<String>,
+) -> StdResult<Response> {
+    let mut attributes = vec![
+        attr("action", "set_auto_claim"),
+        attr("holder_address", info.sender.as_str()),
+    ];
+
+    if enabled {
+        let recipient = match recipient {
+            Some(value) => deps.api.addr_validate(&value)?,
+            None => info.sender.clone(),
+        };
+        AUTO_CLAIMS.save(deps.storage, &info.sender, &recipient)?;
+        attributes.push(attr("recipient", recipient));
+    } else {
+        AUTO_CLAIMS.remove(deps.storage, &info.sender);
+    }
+
+    Ok(Response::new().add_attributes(attributes))
+}
+
+/// Settles the rewards of a holder with auto claim enabled, returning them along with the
+/// message paying them out to its recipient
+fn auto_claim_message(
+    storage: &mut dyn Storage,
+    holder_addr: &Addr,
+    reward_denom: String,
+) -> StdResult<Option<(Uint128, CosmosMsg)>> {
+    let recipient = match AUTO_CLAIMS.may_load(storage, holder_addr)? {
+        Some(recipient) => recipient,
+        None => return Ok(None),
+    };
+
+    let rewards = settle_rewards(storage, holder_addr)?;
+    if rewards.is_zero() {
+        return Ok(None);
+    }
+
+    Ok(Some((
+        rewards,
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: recipient.to_string(),
+            amount: vec![Coin {
+                denom: reward_denom,
+                amount: rewards,
+            }],
+        }),
+    )))
+}
+
+/// Moves the accrued rewards of the holder out of the reward balance, keeping the decimals
+/// as pending rewards. Returns the amount to send to the holder
+fn settle_rewards(storage: &mut dyn Storage, holder_addr: &Addr) -> StdResult<Uint128> {
+    let mut holder: Holder = read_holder(storage, holder_addr)?;
+    let mut state: State = read_state(storage)?;
+
     let reward_with_decimals =
         calculate_decimal_rewards(state.global_index, holder.index, holder.balance)?;
 
@@ -38,34 +319,18 @@ pub fn execute_claim_rewards(
     let rewards = all_reward_with_decimals * Uint256::one();
 
     if rewards.is_zero() {
-        return Err(StdError::generic_err("No rewards have accrued yet"));
+        return Ok(Uint128::zero());
     }
 
     let new_balance = (state.prev_reward_balance.checked_sub(rewards.try_into()?))?;
     state.prev_reward_balance = new_balance;
-    store_state(deps.storage, &state)?;
+    store_state(storage, &state)?;
 
     holder.pending_rewards = decimals;
     holder.index = state.global_index;
-    store_holder(deps.storage, &holder_addr_raw, &holder)?;
-
-    let bank_msg: CosmosMsg = CosmosMsg::Bank(BankMsg::Send {
-        to_address: recipient.to_string(),
-        amount: vec![Coin {
-            denom: config.reward_denom,
-            amount: rewards.try_into()?,
-        }],
-    });
-
-    let res = Response::new()
-        .add_attributes(vec![
-            attr("action", "claim_reward"),
-            attr("holder_address", holder_addr),
-            attr("rewards", rewards),
-        ])
-        .add_message(bank_msg);
+    store_holder(storage, holder_addr, &holder)?;
 
-    Ok(res)
+    Ok(rewards.try_into()?)
 }
 
 pub fn execute_increase_balance(
@@ -100,13 +365,20 @@ pub fn execute_increase_balance(
     store_holder(deps.storage, &address_raw, &holder)?;
     store_state(deps.storage, &state)?;
 
-    let attributes = vec![
+    let mut attributes = vec![
         attr("action", "increase_balance"),
         attr("holder_address", address),
         attr("amount", amount),
     ];
 
-    let res = Response::new().add_attributes(attributes);
+    let auto_claim = auto_claim_message(deps.storage, &address_raw, config.reward_denom)?;
+    if let Some((rewards, _)) = &auto_claim {
+        attributes.push(attr("auto_claimed", *rewards));
+    }
+
+    let res = Response::new()
+        .add_attributes(attributes)
+        .add_messages(auto_claim.map(|(_, msg)| msg));
     Ok(res)
 }
 
@@ -144,13 +416,20 @@ pub fn execute_decrease_balance(
     store_holder(deps.storage, &address_raw, &holder)?;
     store_state(deps.storage, &state)?;
 
-    let attributes = vec![
+    let mut attributes = vec![
         attr("action", "decrease_balance"),
         attr("holder_address", address),
         attr("amount", amount),
     ];
 
-    let res = Response::new().add_attributes(attributes);
+    let auto_claim = auto_claim_message(deps.storage, &address_raw, config.reward_denom)?;
+    if let Some((rewards, _)) = &auto_claim {
+        attributes.push(attr("auto_claimed", *rewards));
+    }
+
+    let res = Response::new()
+        .add_attributes(attributes)
+        .add_messages(auto_claim.map(|(_, msg)| msg));
 
     Ok(res)
 }
@@ -190,6 +469,31 @@ pub fn query_holders(
     Ok(HoldersResponse { holders })
 }
 
+pub fn query_claimers(deps: Deps, holder: String) -> StdResult<ClaimersResponse> {
+    let holder_addr = deps.api.addr_validate(&holder)?;
+    let claimers = CLAIMERS
+        .prefix(&holder_addr)
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            let (claimer, recipient) = item?;
+            Ok(ClaimerResponse {
+                claimer: claimer.to_string(),
+                recipient: recipient.to_string(),
+            })
+        })
+        .collect::<StdResult<Vec<ClaimerResponse>>>()?;
+
+    Ok(ClaimersResponse { claimers })
+}
+
+pub fn query_auto_claim(deps: Deps, address: String) -> StdResult<AutoClaimResponse> {
+    let recipient = AUTO_CLAIMS.may_load(deps.storage, &deps.api.addr_validate(&address)?)?;
+
+    Ok(AutoClaimResponse {
+        recipient: recipient.map(|recipient| recipient.to_string()),
+    })
+}
+
 // calculate the reward based on the sender's index and the global index.
 fn calculate_decimal_rewards(
     global_index: Decimal256,
@@ -111,7 +111,7 @@ pub fn execute_send(
     let reward_contract = query_reward_contract(&deps)?;
 
     let res: Response = cw20_send(deps, env, info, contract.clone(), amount, msg)?;
-    let messages = vec![
+    let messages = [
         vec![
             SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
                 contract_addr: reward_contract.to_string(),
@@ -219,7 +219,7 @@ pub fn execute_send_from(
     let valid_owner = deps.api.addr_validate(owner.as_str())?;
 
     let res: Response = cw20_send_from(deps, env, info, owner, contract.clone(), amount, msg)?;
-    let messages = vec![
+    let messages = [
         vec![
             SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
                 contract_addr: reward_contract.to_string(),
@@ -16,6 +16,83 @@ use crate::registry::ValidatorResponse;
 use cosmwasm_std::{StdError, StdResult, Uint128};
 use std::ops::Sub;
 
+/// Computes how much each validator should have delegated out of `total`.
+/// The stake is split proportionally to the validators' weights, validators which would
+/// exceed their max cap are capped and the rest is shared among the others.
+pub fn calculate_target_delegations(
+    total: Uint128,
+    validators: &[ValidatorResponse],
+) -> StdResult<Vec<Uint128>> {
+    let mut targets = vec![Uint128::zero(); validators.len()];
+    let mut capped = vec![false; validators.len()];
+    let mut to_distribute = total;
+
+    loop {
+        let total_weight: u128 = validators
+            .iter()
+            .zip(capped.iter())
+            .filter(|(_, capped)| !**capped)
+            .map(|(v, _)| v.weight as u128)
+            .sum();
+        if total_weight == 0 || to_distribute.is_zero() {
+            break;
+        }
+
+        // cap the validators whose share is above their max cap and share the rest again
+        let mut newly_capped = false;
+        for (index, validator) in validators.iter().enumerate() {
+            if capped[index] {
+                continue;
+            }
+            if let Some(max_cap) = validator.max_cap {
+                if to_distribute.multiply_ratio(validator.weight, total_weight) > max_cap {
+                    targets[index] = max_cap;
+                    capped[index] = true;
+                    newly_capped = true;
+                }
+            }
+        }
+        if newly_capped {
+            let total_capped: Uint128 = targets
+                .iter()
+                .zip(capped.iter())
+                .filter(|(_, capped)| **capped)
+                .map(|(target, _)| *target)
+                .sum();
+            to_distribute = total.checked_sub(total_capped)?;
+            continue;
+        }
+
+        let mut distributed = Uint128::zero();
+        for (index, validator) in validators.iter().enumerate() {
+            if capped[index] {
+                continue;
+            }
+            targets[index] = to_distribute.multiply_ratio(validator.weight, total_weight);
+            distributed += targets[index];
+        }
+
+        // the rounding leftover is given coin by coin to the first validators
+        let mut remaining_coins = to_distribute.checked_sub(distributed)?;
+        for (index, validator) in validators.iter().enumerate() {
+            if remaining_coins.is_zero() {
+                break;
+            }
+            if capped[index]
+                || validator.weight == 0
+                || validator.max_cap.is_some_and(|cap| targets[index] >= cap)
+            {
+                continue;
+            }
+            targets[index] += Uint128::new(1);
+            remaining_coins = remaining_coins.checked_sub(Uint128::new(1))?;
+        }
+        break;
+    }
+
+    Ok(targets)
+}
+
 pub fn calculate_delegations(
     mut amount_to_delegate: Uint128,
     validators: &[ValidatorResponse],
@@ -23,23 +100,15 @@ pub fn calculate_delegations(
     if validators.is_empty() {
         return Err(StdError::generic_err("Empty validators set"));
     }
-    let total_delegated: u128 = validators.iter().map(|v| v.total_delegated.u128()).sum();
-    let total_coins_to_distribute = Uint128::from(total_delegated) + amount_to_delegate;
-    let coins_per_validator = total_coins_to_distribute.u128() / validators.len() as u128;
-    let remaining_coins = total_coins_to_distribute.u128() % validators.len() as u128;
+    let total_delegated: Uint128 = validators.iter().map(|v| v.total_delegated).sum();
+    let targets = calculate_target_delegations(total_delegated + amount_to_delegate, validators)?;
 
     let mut delegations = vec![Uint128::zero(); validators.len()];
     for (index, validator) in validators.iter().enumerate() {
-        let extra_coin = if (index + 1) as u128 <= remaining_coins {
-            1u128
-        } else {
-            0u128
-        };
-        if coins_per_validator + extra_coin < validator.total_delegated.u128() {
+        if targets[index] <= validator.total_delegated {
             continue;
         }
-        let mut to_delegate =
-            Uint128::from(coins_per_validator + extra_coin).sub(validator.total_delegated);
+        let mut to_delegate = targets[index].sub(validator.total_delegated);
         if to_delegate > amount_to_delegate {
             to_delegate = amount_to_delegate
         }
@@ -72,18 +141,13 @@ pub fn calculate_undelegations(
 
     while !undelegation_amount.is_zero() {
         let total_coins_after_undelegation = total_delegated.sub(undelegation_amount);
-        let coins_per_validator = total_coins_after_undelegation.u128() / validators.len() as u128;
-        let remaining_coins = total_coins_after_undelegation.u128() % validators.len() as u128;
+        let targets =
+            calculate_target_delegations(total_coins_after_undelegation, validators.as_slice())?;
 
         for (index, validator) in validators.iter_mut().enumerate() {
-            let extra_coin = if (index + 1) as u128 <= remaining_coins {
-                1u128
-            } else {
-                0u128
-            };
-            let mut to_undelegate = validator.total_delegated.checked_sub(
-                Uint128::from(coins_per_validator + extra_coin).min(validator.total_delegated),
-            )?;
+            let mut to_undelegate = validator
+                .total_delegated
+                .checked_sub(targets[index].min(validator.total_delegated))?;
             if to_undelegate > undelegation_amount {
                 to_undelegate = undelegation_amount
             }
@@ -100,3 +164,51 @@ pub fn calculate_undelegations(
     }
     Ok(undelegations)
 }
+
+/// Computes the redelegations needed to move the validators toward their `targets`.
+/// A validator can only give away the `redelegatable` part of its surplus.
+/// Returns up to `max_moves` (source index, destination index, amount) moves.
+pub fn calculate_redelegations(
+    validators: &[ValidatorResponse],
+    targets: &[Uint128],
+    redelegatable: &[Uint128],
+    max_moves: usize,
+) -> StdResult<Vec<(usize, usize, Uint128)>> {
+    let mut surpluses: Vec<(usize, Uint128)> = vec![];
+    let mut deficits: Vec<(usize, Uint128)> = vec![];
+    for (index, validator) in validators.iter().enumerate() {
+        if validator.total_delegated > targets[index] {
+            let surplus = validator
+                .total_delegated
+                .checked_sub(targets[index])?
+                .min(redelegatable[index]);
+            if !surplus.is_zero() {
+                surpluses.push((index, surplus));
+            }
+        } else if validator.total_delegated < targets[index] {
+            deficits.push((
+                index,
+                targets[index].checked_sub(validator.total_delegated)?,
+            ));
+        }
+    }
+    surpluses.sort_by_key(|(_, amount)| std::cmp::Reverse(*amount));
+    deficits.sort_by_key(|(_, amount)| std::cmp::Reverse(*amount));
+
+    let mut moves = vec![];
+    let (mut src, mut dst) = (0, 0);
+    while src < surpluses.len() && dst < deficits.len() && moves.len() < max_moves {
+        let amount = surpluses[src].1.min(deficits[dst].1);
+        moves.push((surpluses[src].0, deficits[dst].0, amount));
+
+        surpluses[src].1 = surpluses[src].1.checked_sub(amount)?;
+        deficits[dst].1 = deficits[dst].1.checked_sub(amount)?;
+        if surpluses[src].1.is_zero() {
+            src += 1;
+        }
+        if deficits[dst].1.is_zero() {
+            dst += 1;
+        }
+    }
+    Ok(moves)
+}
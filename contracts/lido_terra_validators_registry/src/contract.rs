@@ -13,22 +13,35 @@
 // limitations under the License.
 
 use std::collections::HashMap;
-use std::convert::TryInto;
 
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 
 use cosmwasm_std::{
-    to_binary, Binary, Coin, CosmosMsg, Deps, DepsMut, Env, MessageInfo, Response, StdError,
-    StdResult, Uint128, WasmMsg,
+    attr, to_binary, Addr, Binary, Coin, CosmosMsg, Decimal, Deps, DepsMut, Env, FullDelegation,
+    MessageInfo, Order, QuerierWrapper, Response, StdError, StdResult, Storage, Uint128, WasmMsg,
 };
+use cw_storage_plus::Bound;
 
-use crate::common::calculate_delegations;
+use crate::common::{calculate_delegations, calculate_redelegations, calculate_target_delegations};
 use crate::msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg};
-use crate::registry::{Config, Validator, ValidatorResponse, CONFIG, REGISTRY};
+use crate::registry::{
+    read_policy, AllocationsResponse, Config, FlaggedValidator, PendingRedelegation, Validator,
+    ValidatorAllocation, ValidatorInfoResponse, ValidatorPolicy, ValidatorResponse,
+    ValidatorsResponse, CONFIG, FLAGGED_VALIDATORS, OLD_CONFIG, PENDING_OWNER,
+    PENDING_REDELEGATIONS, POLICY, REGISTRY,
+};
 use basset::hub::ExecuteMsg::{RedelegateProxy, UpdateGlobalIndex};
+use basset::ownership::{OwnershipProposal, PendingOwnerResponse};
+
+const DEFAULT_MAX_VALIDATORS: u64 = 30;
 
-const MAX_NUMBER_OF_VALIDATORS: u64 = 30;
+// settings for pagination
+const MAX_LIMIT: u32 = 30;
+const DEFAULT_LIMIT: u32 = 10;
+
+const DEFAULT_REBALANCE_MOVES: u32 = 10;
+const MAX_REBALANCE_MOVES: u32 = 30;
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
@@ -37,21 +50,18 @@ pub fn instantiate(
     info: MessageInfo,
     msg: InstantiateMsg,
 ) -> StdResult<Response> {
+    let max_validators = msg.max_validators.unwrap_or(DEFAULT_MAX_VALIDATORS);
+    assert_max_validators(max_validators, msg.registry.len())?;
+
     CONFIG.save(
         deps.storage,
         &Config {
             owner: deps.api.addr_canonicalize(info.sender.as_str())?,
             hub_contract: deps.api.addr_canonicalize(msg.hub_contract.as_str())?,
+            max_validators,
         },
     )?;
 
-    if msg.registry.len() > 30 {
-        return Err(StdError::generic_err(format!(
-            "Can't have more than {} registered validators",
-            MAX_NUMBER_OF_VALIDATORS
-        )));
-    }
-
     for v in msg.registry {
         // We verify the validator is registered as such
         if deps.querier.query_validator(&v.address)?.is_none() {
@@ -59,7 +69,7 @@ pub fn instantiate(
                 "Address not registered as a valid validator",
             ));
         }
-        REGISTRY.save(deps.storage, v.address.as_str().as_bytes(), &v)?;
+        REGISTRY.save(deps.storage, v.address.as_bytes(), &v)?;
     }
 
     Ok(Response::default())
@@ -70,21 +80,36 @@ pub fn execute(deps: DepsMut, env: Env, info: MessageInfo, msg: ExecuteMsg) -> S
     match msg {
         ExecuteMsg::AddValidator { validator } => add_validator(deps, env, info, validator),
         ExecuteMsg::RemoveValidator { address } => remove_validator(deps, env, info, address),
+        ExecuteMsg::UpdateValidator { validator } => update_validator(deps, env, info, validator),
+        ExecuteMsg::Rebalance { max_moves } => rebalance(deps, env, info, max_moves),
+        ExecuteMsg::ProcessPendingRedelegations {} => {
+            process_pending_redelegations(deps, env, info)
+        }
+        ExecuteMsg::UpdatePolicy { policy } => update_policy(deps, env, info, policy),
+        ExecuteMsg::ScreenValidators {} => screen_validators(deps, env, info),
         ExecuteMsg::UpdateConfig {
-            owner,
             hub_contract,
-        } => execute_update_config(deps, env, info, owner, hub_contract),
+            max_validators,
+        } => execute_update_config(deps, env, info, hub_contract, max_validators),
+        ExecuteMsg::ProposeNewOwner {
+            new_owner,
+            expires_in,
+        } => execute_propose_new_owner(deps, env, info, new_owner, expires_in),
+        ExecuteMsg::CancelOwnershipTransfer {} => {
+            execute_cancel_ownership_transfer(deps, env, info)
+        }
+        ExecuteMsg::AcceptOwnership {} => execute_accept_ownership(deps, env, info),
     }
 }
 
-/// Update the config. Update the owner and hub contract address.
+/// Update the config. Update the hub contract address and the max number of validators.
 /// Only creator/owner is allowed to execute
 pub fn execute_update_config(
     deps: DepsMut,
     _env: Env,
     info: MessageInfo,
-    owner: Option<String>,
     hub_contract: Option<String>,
+    max_validators: Option<u64>,
 ) -> StdResult<Response> {
     // only owner must be able to send this message.
     let config = CONFIG.load(deps.storage)?;
@@ -93,20 +118,23 @@ pub fn execute_update_config(
         return Err(StdError::generic_err("unauthorized"));
     }
 
-    if let Some(o) = owner {
-        let owner_raw = deps.api.addr_canonicalize(&o)?;
+    if let Some(hub) = hub_contract {
+        let hub_raw = deps.api.addr_canonicalize(&hub)?;
 
         CONFIG.update(deps.storage, |mut last_config| -> StdResult<_> {
-            last_config.owner = owner_raw;
+            last_config.hub_contract = hub_raw;
             Ok(last_config)
         })?;
     }
 
-    if let Some(hub) = hub_contract {
-        let hub_raw = deps.api.addr_canonicalize(&hub)?;
+    if let Some(max) = max_validators {
+        let registered = REGISTRY
+            .keys(deps.storage, None, None, Order::Ascending)
+            .count();
+        assert_max_validators(max, registered)?;
 
         CONFIG.update(deps.storage, |mut last_config| -> StdResult<_> {
-            last_config.hub_contract = hub_raw;
+            last_config.max_validators = max;
             Ok(last_config)
         })?;
     }
@@ -114,6 +142,76 @@ pub fn execute_update_config(
     Ok(Response::default())
 }
 
+/// Propose a new owner. The ownership transfer only happens once the new owner accepts it.
+/// Only creator/owner is allowed to execute
+pub fn execute_propose_new_owner(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    new_owner: String,
+    expires_in: Option<u64>,
+) -> StdResult<Response> {
+    let config = CONFIG.load(deps.storage)?;
+    let owner_address = deps.api.addr_humanize(&config.owner)?;
+    if info.sender != owner_address {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+
+    PENDING_OWNER.save(
+        deps.storage,
+        &OwnershipProposal {
+            owner: deps.api.addr_validate(&new_owner)?,
+            expiry: OwnershipProposal::expiry(env.block.time.seconds(), expires_in)?,
+        },
+    )?;
+
+    Ok(Response::default())
+}
+
+/// Cancel the pending ownership transfer.
+/// Only creator/owner is allowed to execute
+pub fn execute_cancel_ownership_transfer(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+) -> StdResult<Response> {
+    let config = CONFIG.load(deps.storage)?;
+    let owner_address = deps.api.addr_humanize(&config.owner)?;
+    if info.sender != owner_address {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+
+    PENDING_OWNER.remove(deps.storage);
+
+    Ok(Response::default())
+}
+
+/// Accept the pending ownership transfer.
+/// Only the proposed owner is allowed to execute
+pub fn execute_accept_ownership(deps: DepsMut, env: Env, info: MessageInfo) -> StdResult<Response> {
+    let proposal = PENDING_OWNER
+        .may_load(deps.storage)?
+        .ok_or_else(|| StdError::generic_err("There is no pending ownership transfer"))?;
+
+    if info.sender != proposal.owner {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+    if proposal.is_expired(env.block.time.seconds()) {
+        return Err(StdError::generic_err(
+            "The ownership transfer proposal has expired",
+        ));
+    }
+
+    let owner_raw = deps.api.addr_canonicalize(proposal.owner.as_str())?;
+    CONFIG.update(deps.storage, |mut last_config| -> StdResult<_> {
+        last_config.owner = owner_raw;
+        Ok(last_config)
+    })?;
+    PENDING_OWNER.remove(deps.storage);
+
+    Ok(Response::default())
+}
+
 pub fn add_validator(
     deps: DepsMut,
     _env: Env,
@@ -127,37 +225,71 @@ pub fn add_validator(
         return Err(StdError::generic_err("unauthorized"));
     }
     // We verify the validator is registered as such
-    if deps.querier.query_validator(&validator.address)?.is_none() {
+    let cosmos_validator = deps.querier.query_validator(&validator.address)?;
+    if cosmos_validator.is_none() {
         return Err(StdError::generic_err(
             "Address not registered as a valid validator",
         ));
     }
 
-    // We verify the number of registered validators is not above MAX_NUMBER_OF_VALIDATORS
-    let validators = query_validators(deps.as_ref())?;
-    if validators.len()
-        >= MAX_NUMBER_OF_VALIDATORS
-            .try_into()
-            .map_err(|_| StdError::generic_err("Error parsing u64 to usize"))?
-    {
+    // We verify the validator conforms to the validator policy
+    let active = deps
+        .querier
+        .query_all_validators()?
+        .iter()
+        .any(|v| v.address == validator.address);
+    if let Some(reason) = read_policy(deps.storage)?.violation(cosmos_validator.as_ref(), active) {
         return Err(StdError::generic_err(format!(
-            "Can't have more than {} registered validators",
-            MAX_NUMBER_OF_VALIDATORS
+            "Validator doesn't conform to the validator policy: {}",
+            reason
         )));
     }
 
-    REGISTRY.save(
-        deps.storage,
-        validator.address.as_str().as_bytes(),
-        &validator,
-    )?;
+    // We verify the number of registered validators is not above the max number of validators.
+    // Updating a registered validator doesn't add one
+    let mut registered = REGISTRY
+        .keys(deps.storage, None, None, Order::Ascending)
+        .count();
+    if !REGISTRY.has(deps.storage, validator.address.as_bytes()) {
+        registered += 1;
+    }
+    assert_max_validators(config.max_validators, registered)?;
+
+    REGISTRY.save(deps.storage, validator.address.as_bytes(), &validator)?;
+    // the stake of a validator registered again doesn't have to be moved away anymore
+    PENDING_REDELEGATIONS.remove(deps.storage, validator.address.as_bytes());
+    FLAGGED_VALIDATORS.remove(deps.storage, validator.address.as_bytes());
     Ok(Response::default())
 }
 
-pub fn remove_validator(
+/// Update the weight and max cap of a registered validator.
+/// Only creator/owner is allowed to execute
+pub fn update_validator(
     deps: DepsMut,
     _env: Env,
     info: MessageInfo,
+    validator: Validator,
+) -> StdResult<Response> {
+    let config = CONFIG.load(deps.storage)?;
+    let owner_address = deps.api.addr_humanize(&config.owner)?;
+    if info.sender != owner_address {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+
+    if !REGISTRY.has(deps.storage, validator.address.as_bytes()) {
+        return Err(StdError::generic_err(
+            "Validator is not registered in the registry",
+        ));
+    }
+
+    REGISTRY.save(deps.storage, validator.address.as_bytes(), &validator)?;
+    Ok(Response::default())
+}
+
+pub fn remove_validator(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
     validator_address: String,
 ) -> StdResult<Response> {
     let config = CONFIG.load(deps.storage)?;
@@ -166,7 +298,8 @@ pub fn remove_validator(
         return Err(StdError::generic_err("unauthorized"));
     }
 
-    REGISTRY.remove(deps.storage, validator_address.as_str().as_bytes());
+    REGISTRY.remove(deps.storage, validator_address.as_bytes());
+    FLAGGED_VALIDATORS.remove(deps.storage, validator_address.as_bytes());
 
     let mut validators = query_validators(deps.as_ref())?;
     if validators.is_empty() {
@@ -174,62 +307,373 @@ pub fn remove_validator(
             "Cannot remove the last validator in the registry",
         ));
     }
-    validators.sort_by(|v1, v2| v1.total_delegated.cmp(&v2.total_delegated));
+    validators.sort_by_key(|v1| v1.total_delegated);
+
+    let hub_address = deps.api.addr_humanize(&config.hub_contract)?;
+
+    let mut messages: Vec<CosmosMsg> = vec![];
+    move_stake(
+        deps.storage,
+        &deps.querier,
+        &env,
+        &hub_address,
+        validator_address,
+        validators.as_mut_slice(),
+        &mut messages,
+    )?;
+    if !messages.is_empty() {
+        messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: hub_address.into_string(),
+            msg: to_binary(&UpdateGlobalIndex {})?,
+            funds: vec![],
+        }));
+    }
 
+    let res = Response::new().add_messages(messages);
+    Ok(res)
+}
+
+/// Update the validator policy.
+/// Only creator/owner is allowed to execute
+pub fn update_policy(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    policy: ValidatorPolicy,
+) -> StdResult<Response> {
+    let config = CONFIG.load(deps.storage)?;
+    let owner_address = deps.api.addr_humanize(&config.owner)?;
+    if info.sender != owner_address {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+
+    POLICY.save(deps.storage, &policy)?;
+    Ok(Response::default())
+}
+
+/// Check the registered validators against the validator policy. The offenders are flagged,
+/// which excludes them from delegations, and their stake is moved to the other validators.
+/// Validators conforming to the policy again are unflagged.
+/// Anyone is allowed to execute
+pub fn screen_validators(deps: DepsMut, env: Env, _info: MessageInfo) -> StdResult<Response> {
+    let config = CONFIG.load(deps.storage)?;
     let hub_address = deps.api.addr_humanize(&config.hub_contract)?;
+    let policy = read_policy(deps.storage)?;
 
-    let query = deps
+    let active_validators: Vec<String> = deps
         .querier
-        .query_delegation(hub_address.clone(), validator_address.clone());
+        .query_all_validators()?
+        .into_iter()
+        .map(|v| v.address)
+        .collect();
+
+    let registered = REGISTRY
+        .range(deps.storage, None, None, cosmwasm_std::Order::Ascending)
+        .map(|item| item.map(|(_, v)| v.address))
+        .collect::<StdResult<Vec<String>>>()?;
+
+    let mut attrs = vec![attr("action", "screen_validators")];
+    let mut offenders: Vec<String> = vec![];
+    for address in registered {
+        let validator = deps.querier.query_validator(&address)?;
+        let violation = policy.violation(validator.as_ref(), active_validators.contains(&address));
+        let flagged = FLAGGED_VALIDATORS.has(deps.storage, address.as_bytes());
+        match violation {
+            Some(reason) if !flagged => {
+                attrs.push(attr("flagged", format!("{}:{}", address, reason)));
+                FLAGGED_VALIDATORS.save(deps.storage, address.as_bytes(), &reason)?;
+                offenders.push(address);
+            }
+            None if flagged => {
+                attrs.push(attr("unflagged", address.clone()));
+                FLAGGED_VALIDATORS.remove(deps.storage, address.as_bytes());
+                PENDING_REDELEGATIONS.remove(deps.storage, address.as_bytes());
+            }
+            _ => {}
+        }
+    }
 
     let mut messages: Vec<CosmosMsg> = vec![];
-    if let Ok(q) = query {
-        let delegated_amount = q;
-
-        let mut redelegations: Vec<(String, Coin)> = vec![];
-        if let Some(delegation) = delegated_amount {
-            // Terra core returns zero if there is another active redelegation
-            // That means we cannot start a new redelegation, so we only remove a validator from
-            // the registry.
-            // We'll do a redelegation manually later by sending RedelegateProxy to the hub
-            if delegation.can_redelegate.amount < delegation.amount.amount {
-                return StdResult::Ok(Response::new());
-            }
+    if !offenders.is_empty() {
+        let mut validators = query_validators(deps.as_ref())?;
+        if validators.is_empty() {
+            return Err(StdError::generic_err(
+                "No validator conforms to the validator policy",
+            ));
+        }
+        validators.sort_by_key(|v1| v1.total_delegated);
+
+        for address in offenders {
+            move_stake(
+                deps.storage,
+                &deps.querier,
+                &env,
+                &hub_address,
+                address,
+                validators.as_mut_slice(),
+                &mut messages,
+            )?;
+        }
+    }
+    if !messages.is_empty() {
+        messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: hub_address.into_string(),
+            msg: to_binary(&UpdateGlobalIndex {})?,
+            funds: vec![],
+        }));
+    }
 
-            let (_, delegations) =
-                calculate_delegations(delegation.amount.amount, validators.as_slice())?;
-
-            for i in 0..delegations.len() {
-                if delegations[i].is_zero() {
-                    continue;
-                }
-                redelegations.push((
-                    validators[i].address.clone(),
-                    Coin::new(delegations[i].u128(), delegation.amount.denom.as_str()),
-                ));
+    Ok(Response::new().add_messages(messages).add_attributes(attrs))
+}
+
+/// Redelegates the stake of `validator_address` across `validators`. The part that can't be
+/// redelegated yet is recorded to be moved later with ProcessPendingRedelegations.
+fn move_stake(
+    storage: &mut dyn Storage,
+    querier: &QuerierWrapper,
+    env: &Env,
+    hub_address: &Addr,
+    validator_address: String,
+    validators: &mut [ValidatorResponse],
+    messages: &mut Vec<CosmosMsg>,
+) -> StdResult<()> {
+    if let Some(delegation) = query_full_delegation(querier, hub_address, &validator_address)? {
+        // Terra core returns zero if there is another active redelegation.
+        // That means we can only redelegate a part of the stake (if any) now, the rest is
+        // recorded and can be moved later with ProcessPendingRedelegations
+        let redelegated = redelegate(
+            messages,
+            hub_address,
+            validator_address.clone(),
+            delegation.can_redelegate,
+            validators,
+        )?;
+
+        let remaining = delegation.amount.amount.checked_sub(redelegated)?;
+        if !remaining.is_zero() {
+            PENDING_REDELEGATIONS.save(
+                storage,
+                validator_address.as_bytes(),
+                &PendingRedelegation {
+                    src_validator: validator_address.clone(),
+                    amount: Coin::new(remaining.u128(), delegation.amount.denom.as_str()),
+                    queued_at: env.block.time.seconds(),
+                },
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Queries the delegation of the hub to `validator_address`, with its redelegatable part.
+/// query_delegation() fails on terra/core when there is no delegation pair (see
+/// query_validators), so the delegation is looked up with query_all_delegations() first
+fn query_full_delegation(
+    querier: &QuerierWrapper,
+    hub_address: &Addr,
+    validator_address: &str,
+) -> StdResult<Option<FullDelegation>> {
+    let delegated = querier
+        .query_all_delegations(hub_address.clone())?
+        .iter()
+        .any(|delegation| delegation.validator == validator_address);
+    if !delegated {
+        return Ok(None);
+    }
+    querier.query_delegation(hub_address.clone(), validator_address)
+}
+
+/// Retry the redelegations recorded when a validator was removed while its stake could not
+/// be fully redelegated. Anyone is allowed to execute
+pub fn process_pending_redelegations(
+    deps: DepsMut,
+    _env: Env,
+    _info: MessageInfo,
+) -> StdResult<Response> {
+    let config = CONFIG.load(deps.storage)?;
+    let hub_address = deps.api.addr_humanize(&config.hub_contract)?;
+
+    let pending = PENDING_REDELEGATIONS
+        .range(deps.storage, None, None, cosmwasm_std::Order::Ascending)
+        .map(|item| item.map(|(_, v)| v))
+        .collect::<StdResult<Vec<PendingRedelegation>>>()?;
+
+    let mut validators = query_validators(deps.as_ref())?;
+    validators.sort_by_key(|v1| v1.total_delegated);
+
+    let mut messages: Vec<CosmosMsg> = vec![];
+    let mut attrs = vec![attr("action", "process_pending_redelegations")];
+    for mut pending_redelegation in pending {
+        let key = pending_redelegation.src_validator.clone();
+        let delegation = query_full_delegation(&deps.querier, &hub_address, &key)?;
+
+        // the stake may have been moved away already
+        let delegation = match delegation {
+            Some(delegation) if !delegation.amount.amount.is_zero() => delegation,
+            _ => {
+                PENDING_REDELEGATIONS.remove(deps.storage, key.as_bytes());
+                continue;
             }
+        };
 
-            let regelegate_msg = RedelegateProxy {
-                src_validator: validator_address,
-                redelegations,
-            };
-            messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
-                contract_addr: hub_address.clone().into_string(),
-                msg: to_binary(&regelegate_msg)?,
-                funds: vec![],
-            }));
-
-            let msg = UpdateGlobalIndex {};
-            messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
-                contract_addr: hub_address.into_string(),
-                msg: to_binary(&msg)?,
-                funds: vec![],
-            }));
+        let redelegated = redelegate(
+            &mut messages,
+            &hub_address,
+            key.clone(),
+            delegation.can_redelegate,
+            validators.as_mut_slice(),
+        )?;
+        if !redelegated.is_zero() {
+            attrs.push(attr("redelegation", format!("{}:{}", key, redelegated)));
+        }
+
+        let remaining = delegation.amount.amount.checked_sub(redelegated)?;
+        if remaining.is_zero() {
+            PENDING_REDELEGATIONS.remove(deps.storage, key.as_bytes());
+        } else {
+            pending_redelegation.amount =
+                Coin::new(remaining.u128(), delegation.amount.denom.as_str());
+            PENDING_REDELEGATIONS.save(deps.storage, key.as_bytes(), &pending_redelegation)?;
         }
     }
 
-    let res = Response::new().add_messages(messages);
-    Ok(res)
+    if !messages.is_empty() {
+        messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: hub_address.into_string(),
+            msg: to_binary(&UpdateGlobalIndex {})?,
+            funds: vec![],
+        }));
+    }
+
+    Ok(Response::new().add_messages(messages).add_attributes(attrs))
+}
+
+/// Pushes a RedelegateProxy message moving `amount` from `src_validator` across the registered
+/// validators and returns the amount actually redelegated.
+fn redelegate(
+    messages: &mut Vec<CosmosMsg>,
+    hub_address: &Addr,
+    src_validator: String,
+    amount: Coin,
+    validators: &mut [ValidatorResponse],
+) -> StdResult<Uint128> {
+    if amount.amount.is_zero() {
+        return Ok(Uint128::zero());
+    }
+
+    let (remaining, delegations) = calculate_delegations(amount.amount, validators)?;
+
+    let mut redelegations: Vec<(String, Coin)> = vec![];
+    for i in 0..delegations.len() {
+        if delegations[i].is_zero() {
+            continue;
+        }
+        validators[i].total_delegated += delegations[i];
+        redelegations.push((
+            validators[i].address.clone(),
+            Coin::new(delegations[i].u128(), amount.denom.as_str()),
+        ));
+    }
+    if redelegations.is_empty() {
+        return Ok(Uint128::zero());
+    }
+
+    let regelegate_msg = RedelegateProxy {
+        src_validator,
+        redelegations,
+    };
+    messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: hub_address.to_string(),
+        msg: to_binary(&regelegate_msg)?,
+        funds: vec![],
+    }));
+
+    Ok(amount.amount.checked_sub(remaining)?)
+}
+
+/// Redelegate the stake of the validators above their target delegation to the validators
+/// below it. Only the redelegatable part of a delegation can be moved.
+/// Anyone is allowed to execute
+pub fn rebalance(
+    deps: DepsMut,
+    _env: Env,
+    _info: MessageInfo,
+    max_moves: Option<u32>,
+) -> StdResult<Response> {
+    let config = CONFIG.load(deps.storage)?;
+    let hub_address = deps.api.addr_humanize(&config.hub_contract)?;
+    let max_moves = max_moves
+        .unwrap_or(DEFAULT_REBALANCE_MOVES)
+        .min(MAX_REBALANCE_MOVES) as usize;
+
+    let validators = query_validators(deps.as_ref())?;
+    let total_delegated: Uint128 = validators.iter().map(|v| v.total_delegated).sum();
+    let targets = calculate_target_delegations(total_delegated, validators.as_slice())?;
+
+    let mut denom = String::new();
+    let mut redelegatable = vec![Uint128::zero(); validators.len()];
+    for (index, validator) in validators.iter().enumerate() {
+        // only the validators above their target can be a source of a redelegation
+        if validator.total_delegated <= targets[index] {
+            continue;
+        }
+        if let Some(delegation) = deps
+            .querier
+            .query_delegation(hub_address.clone(), validator.address.clone())?
+        {
+            redelegatable[index] = delegation.can_redelegate.amount;
+            denom = delegation.amount.denom;
+        }
+    }
+
+    let moves = calculate_redelegations(
+        validators.as_slice(),
+        targets.as_slice(),
+        redelegatable.as_slice(),
+        max_moves,
+    )?;
+
+    // group the moves by source validator, one RedelegateProxy per source
+    let mut redelegations: Vec<(String, Vec<(String, Coin)>)> = vec![];
+    let mut attrs = vec![
+        attr("action", "rebalance"),
+        attr("moves", moves.len().to_string()),
+    ];
+    for (src, dst, amount) in moves {
+        let src_validator = validators[src].address.clone();
+        let dst_validator = validators[dst].address.clone();
+        attrs.push(attr(
+            "redelegation",
+            format!("{}:{}:{}", src_validator, dst_validator, amount),
+        ));
+
+        let redelegation = (dst_validator, Coin::new(amount.u128(), denom.as_str()));
+        match redelegations.iter_mut().find(|(s, _)| *s == src_validator) {
+            Some((_, r)) => r.push(redelegation),
+            None => redelegations.push((src_validator, vec![redelegation])),
+        }
+    }
+
+    let mut messages: Vec<CosmosMsg> = vec![];
+    for (src_validator, redelegations) in redelegations {
+        messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: hub_address.to_string(),
+            msg: to_binary(&RedelegateProxy {
+                src_validator,
+                redelegations,
+            })?,
+            funds: vec![],
+        }));
+    }
+    if !messages.is_empty() {
+        messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: hub_address.into_string(),
+            msg: to_binary(&UpdateGlobalIndex {})?,
+            funds: vec![],
+        }));
+    }
+
+    Ok(Response::new().add_messages(messages).add_attributes(attrs))
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
@@ -237,10 +681,19 @@ pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
         QueryMsg::GetValidatorsForDelegation {} => {
             let mut validators = query_validators(deps)?;
-            validators.sort_by(|v1, v2| v1.total_delegated.cmp(&v2.total_delegated));
+            validators.sort_by_key(|v| v.total_delegated);
             to_binary(&validators)
         }
         QueryMsg::Config {} => to_binary(&query_config(deps)?),
+        QueryMsg::PendingOwner {} => to_binary(&query_pending_owner(deps)?),
+        QueryMsg::Allocations {} => to_binary(&query_allocations(deps)?),
+        QueryMsg::PendingRedelegations {} => to_binary(&query_pending_redelegations(deps)?),
+        QueryMsg::Policy {} => to_binary(&read_policy(deps.storage)?),
+        QueryMsg::FlaggedValidators {} => to_binary(&query_flagged_validators(deps)?),
+        QueryMsg::Validators { start_after, limit } => {
+            to_binary(&query_validators_info(deps, start_after, limit)?)
+        }
+        QueryMsg::Validator { address } => to_binary(&query_validator_info(deps, address)?),
     }
 }
 
@@ -249,6 +702,156 @@ fn query_config(deps: Deps) -> StdResult<Config> {
     Ok(config)
 }
 
+fn query_pending_owner(deps: Deps) -> StdResult<PendingOwnerResponse> {
+    Ok(PENDING_OWNER.may_load(deps.storage)?.into())
+}
+
+fn query_validators_info(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<ValidatorsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(|s| Bound::ExclusiveRaw(s.into_bytes()));
+
+    let registered = REGISTRY
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, v)| v))
+        .collect::<StdResult<Vec<Validator>>>()?;
+
+    let context = StakingContext::load(deps)?;
+    let validators = registered
+        .into_iter()
+        .map(|v| context.validator_info(deps, v))
+        .collect::<StdResult<Vec<ValidatorInfoResponse>>>()?;
+    Ok(ValidatorsResponse { validators })
+}
+
+fn query_validator_info(deps: Deps, address: String) -> StdResult<ValidatorInfoResponse> {
+    let registered = REGISTRY
+        .may_load(deps.storage, address.as_bytes())?
+        .ok_or_else(|| StdError::generic_err("Validator is not registered in the registry"))?;
+
+    StakingContext::load(deps)?.validator_info(deps, registered)
+}
+
+/// Staking data shared by the validator info queries
+struct StakingContext {
+    delegations: HashMap<String, Uint128>,
+    total_delegated: Uint128,
+    active_validators: HashMap<String, Decimal>,
+}
+
+impl StakingContext {
+    fn load(deps: Deps) -> StdResult<Self> {
+        let config = CONFIG.load(deps.storage)?;
+        let hub_address = deps.api.addr_humanize(&config.hub_contract)?;
+
+        let mut delegations = HashMap::new();
+        for delegation in deps.querier.query_all_delegations(hub_address)? {
+            delegations.insert(delegation.validator, delegation.amount.amount);
+        }
+        let total_delegated = delegations.values().sum();
+        let active_validators = deps
+            .querier
+            .query_all_validators()?
+            .into_iter()
+            .map(|v| (v.address, v.commission))
+            .collect();
+
+        Ok(StakingContext {
+            delegations,
+            total_delegated,
+            active_validators,
+        })
+    }
+
+    fn validator_info(&self, deps: Deps, validator: Validator) -> StdResult<ValidatorInfoResponse> {
+        let total_delegated = *self
+            .delegations
+            .get(&validator.address)
+            .unwrap_or(&Uint128::zero());
+        let stake_share = if self.total_delegated.is_zero() {
+            Decimal::zero()
+        } else {
+            Decimal::from_ratio(total_delegated, self.total_delegated)
+        };
+
+        // validators out of the active set are only returned by the single validator query
+        let commission = match self.active_validators.get(&validator.address) {
+            Some(commission) => Some(*commission),
+            None => deps
+                .querier
+                .query_validator(&validator.address)?
+                .map(|v| v.commission),
+        };
+        let in_active_set = self.active_validators.contains_key(&validator.address);
+        let flagged_reason =
+            FLAGGED_VALIDATORS.may_load(deps.storage, validator.address.as_bytes())?;
+
+        let weight = validator.effective_weight();
+        let below_max_cap = match validator.max_cap {
+            Some(cap) => total_delegated < cap,
+            None => true,
+        };
+        let accepts_delegations = flagged_reason.is_none() && weight > 0 && below_max_cap;
+
+        Ok(ValidatorInfoResponse {
+            address: validator.address,
+            weight,
+            max_cap: validator.max_cap,
+            total_delegated,
+            stake_share,
+            commission,
+            in_active_set,
+            flagged_reason,
+            accepts_delegations,
+        })
+    }
+}
+
+fn query_flagged_validators(deps: Deps) -> StdResult<Vec<FlaggedValidator>> {
+    FLAGGED_VALIDATORS
+        .range(deps.storage, None, None, cosmwasm_std::Order::Ascending)
+        .map(|item| {
+            let (address, reason) = item?;
+            Ok(FlaggedValidator {
+                address: String::from_utf8(address)?,
+                reason,
+            })
+        })
+        .collect()
+}
+
+fn query_pending_redelegations(deps: Deps) -> StdResult<Vec<PendingRedelegation>> {
+    PENDING_REDELEGATIONS
+        .range(deps.storage, None, None, cosmwasm_std::Order::Ascending)
+        .map(|item| item.map(|(_, v)| v))
+        .collect()
+}
+
+fn query_allocations(deps: Deps) -> StdResult<AllocationsResponse> {
+    let validators = query_validators(deps)?;
+    let total_delegated: Uint128 = validators.iter().map(|v| v.total_delegated).sum();
+    let targets = calculate_target_delegations(total_delegated, validators.as_slice())?;
+
+    Ok(AllocationsResponse {
+        total_delegated,
+        validators: validators
+            .into_iter()
+            .zip(targets)
+            .map(|(v, target_delegated)| ValidatorAllocation {
+                address: v.address,
+                weight: v.weight,
+                max_cap: v.max_cap,
+                target_delegated,
+                total_delegated: v.total_delegated,
+            })
+            .collect(),
+    })
+}
+
 fn query_validators(deps: Deps) -> StdResult<Vec<ValidatorResponse>> {
     let config = CONFIG.load(deps.storage)?;
     let hub_address = deps.api.addr_humanize(&config.hub_contract)?;
@@ -260,9 +863,16 @@ fn query_validators(deps: Deps) -> StdResult<Vec<ValidatorResponse>> {
 
     let mut validators: Vec<ValidatorResponse> = vec![];
     for item in REGISTRY.range(deps.storage, None, None, cosmwasm_std::Order::Ascending) {
+        let registered = item?.1;
+        // validators not conforming to the validator policy don't receive delegations
+        if FLAGGED_VALIDATORS.has(deps.storage, registered.address.as_bytes()) {
+            continue;
+        }
         let mut validator = ValidatorResponse {
             total_delegated: Default::default(),
-            address: item?.1.address,
+            weight: registered.effective_weight(),
+            max_cap: registered.max_cap,
+            address: registered.address,
         };
         // There is a bug in terra/core.
         // The bug happens when we do query_delegation() but there are no delegation pair (delegator-validator)
@@ -279,7 +889,41 @@ fn query_validators(deps: Deps) -> StdResult<Vec<ValidatorResponse>> {
     Ok(validators)
 }
 
+/// Checks that `registered` validators fit in the max number of validators
+fn assert_max_validators(max_validators: u64, registered: usize) -> StdResult<()> {
+    if max_validators == 0 {
+        return Err(StdError::generic_err(
+            "The max number of validators must be greater than zero",
+        ));
+    }
+    if registered as u64 > max_validators {
+        return Err(StdError::generic_err(format!(
+            "Can't have more than {} registered validators",
+            max_validators
+        )));
+    }
+    Ok(())
+}
+
+/// The max number of validators used to be a constant, so it is added to the stored config.
+/// `MigrateMsg.max_validators` defaults to the former constant of 30.
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn migrate(_deps: DepsMut, _env: Env, _msg: MigrateMsg) -> StdResult<Response> {
+pub fn migrate(deps: DepsMut, _env: Env, msg: MigrateMsg) -> StdResult<Response> {
+    let old_config = OLD_CONFIG.load(deps.storage)?;
+
+    let max_validators = msg.max_validators.unwrap_or(DEFAULT_MAX_VALIDATORS);
+    let registered = REGISTRY
+        .keys(deps.storage, None, None, Order::Ascending)
+        .count();
+    assert_max_validators(max_validators, registered)?;
+
+    CONFIG.save(
+        deps.storage,
+        &Config {
+            owner: old_config.owner,
+            hub_contract: old_config.hub_contract,
+            max_validators,
+        },
+    )?;
     Ok(Response::default())
 }
@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::registry::Validator;
+use crate::registry::{Validator, ValidatorPolicy};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
@@ -20,6 +20,8 @@ use serde::{Deserialize, Serialize};
 pub struct InstantiateMsg {
     pub registry: Vec<Validator>,
     pub hub_contract: String,
+    /// Maximum number of registered validators, 30 if not set
+    pub max_validators: Option<u64>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -31,11 +33,42 @@ pub enum ExecuteMsg {
     /// Remove a validator from the registry
     RemoveValidator { address: String },
 
+    /// Update the weight and max cap of a registered validator
+    UpdateValidator { validator: Validator },
+
+    /// Redelegate stake from the validators above their target delegation to the ones below it.
+    /// Anyone can execute it. `max_moves` bounds the number of redelegations per call.
+    Rebalance { max_moves: Option<u32> },
+
+    /// Redelegate the stake left on removed validators that had an active redelegation.
+    /// Anyone can execute it.
+    ProcessPendingRedelegations {},
+
+    /// Update the requirements validators must meet to receive delegations
+    UpdatePolicy { policy: ValidatorPolicy },
+
+    /// Flag the registered validators not conforming to the validator policy and move their
+    /// stake away. Anyone can execute it.
+    ScreenValidators {},
+
     /// Update config
     UpdateConfig {
-        owner: Option<String>,
         hub_contract: Option<String>,
+        max_validators: Option<u64>,
+    },
+
+    /// Propose a new owner. The ownership is only transferred once the new owner accepts it.
+    /// `expires_in` is the number of seconds the proposal stays valid for.
+    ProposeNewOwner {
+        new_owner: String,
+        expires_in: Option<u64>,
     },
+
+    /// Cancel the pending ownership transfer
+    CancelOwnershipTransfer {},
+
+    /// Accept the pending ownership transfer. Only the proposed owner can execute it.
+    AcceptOwnership {},
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -44,7 +77,27 @@ pub enum QueryMsg {
     // GetValidatorsForDelegation returns validators sorted by available amount for delegation (delegation_limit - total_delegated)
     GetValidatorsForDelegation {},
     Config {},
+    PendingOwner {},
+    // Allocations returns the target delegation of every registered validator next to its actual delegation
+    Allocations {},
+    // PendingRedelegations returns the removed validators whose stake is still to be redelegated
+    PendingRedelegations {},
+    Policy {},
+    // FlaggedValidators returns the validators excluded from delegations by the validator policy
+    FlaggedValidators {},
+    // Validators returns the registered validators with their staking details, paginated by address
+    Validators {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    // Validator returns the staking details of a registered validator
+    Validator {
+        address: String,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
-pub struct MigrateMsg {}
+pub struct MigrateMsg {
+    /// Maximum number of registered validators, 30 if not set
+    pub max_validators: Option<u64>,
+}
@@ -15,23 +15,68 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::CanonicalAddr;
+use basset::ownership::OwnershipProposal;
 use cosmwasm_std::Uint128;
+use cosmwasm_std::{
+    CanonicalAddr, Coin, Decimal, StdResult, Storage, Validator as CosmosValidator,
+};
 use cw_storage_plus::{Item, Map};
 
 pub static CONFIG: Item<Config> = Item::new("config");
 
+/// Config stored before `max_validators` was added, read by `migrate`
+pub static OLD_CONFIG: Item<OldConfig> = Item::new("config");
+
+pub static PENDING_OWNER: Item<OwnershipProposal> = Item::new("pending_owner");
+
 pub static REGISTRY: Map<&[u8], Validator> = Map::new("validators_registry");
 
+pub static POLICY: Item<ValidatorPolicy> = Item::new("validator_policy");
+
+/// Flagged validators with the reason they don't conform to the validator policy
+pub static FLAGGED_VALIDATORS: Map<&[u8], String> = Map::new("flagged_validators");
+
+pub static PENDING_REDELEGATIONS: Map<&[u8], PendingRedelegation> =
+    Map::new("pending_redelegations");
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Config {
     pub owner: CanonicalAddr,
     pub hub_contract: CanonicalAddr,
+    pub max_validators: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OldConfig {
+    pub owner: CanonicalAddr,
+    pub hub_contract: CanonicalAddr,
+}
+
+/// Weight of a validator registered without an explicit one
+pub const DEFAULT_WEIGHT: u64 = 1;
+
+fn default_weight() -> u64 {
+    DEFAULT_WEIGHT
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Validator {
     pub address: String,
+
+    /// Relative share of the total stake the validator should receive.
+    /// Defaults to `DEFAULT_WEIGHT` when not set.
+    #[serde(default)]
+    pub weight: Option<u64>,
+
+    /// Maximum amount that can be delegated to the validator
+    #[serde(default)]
+    pub max_cap: Option<Uint128>,
+}
+
+impl Validator {
+    pub fn effective_weight(&self) -> u64 {
+        self.weight.unwrap_or(DEFAULT_WEIGHT)
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -40,4 +85,111 @@ pub struct ValidatorResponse {
     pub total_delegated: Uint128,
 
     pub address: String,
+
+    #[serde(default = "default_weight")]
+    pub weight: u64,
+
+    #[serde(default)]
+    pub max_cap: Option<Uint128>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ValidatorAllocation {
+    pub address: String,
+    pub weight: u64,
+    pub max_cap: Option<Uint128>,
+
+    /// Amount the validator should have delegated according to its weight
+    pub target_delegated: Uint128,
+
+    /// Amount currently delegated to the validator
+    pub total_delegated: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AllocationsResponse {
+    pub total_delegated: Uint128,
+    pub validators: Vec<ValidatorAllocation>,
+}
+
+/// Stake left on a removed validator that couldn't be redelegated yet
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingRedelegation {
+    pub src_validator: String,
+
+    /// Amount still delegated to the validator when last checked
+    pub amount: Coin,
+
+    /// Time the validator was removed, in seconds
+    pub queued_at: u64,
+}
+
+/// Requirements a validator must meet to receive delegations
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct ValidatorPolicy {
+    /// Maximum commission rate of a validator
+    pub max_commission: Option<Decimal>,
+
+    /// Whether validators out of the active set (jailed, tombstoned or unbonded) can receive
+    /// delegations. The staking querier doesn't expose the uptime of a validator, but a
+    /// validator jailed for downtime leaves the active set.
+    pub allow_jailed: bool,
+}
+
+impl ValidatorPolicy {
+    /// Returns the reason the validator doesn't conform to the policy, if any
+    pub fn violation(&self, validator: Option<&CosmosValidator>, active: bool) -> Option<String> {
+        let validator = match validator {
+            Some(v) => v,
+            None => return Some("not_found".to_string()),
+        };
+        if !active && !self.allow_jailed {
+            return Some("inactive".to_string());
+        }
+        if let Some(max_commission) = self.max_commission {
+            if validator.commission > max_commission {
+                return Some("commission".to_string());
+            }
+        }
+        None
+    }
+}
+
+pub fn read_policy(storage: &dyn Storage) -> StdResult<ValidatorPolicy> {
+    Ok(POLICY.may_load(storage)?.unwrap_or_default())
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FlaggedValidator {
+    pub address: String,
+    pub reason: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ValidatorInfoResponse {
+    pub address: String,
+    pub weight: u64,
+    pub max_cap: Option<Uint128>,
+    pub total_delegated: Uint128,
+
+    /// Share of the total stake delegated to the validator
+    pub stake_share: Decimal,
+
+    /// Commission rate, `None` if the validator is unknown to the staking module
+    pub commission: Option<Decimal>,
+
+    /// Whether the validator is in the active set of the staking module. Validators out of it
+    /// (jailed, tombstoned or unbonded) earn no staking rewards
+    pub in_active_set: bool,
+
+    /// Reason the validator doesn't conform to the validator policy, if flagged
+    pub flagged_reason: Option<String>,
+
+    /// Whether the validator currently receives new delegations
+    pub accepts_delegations: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ValidatorsResponse {
+    pub validators: Vec<ValidatorInfoResponse>,
 }
@@ -42,6 +42,7 @@ pub fn mock_dependencies(
 pub struct WasmMockQuerier {
     base: MockQuerier,
     validators: Vec<RegistryValidator>,
+    staking_validators: Vec<Validator>,
 }
 
 impl Querier for WasmMockQuerier {
@@ -68,17 +69,24 @@ impl WasmMockQuerier {
                 msg: _,
             }) => {
                 let mut validators = self.validators.clone();
-                validators.sort_by(|v1, v2| v1.total_delegated.cmp(&v2.total_delegated));
+                validators.sort_by_key(|v| v.total_delegated);
                 QuerierResult::Ok(ContractResult::from(to_binary(&validators)))
             }
             QueryRequest::Staking(StakingQuery::Validator { address }) => {
-                QuerierResult::Ok(ContractResult::from(to_binary(&ValidatorResponse {
-                    validator: Some(Validator {
+                // validators out of the active set are still returned by the staking module
+                let validator = self
+                    .staking_validators
+                    .iter()
+                    .find(|v| &v.address == address)
+                    .cloned()
+                    .unwrap_or(Validator {
                         address: address.to_string(),
                         commission: Decimal::zero(),
                         max_commission: Decimal::zero(),
                         max_change_rate: Decimal::zero(),
-                    }),
+                    });
+                QuerierResult::Ok(ContractResult::from(to_binary(&ValidatorResponse {
+                    validator: Some(validator),
                 })))
             }
             _ => self.base.handle_query(request),
@@ -90,6 +98,7 @@ impl WasmMockQuerier {
         validators: &[Validator],
         delegations: &[FullDelegation],
     ) {
+        self.staking_validators = validators.to_vec();
         self.base.update_staking(denom, validators, delegations);
     }
 }
@@ -99,6 +108,7 @@ impl WasmMockQuerier {
         WasmMockQuerier {
             base,
             validators: vec![],
+            staking_validators: vec![],
         }
     }
 }
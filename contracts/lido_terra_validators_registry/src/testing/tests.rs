@@ -11,16 +11,22 @@
 // limitations under the License.
 
 use crate::common::{calculate_delegations, calculate_undelegations};
-use crate::contract::{execute, instantiate};
-use crate::msg::{ExecuteMsg, InstantiateMsg};
-use crate::registry::{Validator, ValidatorResponse, CONFIG, REGISTRY};
+use crate::contract::{execute, instantiate, migrate, query};
+use crate::msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg};
+use crate::registry::{
+    AllocationsResponse, Config, FlaggedValidator, OldConfig, PendingRedelegation, Validator,
+    ValidatorAllocation, ValidatorInfoResponse, ValidatorPolicy, ValidatorResponse,
+    ValidatorsResponse, CONFIG, OLD_CONFIG, PENDING_REDELEGATIONS, REGISTRY,
+};
 use crate::testing::mock_querier::{mock_dependencies, WasmMockQuerier};
 use basset::hub::ExecuteMsg::{RedelegateProxy, UpdateGlobalIndex};
+use basset::ownership::PendingOwnerResponse;
 use cosmwasm_std::testing::{mock_env, mock_info};
 use cosmwasm_std::{
-    coin, coins, to_binary, Addr, Api, Coin, CosmosMsg, FullDelegation, StdError, Uint128,
-    Validator as CosmosValidator, WasmMsg,
+    coin, coins, from_binary, to_binary, Addr, Api, Coin, CosmosMsg, Decimal, FullDelegation,
+    StdError, Uint128, Validator as CosmosValidator, WasmMsg,
 };
+use std::cmp::Reverse;
 
 #[test]
 fn proper_instantiate() {
@@ -31,8 +37,11 @@ fn proper_instantiate() {
     let msg = InstantiateMsg {
         registry: vec![Validator {
             address: Default::default(),
+            weight: None,
+            max_cap: None,
         }],
         hub_contract: hub_address.clone(),
+        max_validators: None,
     };
     let info = mock_info("creator", &coins(1000, "earth"));
 
@@ -53,13 +62,26 @@ fn add_validator() {
     let msg = InstantiateMsg {
         registry: vec![],
         hub_contract: String::from("hub_contract_address"),
+        max_validators: None,
     };
     let info = mock_info("creator", &coins(2, "token"));
     let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
 
     let validator = Validator {
         address: Default::default(),
+        weight: None,
+        max_cap: None,
     };
+    set_delegation_query(
+        &mut deps.querier,
+        &[],
+        &[CosmosValidator {
+            address: validator.address.clone(),
+            commission: Default::default(),
+            max_commission: Default::default(),
+            max_change_rate: Default::default(),
+        }],
+    );
 
     let msg = ExecuteMsg::AddValidator {
         validator: validator.clone(),
@@ -69,7 +91,7 @@ fn add_validator() {
     match _res {
         Ok(_) => {
             let v = REGISTRY
-                .load(&deps.storage, validator.address.as_str().as_bytes())
+                .load(&deps.storage, validator.address.as_bytes())
                 .unwrap();
             assert_eq!(validator, v);
         }
@@ -84,6 +106,7 @@ fn ownership_tests() {
     let msg = InstantiateMsg {
         registry: vec![],
         hub_contract: String::from("hub_contract_address"),
+        max_validators: None,
     };
     let info = mock_info("creator", &coins(2, "token"));
     let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
@@ -92,6 +115,8 @@ fn ownership_tests() {
 
     let validator = Validator {
         address: Default::default(),
+        weight: None,
+        max_cap: None,
     };
 
     let msg = ExecuteMsg::AddValidator {
@@ -108,8 +133,19 @@ fn ownership_tests() {
 
     let msg = ExecuteMsg::UpdateConfig {
         hub_contract: None,
-        owner: None,
+        max_validators: None,
+    };
+    let res = execute(deps.as_mut(), mock_env(), info.clone(), msg);
+    assert_eq!(res.err().unwrap(), StdError::generic_err("unauthorized"));
+
+    let msg = ExecuteMsg::ProposeNewOwner {
+        new_owner: String::from("villain"),
+        expires_in: None,
     };
+    let res = execute(deps.as_mut(), mock_env(), info.clone(), msg);
+    assert_eq!(res.err().unwrap(), StdError::generic_err("unauthorized"));
+
+    let msg = ExecuteMsg::CancelOwnershipTransfer {};
     let res = execute(deps.as_mut(), mock_env(), info, msg);
     assert_eq!(res.err().unwrap(), StdError::generic_err("unauthorized"));
 }
@@ -121,6 +157,7 @@ fn update_config() {
     let msg = InstantiateMsg {
         registry: vec![],
         hub_contract: String::from("hub_contract_address"),
+        max_validators: None,
     };
     let info = mock_info("creator", &coins(2, "token"));
     let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
@@ -128,7 +165,7 @@ fn update_config() {
     let new_hub_address = String::from("new_hub_contract");
     let msg = ExecuteMsg::UpdateConfig {
         hub_contract: Some(new_hub_address.clone()),
-        owner: None,
+        max_validators: None,
     };
     let res = execute(deps.as_mut(), mock_env(), info.clone(), msg);
     assert!(res.is_ok());
@@ -139,11 +176,59 @@ fn update_config() {
     );
 
     let new_owner = String::from("new_owner");
-    let msg = ExecuteMsg::UpdateConfig {
-        owner: Some(new_owner.clone()),
-        hub_contract: None,
+
+    // the expiry can't overflow the block time
+    let msg = ExecuteMsg::ProposeNewOwner {
+        new_owner: new_owner.clone(),
+        expires_in: Some(u64::MAX),
     };
-    let res = execute(deps.as_mut(), mock_env(), info, msg);
+    let res = execute(deps.as_mut(), mock_env(), info.clone(), msg);
+    assert!(matches!(res, Err(StdError::Overflow { .. })));
+
+    let msg = ExecuteMsg::ProposeNewOwner {
+        new_owner: new_owner.clone(),
+        expires_in: Some(100),
+    };
+    let res = execute(deps.as_mut(), mock_env(), info.clone(), msg);
+    assert!(res.is_ok());
+
+    let pending: PendingOwnerResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::PendingOwner {}).unwrap()).unwrap();
+    assert_eq!(pending.pending_owner, Some(new_owner.clone()));
+
+    // the owner does not change until the proposal is accepted by the new owner
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        ExecuteMsg::AcceptOwnership {},
+    );
+    assert_eq!(res.err().unwrap(), StdError::generic_err("unauthorized"));
+    let config = CONFIG.load(&deps.storage).unwrap();
+    assert_eq!(
+        deps.api.addr_canonicalize(info.sender.as_str()).unwrap(),
+        config.owner
+    );
+
+    let mut expired_env = mock_env();
+    expired_env.block.time = expired_env.block.time.plus_seconds(100);
+    let res = execute(
+        deps.as_mut(),
+        expired_env,
+        mock_info(&new_owner, &[]),
+        ExecuteMsg::AcceptOwnership {},
+    );
+    assert_eq!(
+        res.err().unwrap(),
+        StdError::generic_err("The ownership transfer proposal has expired")
+    );
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(&new_owner, &[]),
+        ExecuteMsg::AcceptOwnership {},
+    );
     assert!(res.is_ok());
     let config = CONFIG.load(&deps.storage).unwrap();
     assert_eq!(
@@ -161,18 +246,26 @@ fn remove_validator() {
         .unwrap();
     let validator1 = Validator {
         address: String::from("validator"),
+        weight: None,
+        max_cap: None,
     };
 
     let validator2 = Validator {
         address: String::from("validator2"),
+        weight: None,
+        max_cap: None,
     };
 
     let validator3 = Validator {
         address: String::from("validator3"),
+        weight: None,
+        max_cap: None,
     };
 
     let validator4 = Validator {
         address: String::from("validator4"),
+        weight: None,
+        max_cap: None,
     };
 
     let validators = [
@@ -247,6 +340,7 @@ fn remove_validator() {
             validator4.clone(),
         ],
         hub_contract: hub_contract_address.to_string(),
+        max_validators: None,
     };
 
     let info = mock_info("creator", &coins(2, "token"));
@@ -259,7 +353,7 @@ fn remove_validator() {
     let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg);
     match _res {
         Ok(res) => {
-            let reg = REGISTRY.load(&deps.storage, validator4.address.as_str().as_bytes());
+            let reg = REGISTRY.load(&deps.storage, validator4.address.as_bytes());
             assert!(reg.is_err(), "Validator was not removed");
 
             let redelegate = &res.messages[0];
@@ -360,7 +454,7 @@ fn remove_validator() {
     let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg);
     match _res {
         Ok(res) => {
-            let reg = REGISTRY.load(&deps.storage, validator3.address.as_str().as_bytes());
+            let reg = REGISTRY.load(&deps.storage, validator3.address.as_bytes());
             assert!(reg.is_err(), "Validator was not removed");
 
             let redelegate = &res.messages[0];
@@ -446,7 +540,7 @@ fn remove_validator() {
     let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg);
     match _res {
         Ok(res) => {
-            let reg = REGISTRY.load(&deps.storage, validator2.address.as_str().as_bytes());
+            let reg = REGISTRY.load(&deps.storage, validator2.address.as_bytes());
             assert!(reg.is_err(), "Validator was not removed");
 
             let redelegate = &res.messages[0];
@@ -563,6 +657,7 @@ fn remove_validator() {
     let msg = InstantiateMsg {
         registry: vec![validator1.clone(), validator2],
         hub_contract: hub_contract_address.to_string(),
+        max_validators: None,
     };
 
     let mut deps = mock_dependencies(&coins(2, "token"));
@@ -582,6 +677,8 @@ macro_rules! default_validator_with_delegations {
         ValidatorResponse {
             total_delegated: Uint128::from($total as u128),
             address: Default::default(),
+            weight: 1,
+            max_cap: None,
         }
     };
 }
@@ -600,7 +697,7 @@ fn test_calculate_delegations() {
     ];
 
     // sort validators for the right delegations
-    validators.sort_by(|v1, v2| v1.total_delegated.cmp(&v2.total_delegated));
+    validators.sort_by_key(|v| v.total_delegated);
 
     let buffered_balance = Uint128::from(10u128);
     let (remained_balance, delegations) =
@@ -638,7 +735,7 @@ fn test_calculate_undelegations() {
     ];
 
     // sort validators for the right delegations
-    validators.sort_by(|v1, v2| v2.total_delegated.cmp(&v1.total_delegated));
+    validators.sort_by_key(|v| Reverse(v.total_delegated));
 
     let undelegate_amount = Uint128::from(100u128);
     let undelegations = calculate_undelegations(undelegate_amount, validators.clone()).unwrap();
@@ -667,7 +764,7 @@ fn test_calculate_undelegations() {
     ];
 
     // sort validators for the right delegations
-    validators.sort_by(|v1, v2| v2.total_delegated.cmp(&v1.total_delegated));
+    validators.sort_by_key(|v| Reverse(v.total_delegated));
 
     let undelegate_amount = Uint128::from(10u128);
     let undelegations = calculate_undelegations(undelegate_amount, validators.clone()).unwrap();
@@ -690,7 +787,7 @@ fn test_calculate_undelegations() {
         default_validator_with_delegations!(10),
     ];
     // sort validators for the right delegations
-    validators.sort_by(|v1, v2| v2.total_delegated.cmp(&v1.total_delegated));
+    validators.sort_by_key(|v| Reverse(v.total_delegated));
 
     let undelegate_amount = Uint128::from(1000u128);
     if let Some(e) = calculate_undelegations(undelegate_amount, validators.clone()).err() {
@@ -748,6 +845,959 @@ fn test_calculate_undelegations() {
     }
 }
 
+#[test]
+fn test_calculate_weighted_delegations() {
+    let mut validators = vec![
+        default_validator_with_delegations!(0),
+        default_validator_with_delegations!(0),
+        default_validator_with_delegations!(0),
+    ];
+    validators[1].weight = 2;
+
+    let (remained_balance, delegations) =
+        calculate_delegations(Uint128::from(100u128), validators.as_slice()).unwrap();
+    assert_eq!(remained_balance, Uint128::zero());
+    assert_eq!(
+        delegations,
+        vec![
+            Uint128::from(25u128),
+            Uint128::from(50u128),
+            Uint128::from(25u128)
+        ]
+    );
+
+    // the capped validator share goes to the other validators
+    validators[1].max_cap = Some(Uint128::from(30u128));
+    let (remained_balance, delegations) =
+        calculate_delegations(Uint128::from(100u128), validators.as_slice()).unwrap();
+    assert_eq!(remained_balance, Uint128::zero());
+    assert_eq!(
+        delegations,
+        vec![
+            Uint128::from(35u128),
+            Uint128::from(30u128),
+            Uint128::from(35u128)
+        ]
+    );
+
+    // nothing can be delegated above the caps
+    for validator in validators.iter_mut() {
+        validator.max_cap = Some(Uint128::from(10u128));
+    }
+    let (remained_balance, delegations) =
+        calculate_delegations(Uint128::from(100u128), validators.as_slice()).unwrap();
+    assert_eq!(remained_balance, Uint128::from(70u128));
+    assert_eq!(delegations, vec![Uint128::from(10u128); 3]);
+}
+
+#[test]
+fn test_calculate_weighted_undelegations() {
+    let mut validators = vec![
+        default_validator_with_delegations!(30),
+        default_validator_with_delegations!(30),
+        default_validator_with_delegations!(30),
+    ];
+    // a validator without weight is drained first
+    validators[0].weight = 0;
+
+    let undelegations = calculate_undelegations(Uint128::from(40u128), validators).unwrap();
+    assert_eq!(
+        undelegations,
+        vec![
+            Uint128::from(30u128),
+            Uint128::from(5u128),
+            Uint128::from(5u128)
+        ]
+    );
+}
+
+#[test]
+fn update_validator() {
+    let mut deps = mock_dependencies(&[]);
+    let hub_contract_address = deps
+        .api
+        .addr_validate(&String::from("hub_contract_address"))
+        .unwrap();
+    let validator1 = Validator {
+        address: String::from("validator"),
+        weight: None,
+        max_cap: None,
+    };
+    let validator2 = Validator {
+        address: String::from("validator2"),
+        weight: Some(3),
+        max_cap: None,
+    };
+    let validators = [
+        CosmosValidator {
+            address: validator1.address.clone(),
+            commission: Default::default(),
+            max_commission: Default::default(),
+            max_change_rate: Default::default(),
+        },
+        CosmosValidator {
+            address: validator2.address.clone(),
+            commission: Default::default(),
+            max_commission: Default::default(),
+            max_change_rate: Default::default(),
+        },
+    ];
+    set_delegation_query(
+        &mut deps.querier,
+        &[
+            sample_delegation(
+                hub_contract_address.clone(),
+                validator1.address.clone(),
+                coin(20, "uluna"),
+            ),
+            sample_delegation(
+                hub_contract_address.clone(),
+                validator2.address.clone(),
+                coin(20, "uluna"),
+            ),
+        ],
+        &validators,
+    );
+    let msg = InstantiateMsg {
+        registry: vec![validator1.clone(), validator2.clone()],
+        hub_contract: hub_contract_address.to_string(),
+        max_validators: None,
+    };
+    let info = mock_info("creator", &[]);
+    instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+    let allocations: AllocationsResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Allocations {}).unwrap()).unwrap();
+    assert_eq!(
+        allocations,
+        AllocationsResponse {
+            total_delegated: Uint128::from(40u128),
+            validators: vec![
+                ValidatorAllocation {
+                    address: validator1.address.clone(),
+                    weight: 1,
+                    max_cap: None,
+                    target_delegated: Uint128::from(10u128),
+                    total_delegated: Uint128::from(20u128),
+                },
+                ValidatorAllocation {
+                    address: validator2.address.clone(),
+                    weight: 3,
+                    max_cap: None,
+                    target_delegated: Uint128::from(30u128),
+                    total_delegated: Uint128::from(20u128),
+                },
+            ],
+        }
+    );
+
+    let update = Validator {
+        address: validator1.address.clone(),
+        weight: Some(3),
+        max_cap: Some(Uint128::from(100u128)),
+    };
+
+    // only the owner can update a validator
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("attacker", &[]),
+        ExecuteMsg::UpdateValidator {
+            validator: update.clone(),
+        },
+    );
+    assert_eq!(res.unwrap_err(), StdError::generic_err("unauthorized"));
+
+    // the validator must be registered
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        ExecuteMsg::UpdateValidator {
+            validator: Validator {
+                address: String::from("validator3"),
+                weight: Some(3),
+                max_cap: None,
+            },
+        },
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        StdError::generic_err("Validator is not registered in the registry")
+    );
+
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::UpdateValidator {
+            validator: update.clone(),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        REGISTRY
+            .load(&deps.storage, validator1.address.as_bytes())
+            .unwrap(),
+        update
+    );
+
+    let allocations: AllocationsResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Allocations {}).unwrap()).unwrap();
+    assert_eq!(
+        allocations.validators[0].target_delegated,
+        Uint128::from(20u128)
+    );
+    assert_eq!(
+        allocations.validators[1].target_delegated,
+        Uint128::from(20u128)
+    );
+}
+
+#[test]
+fn rebalance() {
+    let mut deps = mock_dependencies(&[]);
+    let hub_contract_address = deps
+        .api
+        .addr_validate(&String::from("hub_contract_address"))
+        .unwrap();
+    let addresses = ["validator1", "validator2", "validator3", "validator4"];
+    let validators: Vec<CosmosValidator> = addresses
+        .iter()
+        .map(|address| CosmosValidator {
+            address: address.to_string(),
+            commission: Default::default(),
+            max_commission: Default::default(),
+            max_change_rate: Default::default(),
+        })
+        .collect();
+    let mut delegations: Vec<FullDelegation> = addresses
+        .iter()
+        .zip([10u128, 20, 30, 60])
+        .map(|(address, amount)| {
+            sample_delegation(
+                hub_contract_address.clone(),
+                address.to_string(),
+                coin(amount, "uluna"),
+            )
+        })
+        .collect();
+    set_delegation_query(&mut deps.querier, &delegations, &validators);
+
+    let msg = InstantiateMsg {
+        registry: addresses
+            .iter()
+            .map(|address| Validator {
+                address: address.to_string(),
+                weight: None,
+                max_cap: None,
+            })
+            .collect(),
+        hub_contract: hub_contract_address.to_string(),
+        max_validators: None,
+    };
+    instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+    // anyone can rebalance, validator4 is 30 above the target of 30
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("anyone", &[]),
+        ExecuteMsg::Rebalance { max_moves: None },
+    )
+    .unwrap();
+    assert_eq!(res.messages.len(), 2);
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: hub_contract_address.to_string(),
+            msg: to_binary(&RedelegateProxy {
+                src_validator: "validator4".to_string(),
+                redelegations: vec![
+                    ("validator1".to_string(), coin(20, "uluna")),
+                    ("validator2".to_string(), coin(10, "uluna")),
+                ],
+            })
+            .unwrap(),
+            funds: vec![],
+        })
+    );
+    assert_eq!(
+        res.messages[1].msg,
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: hub_contract_address.to_string(),
+            msg: to_binary(&UpdateGlobalIndex {}).unwrap(),
+            funds: vec![],
+        })
+    );
+    assert_eq!(res.attributes[1].value, "2");
+    assert_eq!(res.attributes[2].value, "validator4:validator1:20");
+    assert_eq!(res.attributes[3].value, "validator4:validator2:10");
+
+    // the number of moves is bounded
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("anyone", &[]),
+        ExecuteMsg::Rebalance { max_moves: Some(1) },
+    )
+    .unwrap();
+    assert_eq!(res.attributes[1].value, "1");
+    assert_eq!(res.attributes[2].value, "validator4:validator1:20");
+
+    // only the redelegatable part of a delegation is moved
+    delegations[3].can_redelegate = coin(5, "uluna");
+    set_delegation_query(&mut deps.querier, &delegations, &validators);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("anyone", &[]),
+        ExecuteMsg::Rebalance { max_moves: None },
+    )
+    .unwrap();
+    assert_eq!(res.attributes[1].value, "1");
+    assert_eq!(res.attributes[2].value, "validator4:validator1:5");
+
+    // nothing to do when there is no redelegatable stake
+    delegations[3].can_redelegate = coin(0, "uluna");
+    set_delegation_query(&mut deps.querier, &delegations, &validators);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("anyone", &[]),
+        ExecuteMsg::Rebalance { max_moves: None },
+    )
+    .unwrap();
+    assert_eq!(res.messages.len(), 0);
+}
+
+#[test]
+fn pending_redelegations() {
+    let mut deps = mock_dependencies(&[]);
+    let hub_contract_address = deps
+        .api
+        .addr_validate(&String::from("hub_contract_address"))
+        .unwrap();
+    let addresses = ["validator1", "validator2", "validator3"];
+    let validators: Vec<CosmosValidator> = addresses
+        .iter()
+        .map(|address| CosmosValidator {
+            address: address.to_string(),
+            commission: Default::default(),
+            max_commission: Default::default(),
+            max_change_rate: Default::default(),
+        })
+        .collect();
+    let mut delegation = sample_delegation(
+        hub_contract_address.clone(),
+        "validator1".to_string(),
+        coin(100, "uluna"),
+    );
+    // validator1 has an active redelegation, only 40 can be redelegated
+    delegation.can_redelegate = coin(40, "uluna");
+    set_delegation_query(&mut deps.querier, &[delegation.clone()], &validators);
+
+    let msg = InstantiateMsg {
+        registry: addresses
+            .iter()
+            .map(|address| Validator {
+                address: address.to_string(),
+                weight: None,
+                max_cap: None,
+            })
+            .collect(),
+        hub_contract: hub_contract_address.to_string(),
+        max_validators: None,
+    };
+    let info = mock_info("creator", &[]);
+    instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::RemoveValidator {
+            address: "validator1".to_string(),
+        },
+    )
+    .unwrap();
+    assert_eq!(res.messages.len(), 2);
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: hub_contract_address.to_string(),
+            msg: to_binary(&RedelegateProxy {
+                src_validator: "validator1".to_string(),
+                redelegations: vec![
+                    ("validator2".to_string(), coin(20, "uluna")),
+                    ("validator3".to_string(), coin(20, "uluna")),
+                ],
+            })
+            .unwrap(),
+            funds: vec![],
+        })
+    );
+
+    let pending: Vec<PendingRedelegation> =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::PendingRedelegations {}).unwrap())
+            .unwrap();
+    assert_eq!(
+        pending,
+        vec![PendingRedelegation {
+            src_validator: "validator1".to_string(),
+            amount: coin(60, "uluna"),
+            queued_at: mock_env().block.time.seconds(),
+        }]
+    );
+
+    // the redelegation is still active, nothing can be moved
+    delegation.amount = coin(60, "uluna");
+    delegation.can_redelegate = coin(0, "uluna");
+    let delegations = [
+        delegation.clone(),
+        sample_delegation(
+            hub_contract_address.clone(),
+            "validator2".to_string(),
+            coin(20, "uluna"),
+        ),
+        sample_delegation(
+            hub_contract_address.clone(),
+            "validator3".to_string(),
+            coin(20, "uluna"),
+        ),
+    ];
+    set_delegation_query(&mut deps.querier, &delegations, &validators);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("anyone", &[]),
+        ExecuteMsg::ProcessPendingRedelegations {},
+    )
+    .unwrap();
+    assert_eq!(res.messages.len(), 0);
+    assert!(PENDING_REDELEGATIONS.has(&deps.storage, "validator1".as_bytes()));
+
+    // anyone can move the rest once the redelegation is over
+    let mut delegations = delegations;
+    delegations[0].can_redelegate = coin(60, "uluna");
+    set_delegation_query(&mut deps.querier, &delegations, &validators);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("anyone", &[]),
+        ExecuteMsg::ProcessPendingRedelegations {},
+    )
+    .unwrap();
+    assert_eq!(res.messages.len(), 2);
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: hub_contract_address.to_string(),
+            msg: to_binary(&RedelegateProxy {
+                src_validator: "validator1".to_string(),
+                redelegations: vec![
+                    ("validator2".to_string(), coin(30, "uluna")),
+                    ("validator3".to_string(), coin(30, "uluna")),
+                ],
+            })
+            .unwrap(),
+            funds: vec![],
+        })
+    );
+    assert_eq!(
+        res.messages[1].msg,
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: hub_contract_address.to_string(),
+            msg: to_binary(&UpdateGlobalIndex {}).unwrap(),
+            funds: vec![],
+        })
+    );
+    let pending: Vec<PendingRedelegation> =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::PendingRedelegations {}).unwrap())
+            .unwrap();
+    assert!(pending.is_empty());
+
+    // a pending redelegation of a validator the hub no longer delegates to is dropped
+    PENDING_REDELEGATIONS
+        .save(
+            &mut deps.storage,
+            "validator1".as_bytes(),
+            &PendingRedelegation {
+                src_validator: "validator1".to_string(),
+                amount: coin(60, "uluna"),
+                queued_at: mock_env().block.time.seconds(),
+            },
+        )
+        .unwrap();
+    set_delegation_query(&mut deps.querier, &delegations[1..], &validators);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("anyone", &[]),
+        ExecuteMsg::ProcessPendingRedelegations {},
+    )
+    .unwrap();
+    assert_eq!(res.messages.len(), 0);
+    assert!(!PENDING_REDELEGATIONS.has(&deps.storage, "validator1".as_bytes()));
+}
+
+#[test]
+fn screen_validators() {
+    let mut deps = mock_dependencies(&[]);
+    let hub_contract_address = deps
+        .api
+        .addr_validate(&String::from("hub_contract_address"))
+        .unwrap();
+    let cosmos_validator = |address: &str, commission: Decimal| CosmosValidator {
+        address: address.to_string(),
+        commission,
+        max_commission: Decimal::one(),
+        max_change_rate: Default::default(),
+    };
+    let delegations: Vec<FullDelegation> = ["validator1", "validator2", "validator3"]
+        .iter()
+        .map(|address| {
+            sample_delegation(
+                hub_contract_address.clone(),
+                address.to_string(),
+                coin(30, "uluna"),
+            )
+        })
+        .collect();
+    set_delegation_query(
+        &mut deps.querier,
+        &delegations,
+        &[
+            cosmos_validator("validator1", Decimal::zero()),
+            cosmos_validator("validator2", Decimal::zero()),
+            cosmos_validator("validator3", Decimal::zero()),
+            cosmos_validator("validator4", Decimal::percent(50)),
+        ],
+    );
+
+    let msg = InstantiateMsg {
+        registry: ["validator1", "validator2", "validator3"]
+            .iter()
+            .map(|address| Validator {
+                address: address.to_string(),
+                weight: None,
+                max_cap: None,
+            })
+            .collect(),
+        hub_contract: hub_contract_address.to_string(),
+        max_validators: None,
+    };
+    let info = mock_info("creator", &[]);
+    instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+    let policy = ValidatorPolicy {
+        max_commission: Some(Decimal::percent(10)),
+        allow_jailed: false,
+    };
+    // only the owner can update the policy
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("attacker", &[]),
+        ExecuteMsg::UpdatePolicy {
+            policy: policy.clone(),
+        },
+    );
+    assert_eq!(res.unwrap_err(), StdError::generic_err("unauthorized"));
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        ExecuteMsg::UpdatePolicy {
+            policy: policy.clone(),
+        },
+    )
+    .unwrap();
+    let res: ValidatorPolicy =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Policy {}).unwrap()).unwrap();
+    assert_eq!(res, policy);
+
+    // a validator with a high commission can't be added
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::AddValidator {
+            validator: Validator {
+                address: "validator4".to_string(),
+                weight: None,
+                max_cap: None,
+            },
+        },
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        StdError::generic_err("Validator doesn't conform to the validator policy: commission")
+    );
+
+    // validator1 raises its commission and validator2 leaves the active set
+    set_delegation_query(
+        &mut deps.querier,
+        &delegations,
+        &[
+            cosmos_validator("validator1", Decimal::percent(20)),
+            cosmos_validator("validator3", Decimal::zero()),
+        ],
+    );
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("anyone", &[]),
+        ExecuteMsg::ScreenValidators {},
+    )
+    .unwrap();
+    assert_eq!(res.messages.len(), 3);
+    for (message, src_validator) in res.messages.iter().zip(["validator1", "validator2"]) {
+        assert_eq!(
+            message.msg,
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: hub_contract_address.to_string(),
+                msg: to_binary(&RedelegateProxy {
+                    src_validator: src_validator.to_string(),
+                    redelegations: vec![("validator3".to_string(), coin(30, "uluna"))],
+                })
+                .unwrap(),
+                funds: vec![],
+            })
+        );
+    }
+
+    let flagged: Vec<FlaggedValidator> =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::FlaggedValidators {}).unwrap())
+            .unwrap();
+    assert_eq!(
+        flagged,
+        vec![
+            FlaggedValidator {
+                address: "validator1".to_string(),
+                reason: "commission".to_string(),
+            },
+            FlaggedValidator {
+                address: "validator2".to_string(),
+                reason: "inactive".to_string(),
+            },
+        ]
+    );
+
+    // flagged validators don't receive delegations
+    let validators: Vec<ValidatorResponse> = from_binary(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetValidatorsForDelegation {},
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(validators.len(), 1);
+    assert_eq!(validators[0].address, "validator3");
+
+    // validator1 conforms to the policy again
+    set_delegation_query(
+        &mut deps.querier,
+        &delegations,
+        &[
+            cosmos_validator("validator1", Decimal::percent(5)),
+            cosmos_validator("validator3", Decimal::zero()),
+        ],
+    );
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("anyone", &[]),
+        ExecuteMsg::ScreenValidators {},
+    )
+    .unwrap();
+    assert_eq!(res.messages.len(), 0);
+    let flagged: Vec<FlaggedValidator> =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::FlaggedValidators {}).unwrap())
+            .unwrap();
+    assert_eq!(flagged.len(), 1);
+    assert_eq!(flagged[0].address, "validator2");
+
+    // a removed validator is no longer flagged
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("creator", &[]),
+        ExecuteMsg::RemoveValidator {
+            address: "validator2".to_string(),
+        },
+    )
+    .unwrap();
+    let flagged: Vec<FlaggedValidator> =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::FlaggedValidators {}).unwrap())
+            .unwrap();
+    assert!(flagged.is_empty());
+}
+
+#[test]
+fn max_validators() {
+    let mut deps = mock_dependencies(&[]);
+    let validator = |address: &str| Validator {
+        address: address.to_string(),
+        weight: None,
+        max_cap: None,
+    };
+    let info = mock_info("creator", &[]);
+
+    let msg = InstantiateMsg {
+        registry: vec![
+            validator("validator1"),
+            validator("validator2"),
+            validator("validator3"),
+        ],
+        hub_contract: String::from("hub_contract_address"),
+        max_validators: Some(2),
+    };
+    let res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg);
+    assert_eq!(
+        res.unwrap_err(),
+        StdError::generic_err("Can't have more than 2 registered validators")
+    );
+
+    let msg = InstantiateMsg {
+        registry: vec![validator("validator1"), validator("validator2")],
+        hub_contract: String::from("hub_contract_address"),
+        max_validators: Some(2),
+    };
+    instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+    let validators: Vec<CosmosValidator> = ["validator1", "validator3"]
+        .iter()
+        .map(|address| CosmosValidator {
+            address: address.to_string(),
+            commission: Default::default(),
+            max_commission: Default::default(),
+            max_change_rate: Default::default(),
+        })
+        .collect();
+    set_delegation_query(&mut deps.querier, &[], &validators);
+
+    let add_msg = ExecuteMsg::AddValidator {
+        validator: validator("validator3"),
+    };
+    let res = execute(deps.as_mut(), mock_env(), info.clone(), add_msg.clone());
+    assert_eq!(
+        res.unwrap_err(),
+        StdError::generic_err("Can't have more than 2 registered validators")
+    );
+
+    // a registered validator can still be added again at the max
+    let msg = ExecuteMsg::AddValidator {
+        validator: Validator {
+            weight: Some(2),
+            ..validator("validator1")
+        },
+    };
+    execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+    // the max can't be lowered below the number of registered validators
+    let msg = ExecuteMsg::UpdateConfig {
+        hub_contract: None,
+        max_validators: Some(1),
+    };
+    let res = execute(deps.as_mut(), mock_env(), info.clone(), msg);
+    assert_eq!(
+        res.unwrap_err(),
+        StdError::generic_err("Can't have more than 1 registered validators")
+    );
+    let msg = ExecuteMsg::UpdateConfig {
+        hub_contract: None,
+        max_validators: Some(0),
+    };
+    let res = execute(deps.as_mut(), mock_env(), info.clone(), msg);
+    assert_eq!(
+        res.unwrap_err(),
+        StdError::generic_err("The max number of validators must be greater than zero")
+    );
+
+    let msg = ExecuteMsg::UpdateConfig {
+        hub_contract: None,
+        max_validators: Some(3),
+    };
+    execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+    assert_eq!(CONFIG.load(&deps.storage).unwrap().max_validators, 3);
+    execute(deps.as_mut(), mock_env(), info, add_msg).unwrap();
+}
+
+#[test]
+fn test_migrate() {
+    let mut deps = mock_dependencies(&[]);
+    let old_config = OldConfig {
+        owner: deps.api.addr_canonicalize("owner").unwrap(),
+        hub_contract: deps.api.addr_canonicalize("hub_contract_address").unwrap(),
+    };
+    OLD_CONFIG.save(&mut deps.storage, &old_config).unwrap();
+
+    migrate(
+        deps.as_mut(),
+        mock_env(),
+        MigrateMsg {
+            max_validators: None,
+        },
+    )
+    .unwrap();
+
+    let config = CONFIG.load(&deps.storage).unwrap();
+    assert_eq!(
+        config,
+        Config {
+            owner: old_config.owner,
+            hub_contract: old_config.hub_contract,
+            max_validators: 30,
+        }
+    );
+}
+
+#[test]
+fn query_validators_info() {
+    let mut deps = mock_dependencies(&[]);
+    let hub_contract_address = deps
+        .api
+        .addr_validate(&String::from("hub_contract_address"))
+        .unwrap();
+    let delegations: Vec<FullDelegation> = ["validator1", "validator2", "validator3"]
+        .iter()
+        .zip([10u128, 30, 60])
+        .map(|(address, amount)| {
+            sample_delegation(
+                hub_contract_address.clone(),
+                address.to_string(),
+                coin(amount, "uluna"),
+            )
+        })
+        .collect();
+    // validator3 is out of the active set
+    set_delegation_query(
+        &mut deps.querier,
+        &delegations,
+        &[
+            CosmosValidator {
+                address: "validator1".to_string(),
+                commission: Decimal::percent(5),
+                max_commission: Decimal::one(),
+                max_change_rate: Default::default(),
+            },
+            CosmosValidator {
+                address: "validator2".to_string(),
+                commission: Decimal::percent(10),
+                max_commission: Decimal::one(),
+                max_change_rate: Default::default(),
+            },
+        ],
+    );
+
+    let msg = InstantiateMsg {
+        registry: vec![
+            Validator {
+                address: "validator1".to_string(),
+                weight: None,
+                max_cap: None,
+            },
+            Validator {
+                address: "validator2".to_string(),
+                weight: Some(2),
+                max_cap: Some(Uint128::from(30u128)),
+            },
+            Validator {
+                address: "validator3".to_string(),
+                weight: None,
+                max_cap: None,
+            },
+        ],
+        hub_contract: hub_contract_address.to_string(),
+        max_validators: None,
+    };
+    instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+    let res: ValidatorsResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Validators {
+                start_after: None,
+                limit: Some(2),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        res.validators,
+        vec![
+            ValidatorInfoResponse {
+                address: "validator1".to_string(),
+                weight: 1,
+                max_cap: None,
+                total_delegated: Uint128::from(10u128),
+                stake_share: Decimal::percent(10),
+                commission: Some(Decimal::percent(5)),
+                in_active_set: true,
+                flagged_reason: None,
+                accepts_delegations: true,
+            },
+            ValidatorInfoResponse {
+                address: "validator2".to_string(),
+                weight: 2,
+                max_cap: Some(Uint128::from(30u128)),
+                total_delegated: Uint128::from(30u128),
+                stake_share: Decimal::percent(30),
+                commission: Some(Decimal::percent(10)),
+                in_active_set: true,
+                flagged_reason: None,
+                // the validator reached its max cap
+                accepts_delegations: false,
+            },
+        ]
+    );
+
+    let res: ValidatorsResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Validators {
+                start_after: Some("validator2".to_string()),
+                limit: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(res.validators.len(), 1);
+    assert_eq!(res.validators[0].address, "validator3");
+    assert!(!res.validators[0].in_active_set);
+
+    let res: ValidatorInfoResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Validator {
+                address: "validator3".to_string(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(res.stake_share, Decimal::percent(60));
+
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::Validator {
+            address: "validator4".to_string(),
+        },
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        StdError::generic_err("Validator is not registered in the registry")
+    );
+}
+
 fn set_delegation_query(
     querier: &mut WasmMockQuerier,
     delegate: &[FullDelegation],
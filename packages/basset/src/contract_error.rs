@@ -1,4 +1,4 @@
-use cosmwasm_std::{OverflowError, StdError};
+use cosmwasm_std::{OverflowError, StdError, Uint128};
 use thiserror::Error;
 
 #[derive(Error, Debug, PartialEq)]
@@ -23,4 +23,67 @@ pub enum ContractError {
 
     #[error("Asset mismatch")]
     AssetMismatch {},
+
+    #[error("The {contract} contract must have been registered")]
+    MissingRegisteredContract { contract: String },
+
+    #[error("No {denom} assets are provided to bond")]
+    WrongDenom { denom: String },
+
+    #[error("More than one coin is sent; only one asset is supported")]
+    MultipleCoins {},
+
+    #[error("Validators registry is empty")]
+    EmptyRegistry {},
+
+    #[error("Validator must be registered in the registry before redelegating")]
+    ValidatorNotRegistered { validator: String },
+
+    #[error("Burn amount must be greater than 1 {denom}")]
+    BatchTooSmall { denom: String },
+
+    #[error("No withdrawable {denom} assets are available yet")]
+    NoWithdrawableAssets { denom: String },
+
+    #[error("peg_recovery_fee can not be greater than 1")]
+    InvalidPegRecoveryFee {},
+
+    #[error("compound_ratio can not be greater than 1")]
+    InvalidCompoundRatio {},
+
+    #[error("There is no pending ownership transfer")]
+    NoPendingOwner {},
+
+    #[error("The ownership transfer proposal has expired")]
+    OwnershipProposalExpired {},
+
+    #[error("The {operation} operation is paused")]
+    OperationPaused { operation: String },
+
+    #[error("Validators have not enough capacity left to accept {amount} for delegation")]
+    NoDelegationCapacity { amount: Uint128 },
+
+    #[error("No liquidity pool is available to swap {offer} to {ask}")]
+    NoSwapVenue { offer: String, ask: String },
+
+    #[error("The native token has not been created")]
+    NoNativeToken {},
+
+    #[error("The native token has already been created")]
+    NativeTokenAlreadyCreated {},
+
+    #[error("IBC channel {channel_id} is not allowed")]
+    IbcChannelNotAllowed { channel_id: String },
+
+    #[error("The IBC transfer timeout must be at least {min} seconds")]
+    IbcTimeoutTooShort { min: u64 },
+
+    #[error("Can't withdraw to both a recipient and an IBC transfer")]
+    RecipientAndIbcTransfer {},
+
+    #[error("Batch {batch_id} has no released unbond request to withdraw")]
+    BatchNotWithdrawable { batch_id: u64 },
+
+    #[error("Burn requests not found for batch {batch_id}")]
+    UnbondHistoryNotFound { batch_id: u64 },
 }
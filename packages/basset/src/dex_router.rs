@@ -1,10 +1,8 @@
 use cosmwasm_schema::{cw_serde, QueryResponses};
-use cosmwasm_std::{Addr, Decimal, Uint128};
+use cosmwasm_std::{Addr, Decimal, QuerierWrapper, StdResult, Uint128};
 
 use cw20::Cw20ReceiveMsg;
 
-use strum_macros::EnumIter;
-
 #[cw_serde]
 #[derive(Hash, Eq)]
 pub enum AssetInfo {
@@ -47,6 +45,37 @@ pub enum SwapOperation {
     },
 }
 
+impl SwapOperation {
+    /// Returns the offered and the asked asset of the operation
+    pub fn asset_infos(&self) -> (AssetInfo, AssetInfo) {
+        match self {
+            SwapOperation::NativeSwap {
+                offer_denom,
+                ask_denom,
+            } => (
+                AssetInfo::NativeToken {
+                    denom: offer_denom.clone(),
+                },
+                AssetInfo::NativeToken {
+                    denom: ask_denom.clone(),
+                },
+            ),
+            SwapOperation::AstroSwap {
+                offer_asset_info,
+                ask_asset_info,
+            }
+            | SwapOperation::TerraSwap {
+                offer_asset_info,
+                ask_asset_info,
+            }
+            | SwapOperation::TokenSwap {
+                offer_asset_info,
+                ask_asset_info,
+            } => (offer_asset_info.clone(), ask_asset_info.clone()),
+        }
+    }
+}
+
 #[cw_serde]
 pub enum ExecuteMsg {
     /// Receive receives a message of type [`Cw20ReceiveMsg`] and processes it depending on the received template
@@ -77,6 +106,18 @@ pub enum ExecuteMsg {
     },
 }
 
+/// This structure describes the messages a cw20 token can send to the router
+#[cw_serde]
+pub enum Cw20HookMsg {
+    /// ExecuteSwapOperations processes multiple swaps of the sent tokens
+    ExecuteSwapOperations {
+        operations: Vec<SwapOperation>,
+        minimum_receive: Option<Uint128>,
+        to: Option<String>,
+        max_spread: Option<Decimal>,
+    },
+}
+
 /// This structure describes the query messages available in the contract.
 #[cw_serde]
 #[derive(QueryResponses)]
@@ -108,9 +149,151 @@ pub struct SimulateSwapOperationsResponse {
     pub amount: Uint128,
 }
 
-#[derive(Clone, Copy, EnumIter)]
-pub enum SwapMessageType {
-    Astroport,
-    Phoenix,
+/// The swap operation variant understood by the router of a dex
+#[cw_serde]
+#[derive(Copy, Eq)]
+pub enum OperationFlavour {
+    /// Astroport
+    AstroSwap,
+    /// TerraSwap
     TerraSwap,
+    /// Phoenix
+    TokenSwap,
+}
+
+impl OperationFlavour {
+    /// Builds the single hop swap operation of the flavour
+    pub fn swap_operation(
+        self,
+        offer_asset_info: AssetInfo,
+        ask_asset_info: AssetInfo,
+    ) -> SwapOperation {
+        match self {
+            OperationFlavour::AstroSwap => SwapOperation::AstroSwap {
+                offer_asset_info,
+                ask_asset_info,
+            },
+            OperationFlavour::TerraSwap => SwapOperation::TerraSwap {
+                offer_asset_info,
+                ask_asset_info,
+            },
+            OperationFlavour::TokenSwap => SwapOperation::TokenSwap {
+                offer_asset_info,
+                ask_asset_info,
+            },
+        }
+    }
+}
+
+/// Simulates the swap operations on the router
+pub fn simulate_swap_operations(
+    querier: &QuerierWrapper,
+    router: String,
+    offer_amount: Uint128,
+    operations: Vec<SwapOperation>,
+) -> StdResult<Uint128> {
+    let response: SimulateSwapOperationsResponse = querier.query_wasm_smart(
+        router,
+        &QueryMsg::SimulateSwapOperations {
+            offer_amount,
+            operations,
+        },
+    )?;
+    Ok(response.amount)
+}
+
+/// Simulates each of the `routes` (router address, swap operations) and returns the index of
+/// the route with the best output, along with the output. Routes failing the simulation
+/// are skipped.
+pub fn find_best_route(
+    querier: &QuerierWrapper,
+    routes: &[(String, Vec<SwapOperation>)],
+    offer_amount: Uint128,
+) -> Option<(usize, Uint128)> {
+    routes
+        .iter()
+        .map(|(router, operations)| {
+            simulate_swap_operations(querier, router.clone(), offer_amount, operations.clone())
+        })
+        .enumerate()
+        .filter_map(|(index, result)| result.ok().map(|amount| (index, amount)))
+        .max_by_key(|&(_, amount)| amount)
+}
+
+/// Splits `offer_amount` in `chunks` and allocates them one by one to the route with the best
+/// marginal output. Returns the (route index, offer amount, simulated output) of every route
+/// with an allocation. Routes failing the simulation are skipped.
+pub fn split_across_routes(
+    querier: &QuerierWrapper,
+    routes: &[(String, Vec<SwapOperation>)],
+    offer_amount: Uint128,
+    chunks: u32,
+) -> Vec<(usize, Uint128, Uint128)> {
+    let chunks = Uint128::from(chunks.max(1));
+    let chunk = offer_amount / chunks;
+    // the last chunk also takes the remainder of the division
+    let last_chunk = offer_amount - chunk * (chunks - Uint128::one());
+
+    let mut allocated = vec![Uint128::zero(); routes.len()];
+    let mut outputs = vec![Uint128::zero(); routes.len()];
+    let mut remaining = offer_amount;
+    while !remaining.is_zero() {
+        let size = if remaining == last_chunk {
+            last_chunk
+        } else {
+            chunk
+        };
+        let best = routes
+            .iter()
+            .enumerate()
+            .filter_map(|(index, (router, operations))| {
+                simulate_swap_operations(
+                    querier,
+                    router.clone(),
+                    allocated[index] + size,
+                    operations.clone(),
+                )
+                .ok()
+                .map(|output| (index, output))
+            })
+            .max_by_key(|&(index, output)| output.saturating_sub(outputs[index]));
+        match best {
+            Some((index, output)) => {
+                allocated[index] += size;
+                outputs[index] = output;
+                remaining -= size;
+            }
+            None => break,
+        }
+    }
+
+    allocated
+        .into_iter()
+        .zip(outputs)
+        .enumerate()
+        .filter(|(_, (amount, _))| !amount.is_zero())
+        .map(|(index, (amount, output))| (index, amount, output))
+        .collect()
+}
+
+/// Simulates the single hop swap on each of the `venues` (operation flavour, router address) and
+/// returns the index of the venue with the best output, along with the output. Venues failing
+/// the simulation are skipped.
+pub fn find_best_venue(
+    querier: &QuerierWrapper,
+    venues: &[(OperationFlavour, String)],
+    offer_amount: Uint128,
+    offer_asset_info: &AssetInfo,
+    ask_asset_info: &AssetInfo,
+) -> Option<(usize, Uint128)> {
+    let routes: Vec<(String, Vec<SwapOperation>)> = venues
+        .iter()
+        .map(|(flavour, router)| {
+            (
+                router.clone(),
+                vec![flavour.swap_operation(offer_asset_info.clone(), ask_asset_info.clone())],
+            )
+        })
+        .collect();
+    find_best_route(querier, &routes, offer_amount)
 }
@@ -1,7 +1,10 @@
-use cosmwasm_std::{Addr, Coin, Decimal, Uint128};
+use cosmwasm_std::{Addr, Coin, Decimal, Decimal256, Uint128};
 use cw20::Cw20ReceiveMsg;
+
+use crate::ibc::{IbcLifecycleComplete, IbcTransfer};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use strum_macros::Display;
 
 pub type UnbondRequest = Vec<(u64, Uint128)>;
 
@@ -35,6 +38,28 @@ pub struct Config {
     //pub airdrop_registry_contract: Option<Addr>,
 }
 
+/// Token factory denom minted by the hub as an alternative to the CW20 token.
+///
+/// The native token doesn't go through the reward contract, its transfers are not tracked.
+/// Its holders are paid through `basset_rate` instead: their share of the underlying rewards is
+/// restaked by the hub and raises the bAsset each native token stands for, whoever holds it,
+/// IBC escrows and module accounts included. The rewards in other denoms all go to the CW20
+/// holders through the reward contract. Claims and auto-claim don't apply to the native token.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NativeToken {
+    pub denom: String,
+    pub supply: Uint128,
+    /// bAsset one native token stands for, 1 when the token is created
+    pub basset_rate: Decimal,
+}
+
+impl NativeToken {
+    /// bAsset the native token supply stands for
+    pub fn basset_supply(&self) -> Uint128 {
+        self.supply * self.basset_rate
+    }
+}
+
 impl State {
     pub fn update_exchange_rate(&mut self, total_issued: Uint128, requested_with_fee: Uint128) {
         let actual_supply = total_issued + requested_with_fee;
@@ -53,22 +78,55 @@ pub enum ExecuteMsg {
     /// Owner's operations
     ////////////////////
 
-    /// Set the owner
+    /// Set the reward, token and validators registry contracts
     UpdateConfig {
-        owner: Option<String>,
         reward_contract: Option<String>,
         token_contract: Option<String>,
         validators_registry_contract: Option<String>,
         //airdrop_registry_contract: Option<String>,
     },
 
+    /// Propose a new owner. The ownership is only transferred once the new owner accepts it.
+    /// `expires_in` is the number of seconds the proposal stays valid for.
+    ProposeNewOwner {
+        new_owner: String,
+        expires_in: Option<u64>,
+    },
+
+    /// Cancel the pending ownership transfer
+    CancelOwnershipTransfer {},
+
+    /// Accept the pending ownership transfer. Only the proposed owner can execute it.
+    AcceptOwnership {},
+
+    /// Set or remove the guardian. The guardian is allowed to pause operations.
+    UpdateGuardian { guardian: Option<String> },
+
+    /// Pause the given operations. Only the owner or the guardian can execute it.
+    Pause { operations: Vec<PausableOperation> },
+
+    /// Resume the given operations. Only the owner can execute it.
+    Unpause { operations: Vec<PausableOperation> },
+
     /// update the parameters that is needed for the contract
     UpdateParams {
         epoch_period: Option<u64>,
         peg_recovery_fee: Option<Decimal>,
         er_threshold: Option<Decimal>,
+        /// Fraction of the underlying staking rewards restaked by the hub
+        compound_ratio: Option<Decimal>,
     },
 
+    /// Create the token factory denom `factory/{hub}/{subdenom}`, which can be bonded for
+    /// instead of the CW20 token
+    CreateNativeToken { subdenom: String },
+
+    /// Allow unbonded coins to be withdrawn through the transfer channel `channel_id`
+    AllowIbcChannel { channel_id: String },
+
+    /// Disallow withdrawals through the transfer channel `channel_id`
+    DisallowIbcChannel { channel_id: String },
+
     ////////////////////
     /// User's operations
     ////////////////////
@@ -78,13 +136,27 @@ pub enum ExecuteMsg {
     /// Issue `amount` / exchange_rate for the user.
     Bond {},
 
+    /// Same as `Bond`, issuing the native token instead of the CW20 token
+    BondNative {},
+
+    /// Unbond the native token sent along
+    UnbondNative {},
+
     /// Update global index
     UpdateGlobalIndex {
         //airdrop_hooks: Option<Vec<Binary>>,
     },
 
-    /// Send back unbonded coin to the user
-    WithdrawUnbonded {},
+    /// Send back unbonded coin to `recipient`, the user by default, or transfer it to
+    /// another chain. Only `batch_ids` are withdrawn if set, all the released batches otherwise
+    WithdrawUnbonded {
+        #[serde(default)]
+        recipient: Option<String>,
+        #[serde(default)]
+        batch_ids: Option<Vec<u64>>,
+        #[serde(default)]
+        ibc_transfer: Option<IbcTransfer>,
+    },
 
     /// Check whether the slashing has happened or not
     CheckSlashing {},
@@ -106,6 +178,21 @@ pub enum ExecuteMsg {
         src_validator: String,
         redelegations: Vec<(String, Coin)>, //(dst_validator, amount)
     },
+
+    /// Restake the compounded share of the withdrawn rewards and send the rest to the reward
+    /// contract. `prev_balances` are the hub balances before the withdrawal
+    CompoundRewards { prev_balances: Vec<Coin> },
+
+    /// Record the exchange rate snapshot of UpdateGlobalIndex, once the rewards are compounded
+    /// and the global index of the reward contract is updated
+    StoreExchangeRateSnapshot {},
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SudoMsg {
+    /// Completion of a withdrawal transferred over IBC
+    IbcLifecycleComplete(IbcLifecycleComplete),
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -125,12 +212,39 @@ pub enum QueryMsg {
         start_from: Option<u64>,
         limit: Option<u32>,
     },
+    PendingOwner {},
+    PauseStatus {},
+    ExchangeRateHistory {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    Apr {
+        window_seconds: u64,
+    },
+    NativeToken {},
+    IbcChannels {},
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema, Display)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum PausableOperation {
+    Bond,
+    Unbond,
+    Withdraw,
+    UpdateGlobalIndex,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum Cw20HookMsg {
     Unbond {},
+
+    /// Sell the tokens on the best priced liquidity pool and send the underlying coins
+    /// to the sender right away. Fails if less than `min_receive` would be received.
+    InstantUnbond {
+        min_receive: Uint128,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -162,6 +276,22 @@ pub struct ConfigResponse {
     pub validator_registry_contract: Option<String>,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NativeTokenResponse {
+    pub denom: Option<String>,
+    pub supply: Uint128,
+    pub basset_rate: Decimal,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PauseStatusResponse {
+    pub guardian: Option<String>,
+    pub bond: bool,
+    pub unbond: bool,
+    pub withdraw: bool,
+    pub update_global_index: bool,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct WhitelistedValidatorsResponse {
     pub validators: Vec<String>,
@@ -187,3 +317,27 @@ pub struct UnbondRequestsResponse {
 pub struct AllHistoryResponse {
     pub history: Vec<UnbondHistory>,
 }
+
+/// Exchange rate of the hub and global index of the reward contract at `time`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ExchangeRateSnapshot {
+    pub time: u64,
+    pub exchange_rate: Decimal,
+    pub total_bond_amount: Uint128,
+    pub reward_index: Decimal256,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ExchangeRateHistoryResponse {
+    pub snapshots: Vec<ExchangeRateSnapshot>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AprResponse {
+    pub from: u64,
+    pub to: u64,
+    /// Annualized growth of the exchange rate
+    pub exchange_rate_apr: Decimal,
+    /// Annualized growth of the reward index, in reward denom per bAsset
+    pub annual_rewards_per_token: Decimal256,
+}
@@ -0,0 +1,103 @@
+//! Payouts to other chains over ICS-20 transfers.
+//!
+//! The transfers are sent as `MsgTransfer` rather than `IbcMsg::Transfer`: a failed or timed out
+//! transfer refunds its coins to the contract, and only the ibc hooks middleware tells the
+//! contract which transfer failed, through a callback requested in the memo. `IbcMsg::Transfer`
+//! has no memo, the refunded coins would then be mixed with the balance of the contract instead
+//! of going back to their owner.
+
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{Addr, Coin, Env, Reply, StdError, StdResult, SubMsg, SubMsgResult};
+
+use crate::proto::{encode_bytes, encode_coin, encode_uint64, stargate_msg};
+
+const MSG_TRANSFER: &str = "/ibc.applications.transfer.v1.MsgTransfer";
+
+/// Minimum timeout of a transfer, in seconds
+pub const MIN_TIMEOUT: u64 = 600;
+
+/// Where to transfer a payout on another chain
+#[cw_serde]
+pub struct IbcTransfer {
+    /// Transfer channel on this chain, it must be allowed by the owner
+    pub channel_id: String,
+    /// Receiver on the other chain
+    pub remote_address: String,
+    /// The transfer times out after this many seconds, at least `MIN_TIMEOUT`
+    pub timeout: u64,
+}
+
+/// A transfer waiting for its acknowledgement. `amount` is sent back to `owner` if the
+/// transfer fails
+#[cw_serde]
+pub struct PendingTransfer {
+    pub owner: Addr,
+    pub amount: Coin,
+}
+
+/// Callback of the ibc hooks middleware once a transfer sent by the contract completes
+#[cw_serde]
+pub enum IbcLifecycleComplete {
+    IbcAck {
+        channel: String,
+        sequence: u64,
+        ack: String,
+        success: bool,
+    },
+    IbcTimeout {
+        channel: String,
+        sequence: u64,
+    },
+}
+
+#[cw_serde]
+pub struct IbcChannelsResponse {
+    pub channels: Vec<String>,
+}
+
+/// Transfer `amount` to the receiver of `transfer`, with a callback to the contract on
+/// completion. The submessage is replied to with `reply_id`, to record the sequence of its
+/// packet
+pub fn transfer_msg(env: &Env, transfer: IbcTransfer, amount: &Coin, reply_id: u64) -> SubMsg {
+    let contract = env.contract.address.as_str();
+    let timeout = env.block.time.plus_seconds(transfer.timeout).nanos();
+    let memo = format!("{{\"ibc_callback\":\"{}\"}}", contract);
+
+    let mut value = vec![];
+    encode_bytes(&mut value, 1, b"transfer");
+    encode_bytes(&mut value, 2, transfer.channel_id.as_bytes());
+    encode_bytes(&mut value, 3, &encode_coin(amount));
+    encode_bytes(&mut value, 4, contract.as_bytes());
+    encode_bytes(&mut value, 5, transfer.remote_address.as_bytes());
+    encode_uint64(&mut value, 7, timeout);
+    encode_bytes(&mut value, 8, memo.as_bytes());
+
+    SubMsg::reply_on_success(stargate_msg(MSG_TRANSFER, value), reply_id)
+}
+
+/// Channel and sequence of the packet sent by a transfer submessage
+pub fn transfer_packet(msg: Reply) -> StdResult<(String, u64)> {
+    let response = match msg.result {
+        SubMsgResult::Ok(response) => response,
+        SubMsgResult::Err(error) => return Err(StdError::generic_err(error)),
+    };
+    let event = response
+        .events
+        .iter()
+        .find(|event| event.ty == "send_packet")
+        .ok_or_else(|| StdError::generic_err("No packet was sent by the transfer"))?;
+    let attribute = |key: &str| {
+        event
+            .attributes
+            .iter()
+            .find(|attribute| attribute.key == key)
+            .map(|attribute| attribute.value.clone())
+            .ok_or_else(|| StdError::generic_err(format!("No {} in the sent packet", key)))
+    };
+
+    let channel = attribute("packet_src_channel")?;
+    let sequence = attribute("packet_sequence")?
+        .parse::<u64>()
+        .map_err(|_| StdError::generic_err("Invalid packet sequence"))?;
+    Ok((channel, sequence))
+}
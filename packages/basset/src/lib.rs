@@ -1,7 +1,11 @@
 pub mod contract_error;
 pub mod dex_router;
 pub mod hub;
+pub mod ibc;
+pub mod ownership;
+mod proto;
 pub mod reward;
+pub mod token_factory;
 
 #[cfg(test)]
 mod testing;
@@ -0,0 +1,49 @@
+use cosmwasm_std::{Addr, OverflowError, OverflowOperation};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Pending ownership transfer. The proposed owner must accept it before it takes effect.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OwnershipProposal {
+    pub owner: Addr,
+    /// Block time (in seconds) after which the proposal can no longer be accepted
+    pub expiry: Option<u64>,
+}
+
+impl OwnershipProposal {
+    /// Block time at which a proposal made at `block_time` expires, `expires_in` seconds later
+    pub fn expiry(block_time: u64, expires_in: Option<u64>) -> Result<Option<u64>, OverflowError> {
+        expires_in
+            .map(|expires_in| {
+                block_time.checked_add(expires_in).ok_or_else(|| {
+                    OverflowError::new(OverflowOperation::Add, block_time, expires_in)
+                })
+            })
+            .transpose()
+    }
+
+    pub fn is_expired(&self, block_time: u64) -> bool {
+        matches!(self.expiry, Some(expiry) if block_time >= expiry)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingOwnerResponse {
+    pub pending_owner: Option<String>,
+    pub expiry: Option<u64>,
+}
+
+impl From<Option<OwnershipProposal>> for PendingOwnerResponse {
+    fn from(proposal: Option<OwnershipProposal>) -> Self {
+        match proposal {
+            Some(p) => PendingOwnerResponse {
+                pending_owner: Some(p.owner.to_string()),
+                expiry: p.expiry,
+            },
+            None => PendingOwnerResponse {
+                pending_owner: None,
+                expiry: None,
+            },
+        }
+    }
+}
@@ -0,0 +1,50 @@
+//! Hand written protobuf encoding of the stargate messages
+
+use cosmwasm_std::{Binary, Coin, CosmosMsg};
+
+pub(crate) fn stargate_msg(type_url: &str, value: Vec<u8>) -> CosmosMsg {
+    CosmosMsg::Stargate {
+        type_url: type_url.to_string(),
+        value: Binary::from(value),
+    }
+}
+
+pub(crate) fn encode_coin(coin: &Coin) -> Vec<u8> {
+    let mut value = vec![];
+    encode_bytes(&mut value, 1, coin.denom.as_bytes());
+    encode_bytes(&mut value, 2, coin.amount.to_string().as_bytes());
+    value
+}
+
+/// Append a length-delimited field
+pub(crate) fn encode_bytes(buf: &mut Vec<u8>, field: u64, bytes: &[u8]) {
+    encode_varint(buf, field << 3 | 2);
+    encode_varint(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+pub(crate) fn encode_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+/// Append a varint field
+pub(crate) fn encode_uint64(buf: &mut Vec<u8>, field: u64, value: u64) {
+    encode_varint(buf, field << 3);
+    encode_varint(buf, value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn proper_varint_encoding() {
+        let mut buf = vec![];
+        encode_varint(&mut buf, 300);
+        assert_eq!(buf, vec![0xac, 0x02]);
+    }
+}
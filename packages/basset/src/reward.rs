@@ -1,15 +1,27 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::{Decimal256, Uint128};
+use cosmwasm_std::{Decimal, Decimal256, Uint128};
+
+use crate::dex_router::{OperationFlavour, SwapOperation};
+use crate::ibc::{IbcLifecycleComplete, IbcTransfer};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct InstantiateMsg {
     pub hub_contract: String,
     pub reward_denom: String,
-    pub astroport_addr: String,
-    pub phoenix_addr: String,
-    pub terraswap_addr: String,
+    /// Dexes the rewards are swapped on
+    pub dexes: Vec<DexInfo>,
+    /// Max spread of the reward swaps, no limit if not set
+    pub max_spread: Option<Decimal>,
+    /// Tolerance applied to the simulated output of the reward swaps, 1% if not set
+    pub slippage_tolerance: Option<Decimal>,
+    /// Number of chunks the reward swaps are split in across the dexes, no split if not set
+    pub split_chunks: Option<u32>,
+    /// Share of the rewards sent to the fee collector, in basis points, at most 3000. No fee if
+    /// not set
+    pub protocol_fee_bps: Option<u64>,
+    pub fee_collector: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -25,6 +37,76 @@ pub enum ExecuteMsg {
     /// Update the global index
     UpdateGlobalIndex {},
 
+    /// Send the protocol fee taken by the hub on the compounded rewards to the fee collector
+    CollectCompoundProtocolFee {},
+
+    /// Propose a new owner. The ownership is only transferred once the new owner accepts it.
+    /// `expires_in` is the number of seconds the proposal stays valid for.
+    ProposeNewOwner {
+        new_owner: String,
+        expires_in: Option<u64>,
+    },
+
+    /// Cancel the pending ownership transfer
+    CancelOwnershipTransfer {},
+
+    /// Accept the pending ownership transfer. Only the proposed owner can execute it.
+    AcceptOwnership {},
+
+    /// Update the slippage protection and the split mode of the reward swaps. The fields that
+    /// are not set are kept
+    UpdateSwapConfig {
+        /// A max spread of 1 or more removes the limit
+        max_spread: Option<Decimal>,
+        slippage_tolerance: Option<Decimal>,
+        /// A single chunk turns the split mode off
+        split_chunks: Option<u32>,
+    },
+
+    /// Update the protocol fee taken on the rewards, in basis points (at most 3000), and its
+    /// collector
+    UpdateFeeConfig {
+        protocol_fee_bps: u64,
+        fee_collector: Option<String>,
+    },
+
+    /// Set the swap operations used to swap `offer_denom` to the reward denom on `dex`,
+    /// instead of the direct swap
+    SetSwapRoute {
+        offer_denom: String,
+        dex: String,
+        operations: Vec<SwapOperation>,
+    },
+
+    /// Remove the swap route of `offer_denom` on `dex`, falling back to the direct swap
+    ClearSwapRoute { offer_denom: String, dex: String },
+
+    /// Register a dex the rewards can be swapped on
+    AddDex { dex: DexInfo },
+
+    /// Unregister a dex, along with its swap routes
+    RemoveDex { name: String },
+
+    /// Enable or disable a dex. Disabled dexes are not used for the swaps
+    UpdateDex { name: String, enabled: bool },
+
+    /// Allow or deny the swap of `denom`, and set the minimum balance of it worth a swap.
+    /// Once a denom is allowed, only the allowed denoms are swapped
+    SetSwapDenom {
+        denom: String,
+        listing: Option<DenomListing>,
+        min_amount: Option<Uint128>,
+    },
+
+    /// Remove the swap settings of `denom`
+    ClearSwapDenom { denom: String },
+
+    /// Allow rewards to be claimed through the transfer channel `channel_id`
+    AllowIbcChannel { channel_id: String },
+
+    /// Disallow claims through the transfer channel `channel_id`
+    DisallowIbcChannel { channel_id: String },
+
     ////////////////////
     /// bAsset's operations
     ///////////////////
@@ -42,8 +124,42 @@ pub enum ExecuteMsg {
     /// User's operations
     ///////////////////
 
-    /// return the accrued reward in uusd to the user.
-    ClaimRewards { recipient: Option<String> },
+    /// return the accrued reward in uusd to the user, or transfer it to another chain.
+    ClaimRewards {
+        recipient: Option<String>,
+        #[serde(default)]
+        ibc_transfer: Option<IbcTransfer>,
+    },
+
+    /// Claim the rewards of `holders`, who must have approved the sender as their claimer.
+    /// The rewards of each holder are sent to the recipient of its approval
+    ClaimRewardsFor { holders: Vec<String> },
+
+    /// Allow `claimer` to claim the rewards of the sender to `recipient`, the sender by default
+    ApproveClaimer {
+        claimer: String,
+        recipient: Option<String>,
+    },
+
+    /// Revoke the approval of `claimer`
+    RevokeClaimer { claimer: String },
+
+    /// Pay out the accrued rewards of the sender to `recipient`, the sender by default,
+    /// whenever its CW20 balance changes. It doesn't apply to the native token of the hub,
+    /// whose transfers don't go through the reward contract and whose holders have no rewards
+    /// to claim
+    SetAutoClaim {
+        enabled: bool,
+        recipient: Option<String>,
+    },
+}
+
+/// Calls of the ibc hooks middleware once a transfer completes
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SudoMsg {
+    /// Completion of rewards transferred over IBC
+    IbcLifecycleComplete(IbcLifecycleComplete),
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -61,12 +177,139 @@ pub enum QueryMsg {
         start_after: Option<String>,
         limit: Option<u32>,
     },
+    PendingOwner {},
+    Dexes {},
+    SwapRoutes {},
+    SwapDenoms {},
+    FailedSwaps {},
+    Claimers {
+        holder: String,
+    },
+    AutoClaim {
+        address: String,
+    },
+    IndexHistory {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    RewardsForPeriod {
+        balance: Uint128,
+        start: u64,
+        end: u64,
+    },
+    IbcChannels {},
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct ConfigResponse {
+    pub owner: String,
     pub hub_contract: String,
     pub reward_denom: String,
+    pub max_spread: Option<Decimal>,
+    pub slippage_tolerance: Decimal,
+    pub split_chunks: Option<u32>,
+    pub protocol_fee_bps: u64,
+    pub fee_collector: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DexInfo {
+    pub name: String,
+    pub router: String,
+    pub flavour: OperationFlavour,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DexResponse {
+    pub name: String,
+    pub router: String,
+    pub flavour: OperationFlavour,
+    pub enabled: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DexesResponse {
+    pub dexes: Vec<DexResponse>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SwapRouteResponse {
+    pub offer_denom: String,
+    pub dex: String,
+    pub operations: Vec<SwapOperation>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SwapRoutesResponse {
+    pub routes: Vec<SwapRouteResponse>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DenomListing {
+    Allowed,
+    Denied,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SwapDenomResponse {
+    pub denom: String,
+    pub listing: Option<DenomListing>,
+    pub min_amount: Option<Uint128>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SwapDenomsResponse {
+    pub denoms: Vec<SwapDenomResponse>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FailedSwapResponse {
+    pub denom: String,
+    pub amount: Uint128,
+    pub error: String,
+    pub failed_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FailedSwapsResponse {
+    pub failed_swaps: Vec<FailedSwapResponse>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ClaimerResponse {
+    pub claimer: String,
+    pub recipient: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ClaimersResponse {
+    pub claimers: Vec<ClaimerResponse>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AutoClaimResponse {
+    pub recipient: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct IndexUpdateResponse {
+    pub time: u64,
+    pub claimed_rewards: Uint128,
+    pub total_balance: Uint128,
+    pub global_index: Decimal256,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct IndexHistoryResponse {
+    pub updates: Vec<IndexUpdateResponse>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RewardsForPeriodResponse {
+    pub rewards: Uint128,
+    pub start_index: Decimal256,
+    pub end_index: Decimal256,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -74,6 +317,8 @@ pub struct StateResponse {
     pub global_index: Decimal256,
     pub total_balance: Uint128,
     pub prev_reward_balance: Uint128,
+    pub total_protocol_fee: Uint128,
+    pub total_compound_protocol_fee: Uint128,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -95,4 +340,7 @@ pub struct HoldersResponse {
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
-pub struct MigrateMsg {}
+pub struct MigrateMsg {
+    /// Owner of a config migrated from before the owner was introduced, ignored otherwise
+    pub owner: Option<String>,
+}
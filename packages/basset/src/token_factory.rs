@@ -0,0 +1,63 @@
+//! Messages of the token factory module. They are sent as stargate messages, so they are
+//! encoded to protobuf by hand.
+
+use cosmwasm_std::{Coin, CosmosMsg};
+
+use crate::proto::{encode_bytes, encode_coin, stargate_msg};
+
+const MSG_CREATE_DENOM: &str = "/osmosis.tokenfactory.v1beta1.MsgCreateDenom";
+const MSG_MINT: &str = "/osmosis.tokenfactory.v1beta1.MsgMint";
+const MSG_BURN: &str = "/osmosis.tokenfactory.v1beta1.MsgBurn";
+
+/// Full denom of the token `creator` creates with `subdenom`
+pub fn native_denom(creator: &str, subdenom: &str) -> String {
+    format!("factory/{}/{}", creator, subdenom)
+}
+
+/// Create the `factory/{sender}/{subdenom}` denom, administrated by `sender`
+pub fn create_denom_msg(sender: &str, subdenom: &str) -> CosmosMsg {
+    let mut value = vec![];
+    encode_bytes(&mut value, 1, sender.as_bytes());
+    encode_bytes(&mut value, 2, subdenom.as_bytes());
+    stargate_msg(MSG_CREATE_DENOM, value)
+}
+
+/// Mint `amount` to `sender`, the admin of the denom
+pub fn mint_msg(sender: &str, amount: &Coin) -> CosmosMsg {
+    let mut value = vec![];
+    encode_bytes(&mut value, 1, sender.as_bytes());
+    encode_bytes(&mut value, 2, &encode_coin(amount));
+    stargate_msg(MSG_MINT, value)
+}
+
+/// Burn `amount` from the balance of `sender`, the admin of the denom
+pub fn burn_msg(sender: &str, amount: &Coin) -> CosmosMsg {
+    let mut value = vec![];
+    encode_bytes(&mut value, 1, sender.as_bytes());
+    encode_bytes(&mut value, 2, &encode_coin(amount));
+    stargate_msg(MSG_BURN, value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::Binary;
+
+    #[test]
+    fn proper_mint_encoding() {
+        let msg = mint_msg("hub", &Coin::new(150, "factory/hub/ualuna"));
+        let mut expected = vec![0x0a, 3];
+        expected.extend_from_slice(b"hub");
+        expected.extend_from_slice(&[0x12, 25, 0x0a, 18]);
+        expected.extend_from_slice(b"factory/hub/ualuna");
+        expected.extend_from_slice(&[0x12, 3]);
+        expected.extend_from_slice(b"150");
+        assert_eq!(
+            msg,
+            CosmosMsg::Stargate {
+                type_url: MSG_MINT.to_string(),
+                value: Binary::from(expected),
+            }
+        );
+    }
+}